@@ -0,0 +1,86 @@
+//! info line parser throughput
+//!
+//! set UCIENGINE_BENCH_LOG to a recorded engine search to benchmark real engine output,
+//! either a transcript of the engine recorder ( e.g. of `uciproxy --transcript search.jsonl` )
+//! or raw engine output, otherwise benches/data/info_lines.txt is used, a synthetic transcript
+//! in the format of stockfish output ( iterations with full pv lines, dominated by currmove
+//! lines from depth 10 on, pv moves are not legal moves )
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+
+use uciengine::analysis::{AnalysisInfo, ParserConfig};
+use uciengine::transcript::{read_transcript, Direction};
+
+const INFO_LINES: &str = include_str!("data/info_lines.txt");
+
+/// engine lines of recorded search log if set, bundled synthetic lines otherwise
+fn engine_lines() -> Vec<String> {
+    let text = match std::env::var("UCIENGINE_BENCH_LOG") {
+        Ok(path) => std::fs::read_to_string(&path)
+            .unwrap_or_else(|err| panic!("reading bench log {} failed {:?}", path, err)),
+        _ => return INFO_LINES.lines().map(|line| line.to_string()).collect(),
+    };
+
+    // transcripts are jsonl, raw engine output is taken as it is
+    if !text.trim_start().starts_with('{') {
+        return text.lines().map(|line| line.to_string()).collect();
+    }
+
+    read_transcript(&text)
+        .unwrap_or_else(|err| panic!("invalid bench transcript {:?}", err))
+        .into_iter()
+        .filter(|entry| entry.dir == Direction::FromEngine)
+        .map(|entry| entry.line)
+        .collect()
+}
+
+fn parse_lines(c: &mut Criterion) {
+    let engine_lines = engine_lines();
+
+    let lines: Vec<&str> = engine_lines
+        .iter()
+        .map(|line| line.as_str())
+        .filter(|line| line.starts_with("info"))
+        .collect();
+
+    let config = ParserConfig::new();
+
+    let mut group = c.benchmark_group("parse");
+
+    group.throughput(Throughput::Elements(lines.len() as u64));
+
+    group.bench_function("info_lines", |b| {
+        b.iter(|| {
+            let mut ai = AnalysisInfo::new();
+
+            for line in &lines {
+                let _ = ai.parse_with(&config, black_box(line));
+            }
+
+            ai
+        })
+    });
+
+    group.throughput(Throughput::Elements(1));
+
+    group.bench_function("pv_line", |b| {
+        let line = lines
+            .iter()
+            .rev()
+            .find(|line| line.contains(" pv "))
+            .unwrap();
+
+        b.iter(|| {
+            let mut ai = AnalysisInfo::new();
+
+            let _ = ai.parse_with(&config, black_box(line));
+
+            ai
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, parse_lines);
+criterion_main!(benches);
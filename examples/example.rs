@@ -45,11 +45,12 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // wait enough for the go commands to complete in the background
     tokio::time::sleep(tokio::time::Duration::from_millis(20000)).await;
 
-    // quit engine
-    engine.quit();
+    // quit engine, kill it if it does not exit within 3 seconds
+    let shutdown_status = engine
+        .shutdown(tokio::time::Duration::from_millis(3000))
+        .await;
 
-    // wait for engine to quit gracefully
-    tokio::time::sleep(tokio::time::Duration::from_millis(3000)).await;
+    println!("shutdown status {:?}", shutdown_status);
 
     Ok(())
 }
@@ -10,7 +10,6 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .uci_opt("UCI_Variant", "chess")
         .pos_startpos()
         .pos_moves("e2e4 e7e5")
-        .ponder()
         .tc(Timecontrol {
             wtime: 15000,
             winc: 0,
@@ -20,17 +19,19 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let engine = UciEngine::new("stockfish12.exe");
 
-    // start engine detached
-    let _ = engine.go(go_job);
+    // start ponder search
+    let ponder_handle = engine.go_ponder(go_job);
 
     // do something in the meanwhile
     println!("doing something");
 
     // issue ponderhit
-    let result = engine.go(GoJob::new().ponderhit()).await;
+    let result = ponder_handle.hit().await;
 
-    // issue pondermiss
-    //let result = engine.go(GoJob::new().pondermiss()).recv().await;
+    // or issue pondermiss and search the move actually played
+    //let result = ponder_handle
+    //    .miss(GoJob::new().pos_startpos().pos_moves("e2e4 c7c5"))
+    //    .await;
 
     println!("{:?}", result);
 
@@ -0,0 +1,129 @@
+extern crate env_logger;
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use shakmaty::{CastlingMode, Chess, Position as _};
+
+use uciengine::analysis::AnalysisInfo;
+use uciengine::engineimpl::*;
+use uciengine::protocol::*;
+
+/// engine playing random legal moves, searches in a thread so that stop works
+struct RandomMover {
+    pos: Chess,
+    seed: u64,
+    stop: Arc<AtomicBool>,
+    search: Option<std::thread::JoinHandle<()>>,
+}
+
+impl RandomMover {
+    /// wait for running search to send bestmove
+    fn join(&mut self) {
+        if let Some(search) = self.search.take() {
+            let _ = search.join();
+        }
+    }
+}
+
+impl UciEngineImpl for RandomMover {
+    fn name(&self) -> String {
+        "RandomMover".to_string()
+    }
+
+    fn author(&self) -> String {
+        "uciengine example".to_string()
+    }
+
+    fn options(&self) -> Vec<UciOption> {
+        vec![UciOption {
+            name: "Seed".to_string(),
+            option_type: OptionType::Spin {
+                default: 1,
+                min: 1,
+                max: 1_000_000,
+            },
+        }]
+    }
+
+    fn set_option(&mut self, name: &str, value: Option<&str>) -> Result<(), String> {
+        match name {
+            "Seed" => {
+                self.seed = value
+                    .and_then(|value| value.parse().ok())
+                    .ok_or("invalid seed")?
+            }
+            _ => return Err("unknown option".to_string()),
+        }
+
+        Ok(())
+    }
+
+    fn set_position(&mut self, position: &Position, moves: &[String]) {
+        self.join();
+
+        self.pos = position.to_chess(moves).unwrap_or_default();
+    }
+
+    fn go(&mut self, params: &GoParams, output: &UciOutput) {
+        self.join();
+
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 7;
+        self.seed ^= self.seed << 17;
+
+        let moves = self.pos.legal_moves();
+
+        let bestmove = match moves.len() {
+            0 => "(none)".to_string(),
+            len => moves[(self.seed % len as u64) as usize]
+                .to_uci(CastlingMode::Standard)
+                .to_string(),
+        };
+
+        // pretend to think for movetime, or until stopped when searching infinite
+        let think = Duration::from_millis(params.movetime.unwrap_or(100));
+        let infinite = params.infinite || params.ponder;
+
+        self.stop.store(false, Ordering::SeqCst);
+
+        let stop = self.stop.clone();
+        let output = output.clone();
+
+        self.search = Some(std::thread::spawn(move || {
+            let mut ai = AnalysisInfo::new();
+
+            ai.depth = 1;
+            ai.set_pv(&bestmove);
+
+            output.info(&ai);
+
+            let start = std::time::Instant::now();
+
+            while !stop.load(Ordering::SeqCst) && (infinite || start.elapsed() < think) {
+                std::thread::sleep(Duration::from_millis(5));
+            }
+
+            output.bestmove(bestmove, None);
+        }));
+    }
+
+    fn stop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+
+        self.join();
+    }
+}
+
+fn main() -> std::io::Result<()> {
+    env_logger::init();
+
+    UciDriver::new(RandomMover {
+        pos: Chess::default(),
+        seed: 1,
+        stop: Arc::new(AtomicBool::new(false)),
+        search: None,
+    })
+    .run_stdio()
+}
@@ -0,0 +1,21 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+use uciengine::analysis::{AnalysisInfo, ParserConfig, UnknownKeyPolicy};
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(line) = std::str::from_utf8(data) {
+        let lenient = ParserConfig::new()
+            .unknown_key(UnknownKeyPolicy::Skip)
+            .strict(false);
+
+        for config in &[ParserConfig::new(), lenient] {
+            let mut ai = AnalysisInfo::new();
+
+            let _ = ai.parse_with(config, line);
+
+            let _ = (ai.pv(), ai.bestmove(), ai.ponder(), ai.currmove());
+        }
+    }
+});
@@ -4,6 +4,8 @@ use envor::envor::env_true;
 
 use serde::{Deserialize, Serialize};
 
+use std::collections::HashMap;
+
 use thiserror::Error;
 
 /// InfoParseError captures possible info parsing errors
@@ -68,12 +70,18 @@ macro_rules! gen_str_buff {
 			#[$attr]
 			#[doc = "( value will be trimmed to buffer size )"]
 			pub fn set<T: AsRef<str>>(&mut self, value: T) -> Self {
-				let bytes = value.as_ref().as_bytes();
+				let value = value.as_ref();
+				let bytes = value.as_bytes();
 
 				let mut len = bytes.len();
 
 				if len > $size{
 					len = $size;
+
+					// do not cut multi byte characters
+					while !value.is_char_boundary(len) {
+						len -= 1;
+					}
 				}
 
 				self.len = len;
@@ -92,44 +100,43 @@ macro_rules! gen_str_buff {
 				*self
 			}
 
+			#[doc = "set"]
+			#[$attr]
+			#[doc = "( value that does not fit is cut at the last trim character that leaves a fitting prefix )"]
 			pub fn set_trim<T: AsRef<str>>(&mut self, value: T, trim: char) -> Self {
-				let value_ref = value.as_ref();
-				let value_string = value_ref.to_string();
-				let bytes = value_ref.as_bytes();
+				let value = value.as_ref();
 
-				let mut total_len = value_string.len();
+				let mut len = value.len();
 
-			    value_ref.to_string().chars().rev().take_while(|c| {
-			        total_len -= 1;
-			        ( *c != trim ) || ( total_len > $size )
-			    }).collect::<String>().len();
+				if len > $size {
+					len = value
+						.char_indices()
+						.rev()
+						.find(|(index, c)| ( *c == trim ) && ( *index <= $size ))
+						.map_or(0, |(index, _)| index);
+				}
 
-			    self.len = total_len;
+				self.len = len;
 
-			    self.buff[0..total_len].copy_from_slice(&bytes[0..total_len]);
+				self.buff[0..len].copy_from_slice(&value.as_bytes()[0..len]);
 
 				*self
 			}
 		}
 
+		#[doc = "implement Default for"]
+		#[$attr]
+		impl Default for $type {
+			fn default() -> Self {
+				Self::new()
+			}
+		}
+
 		#[doc = "implement From<&str> for"]
 		#[$attr]
 		impl std::convert::From<&str> for $type {
 			fn from(value: &str) -> Self {
-				let bytes = value.as_bytes();
-
-				let mut len = bytes.len();
-
-				if len > $size{
-					len = $size;
-				}
-
-				let mut buff = $type::new();
-
-                buff.len = len;
-				buff.buff[0..len].copy_from_slice(&bytes[0..len]);
-
-				buff
+				$type::new().set(value)
 			}
 		}
 
@@ -196,7 +203,7 @@ gen_str_buff!(
 );
 
 /// score
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum Score {
     /// centipawn
     Cp(i32),
@@ -205,7 +212,7 @@ pub enum Score {
 }
 
 /// score type
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum ScoreType {
     /// exact
     Exact,
@@ -362,8 +369,144 @@ pub struct AnalysisInfoSerde {
     pub scoretype: ScoreType,
 }
 
+/// policy for info keys unknown to the parser
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum UnknownKeyPolicy {
+    /// fail with invalid key error
+    Reject,
+    /// skip the key together with as many arguments as set in the arity table ( default 1 )
+    Skip,
+}
+
+/// info parser configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ParserConfig {
+    /// policy for unknown info keys
+    pub unknown_key: UnknownKeyPolicy,
+    /// number of arguments of unknown keys that are skipped, usize::MAX skips the rest of the line
+    pub arities: HashMap<String, usize>,
+    /// strict parser fails on malformed values, lenient parser skips them and goes on
+    pub strict: bool,
+    /// collect parse statistics in the engine
+    pub collect_stats: bool,
+}
+
+/// default parser configuration
+impl Default for ParserConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// parser configuration implementation
+impl ParserConfig {
+    /// create new strict parser configuration rejecting unknown keys, without statistics
+    pub fn new() -> Self {
+        Self {
+            unknown_key: UnknownKeyPolicy::Reject,
+            arities: HashMap::new(),
+            strict: true,
+            collect_stats: false,
+        }
+    }
+
+    /// create parser configuration from the legacy environment variables
+    /// ALLOW_UNKNOWN_INFO_KEY ( skip unknown keys ) and TEST_PARSE_INFO ( collect statistics )
+    pub fn from_env() -> Self {
+        let mut config = Self::new().collect_stats(env_true("TEST_PARSE_INFO"));
+
+        if env_true("ALLOW_UNKNOWN_INFO_KEY") {
+            config = config.unknown_key(UnknownKeyPolicy::Skip);
+        }
+
+        config
+    }
+
+    /// parser configuration of the constructors that take none, for one more release
+    /// this is the configuration from the legacy environment variables, with a warning
+    /// when they are set
+    pub(crate) fn implicit() -> Self {
+        static WARNED: std::sync::Once = std::sync::Once::new();
+
+        let legacy: Vec<&str> = ["ALLOW_UNKNOWN_INFO_KEY", "TEST_PARSE_INFO"]
+            .iter()
+            .copied()
+            .filter(|name| std::env::var_os(name).is_some())
+            .collect();
+
+        if !legacy.is_empty() {
+            WARNED.call_once(|| {
+                warn!(
+                    "{} will no longer be read implicitly in the next release, \
+                     pass ParserConfig::from_env() to spawn_with_config instead",
+                    legacy.join(" and ")
+                )
+            });
+        }
+
+        Self::from_env()
+    }
+
+    /// set unknown key policy and return self
+    pub fn unknown_key(mut self, policy: UnknownKeyPolicy) -> Self {
+        self.unknown_key = policy;
+
+        self
+    }
+
+    /// set number of arguments of unknown key and return self
+    pub fn arity<T: core::fmt::Display>(mut self, key: T, arity: usize) -> Self {
+        self.arities.insert(format!("{}", key), arity);
+
+        self
+    }
+
+    /// set strictness and return self
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+
+        self
+    }
+
+    /// set statistics collection and return self
+    pub fn collect_stats(mut self, collect_stats: bool) -> Self {
+        self.collect_stats = collect_stats;
+
+        self
+    }
+}
+
+/// engine output parse statistics
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ParseStats {
+    /// number of lines read
+    pub lines: usize,
+    /// number of lines parsed without error
+    pub ok: usize,
+    /// number of lines that failed to parse
+    pub failed: usize,
+    /// last parse error
+    pub last_error: Option<String>,
+}
+
+/// parse statistics implementation
+impl ParseStats {
+    /// record parse result of a line
+    pub fn record(&mut self, parse_result: &Result<(), InfoParseError>) {
+        self.lines += 1;
+
+        match parse_result {
+            Ok(_) => self.ok += 1,
+            Err(err) => {
+                self.failed += 1;
+                self.last_error = Some(err.to_string());
+            }
+        }
+    }
+}
+
 /// parsing state
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 #[allow(dead_code)]
 // TODO: make this pub(crate)
 pub enum ParsingState {
@@ -389,6 +532,13 @@ pub enum ParsingState {
     PvRest,
 }
 
+/// default analysis info
+impl Default for AnalysisInfo {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// analysis info implementation
 impl AnalysisInfo {
     /// create new analysis info
@@ -494,204 +644,240 @@ impl AnalysisInfo {
         self.currmove.to_opt()
     }
 
-    /// parse info string
+    /// set pv, bestmove and ponder are set from the first two moves of the pv
+    pub fn set_pv<T: std::convert::AsRef<str>>(&mut self, pv: T) {
+        let pv = pv.as_ref().trim();
+
+        let mut moves = pv.split_whitespace();
+
+        self.bestmove = UciBuff::from(moves.next().unwrap_or(""));
+        self.ponder = UciBuff::from(moves.next().unwrap_or(""));
+
+        self.pv.set_trim(pv, ' ');
+    }
+
+    /// parse info string with default parser configuration
     pub fn parse<T: std::convert::AsRef<str>>(&mut self, info: T) -> Result<(), InfoParseError> {
-        let info = info.as_ref();
-        let mut ps = ParsingState::Info;
-        let mut pv_buff = String::new();
-        let mut pv_on = false;
+        self.parse_with(&ParserConfig::default(), info)
+    }
 
-        let allow_unknown_key = env_true("ALLOW_UNKNOWN_INFO_KEY");
+    /// parse info string with parser configuration
+    pub fn parse_with<T: std::convert::AsRef<str>>(
+        &mut self,
+        config: &ParserConfig,
+        info: T,
+    ) -> Result<(), InfoParseError> {
+        let info = info.as_ref();
 
-        for token in info.split(" ") {
-            match ps {
-                ParsingState::Info => {
-                    match token {
-                        "info" => ps = ParsingState::Key,
-                        _ => {
-                            // not an info
-                            return Ok(());
-                        }
+        let mut parser = InfoParser {
+            config,
+            info,
+            ps: ParsingState::Info,
+            pv_start: None,
+            skip: 0,
+        };
+
+        for token in info.split(' ') {
+            match parser.token(self, token) {
+                Ok(true) => {}
+                Ok(false) => break,
+                Err(err) => {
+                    if config.strict {
+                        error!("{:?}", err);
+
+                        return Err(err);
                     }
+
+                    // lenient parser skips malformed value and goes on with next key
+                    warn!("skipping malformed info value {:?}", err);
+
+                    parser.ps = ParsingState::Key;
                 }
-                ParsingState::Key => {
-                    if (token == "string") || (token == "refutation") || (token == "currline") {
-                        // string, refutation and currline are not supported
-                        return Ok(());
-                    }
+            }
+        }
 
-                    ps = match token {
-                        "lowerbound" => {
-                            self.scoretype = ScoreType::Lowerbound;
+        // pv is borrowed from the line, it runs from the first pv move to the end of the line
+        if let Some(pv_start) = parser.pv_start {
+            self.pv.set_trim(info[pv_start..].trim_end(), ' ');
+        }
 
-                            ParsingState::Key
-                        }
-                        "upperbound" => {
-                            self.scoretype = ScoreType::Upperbound;
+        Ok(())
+    }
+}
 
-                            ParsingState::Key
-                        }
-                        "depth" => ParsingState::Depth,
-                        "seldepth" => ParsingState::Seldepth,
-                        "time" => ParsingState::Time,
-                        "nodes" => ParsingState::Nodes,
-                        "multipv" => ParsingState::Multipv,
-                        "score" => ParsingState::Score,
-                        "currmove" => ParsingState::Currmove,
-                        "currmovenumber" => ParsingState::Currmovenumber,
-                        "hashfull" => ParsingState::Hashfull,
-                        "nps" => ParsingState::Nps,
-                        "tbhits" => ParsingState::Tbhits,
-                        "cpuload" => ParsingState::Cpuload,
-                        "pv" => ParsingState::PvBestmove,
-                        _ => {
-                            if allow_unknown_key {
-                                ParsingState::Unknown
-                            } else {
-                                return Err(InfoParseError::InvalidKeyError(token.to_string()));
-                            }
-                        }
-                    };
+/// info line parser state, borrows from the parsed line and does not allocate
+/// unless an error is returned
+struct InfoParser<'a> {
+    /// configuration
+    config: &'a ParserConfig,
+    /// parsed line
+    info: &'a str,
+    /// parsing state
+    ps: ParsingState,
+    /// byte offset of the first pv move in the line, all tokens from there belong to the pv
+    pv_start: Option<usize>,
+    /// number of unknown key arguments still to be skipped
+    skip: usize,
+}
 
-                    if let ParsingState::Score = ps {
-                        self.scoretype = ScoreType::Exact;
-                    }
-                }
-                ParsingState::Score => match token {
-                    "cp" => ps = ParsingState::ScoreCp,
-                    "mate" => ps = ParsingState::ScoreMate,
-                    "upperbound" => self.scoretype = ScoreType::Upperbound,
-                    "lowerbound" => self.scoretype = ScoreType::Lowerbound,
+/// info parser implementation
+impl<'a> InfoParser<'a> {
+    /// parse number token in current state
+    fn number<N: std::str::FromStr>(&self, token: &str) -> Result<N, InfoParseError> {
+        token
+            .parse::<N>()
+            .map_err(|_| InfoParseError::ParseNumberError(self.ps, token.to_string()))
+    }
+
+    /// parse token into analysis info, returns false if the rest of the line is to be ignored
+    fn token(&mut self, ai: &mut AnalysisInfo, token: &'a str) -> Result<bool, InfoParseError> {
+        match self.ps {
+            ParsingState::Info => {
+                match token {
+                    "info" => self.ps = ParsingState::Key,
                     _ => {
-                        // not a valid score specifier
-                        return info_parse_error(InfoParseError::InvalidScoreSpecifier(
-                            token.to_string(),
-                        ));
+                        // not an info
+                        return Ok(false);
                     }
-                },
-                ParsingState::Unknown => {
-                    // ignore this token and hope for the best ( namely that it had a single token arg )
-                    warn!("unknown info key {}", token);
-
-                    ps = ParsingState::Key
                 }
-                _ => {
-                    let mut keep_state = false;
-
-                    match ps {
-                        ParsingState::Depth => match token.parse::<usize>() {
-                            Ok(depth) => self.depth = depth,
-                            _ => return parse_number_error(ps, token),
-                        },
-                        ParsingState::Seldepth => match token.parse::<usize>() {
-                            Ok(seldepth) => self.seldepth = seldepth,
-                            _ => return parse_number_error(ps, token),
-                        },
-                        ParsingState::Time => match token.parse::<usize>() {
-                            Ok(time) => self.time = time,
-                            _ => return parse_number_error(ps, token),
-                        },
-                        ParsingState::Nodes => match token.parse::<u64>() {
-                            Ok(nodes) => self.nodes = nodes,
-                            _ => return parse_number_error(ps, token),
-                        },
-                        ParsingState::Multipv => match token.parse::<usize>() {
-                            Ok(multipv) => self.multipv = multipv,
-                            _ => return parse_number_error(ps, token),
-                        },
-                        ParsingState::ScoreCp => match token {
-                            "upperbound" => {
-                                self.scoretype = ScoreType::Upperbound;
-
-                                keep_state = true
-                            }
-                            "lowerbound" => {
-                                self.scoretype = ScoreType::Lowerbound;
+            }
+            ParsingState::Key => {
+                if (token == "string") || (token == "refutation") || (token == "currline") {
+                    // string, refutation and currline are not supported
+                    return Ok(false);
+                }
 
-                                keep_state = true
-                            }
-                            _ => match token.parse::<i32>() {
-                                Ok(score_cp) => self.score = Score::Cp(score_cp),
-                                _ => return parse_number_error(ps, token),
-                            },
-                        },
-                        ParsingState::ScoreMate => match token {
-                            "upperbound" => {
-                                self.scoretype = ScoreType::Upperbound;
-
-                                keep_state = true
-                            }
-                            "lowerbound" => {
-                                self.scoretype = ScoreType::Lowerbound;
+                self.ps = match token {
+                    "lowerbound" => {
+                        ai.scoretype = ScoreType::Lowerbound;
+
+                        ParsingState::Key
+                    }
+                    "upperbound" => {
+                        ai.scoretype = ScoreType::Upperbound;
 
-                                keep_state = true
+                        ParsingState::Key
+                    }
+                    "depth" => ParsingState::Depth,
+                    "seldepth" => ParsingState::Seldepth,
+                    "time" => ParsingState::Time,
+                    "nodes" => ParsingState::Nodes,
+                    "multipv" => ParsingState::Multipv,
+                    "score" => ParsingState::Score,
+                    "currmove" => ParsingState::Currmove,
+                    "currmovenumber" => ParsingState::Currmovenumber,
+                    "hashfull" => ParsingState::Hashfull,
+                    "nps" => ParsingState::Nps,
+                    "tbhits" => ParsingState::Tbhits,
+                    "cpuload" => ParsingState::Cpuload,
+                    "pv" => ParsingState::PvBestmove,
+                    _ => match self.config.unknown_key {
+                        UnknownKeyPolicy::Skip => {
+                            warn!("unknown info key {}", token);
+
+                            self.skip = self.config.arities.get(token).copied().unwrap_or(1);
+
+                            if self.skip == 0 {
+                                ParsingState::Key
+                            } else {
+                                ParsingState::Unknown
                             }
-                            _ => match token.parse::<i32>() {
-                                Ok(score_mate) => self.score = Score::Mate(score_mate),
-                                _ => return parse_number_error(ps, token),
-                            },
-                        },
-                        ParsingState::Currmove => {
-                            self.currmove.set(token);
-
-                            ()
                         }
-                        ParsingState::Currmovenumber => match token.parse::<usize>() {
-                            Ok(currmovenumber) => self.currmovenumber = currmovenumber,
-                            _ => return parse_number_error(ps, token),
-                        },
-                        ParsingState::Hashfull => match token.parse::<usize>() {
-                            Ok(hashfull) => self.hashfull = hashfull,
-                            _ => return parse_number_error(ps, token),
-                        },
-                        ParsingState::Nps => match token.parse::<u64>() {
-                            Ok(nps) => self.nps = nps,
-                            _ => return parse_number_error(ps, token),
-                        },
-                        ParsingState::Tbhits => match token.parse::<u64>() {
-                            Ok(tbhits) => self.tbhits = tbhits,
-                            _ => return parse_number_error(ps, token),
-                        },
-                        ParsingState::Cpuload => match token.parse::<usize>() {
-                            Ok(cpuload) => self.cpuload = cpuload,
-                            _ => return parse_number_error(ps, token),
-                        },
-                        ParsingState::PvBestmove => {
-                            pv_buff = pv_buff + token;
-
-                            self.bestmove = UciBuff::from(token);
-
-                            self.ponder.reset();
-
-                            pv_on = true;
-
-                            ps = ParsingState::PvPonder
+                        UnknownKeyPolicy::Reject => {
+                            return Err(InfoParseError::InvalidKeyError(token.to_string()));
                         }
-                        ParsingState::PvPonder => {
-                            pv_buff = pv_buff + " " + token;
+                    },
+                };
+
+                if let ParsingState::Score = self.ps {
+                    ai.scoretype = ScoreType::Exact;
+                }
+            }
+            ParsingState::Score => match token {
+                "cp" => self.ps = ParsingState::ScoreCp,
+                "mate" => self.ps = ParsingState::ScoreMate,
+                "upperbound" => ai.scoretype = ScoreType::Upperbound,
+                "lowerbound" => ai.scoretype = ScoreType::Lowerbound,
+                _ => {
+                    // not a valid score specifier
+                    return Err(InfoParseError::InvalidScoreSpecifier(token.to_string()));
+                }
+            },
+            ParsingState::Unknown => {
+                // skip argument of unknown key
+                self.skip -= 1;
+
+                if self.skip == 0 {
+                    self.ps = ParsingState::Key
+                }
+            }
+            _ => {
+                let mut keep_state = false;
+
+                match self.ps {
+                    ParsingState::Depth => ai.depth = self.number(token)?,
+                    ParsingState::Seldepth => ai.seldepth = self.number(token)?,
+                    ParsingState::Time => ai.time = self.number(token)?,
+                    ParsingState::Nodes => ai.nodes = self.number(token)?,
+                    ParsingState::Multipv => ai.multipv = self.number(token)?,
+                    ParsingState::ScoreCp | ParsingState::ScoreMate => match token {
+                        "upperbound" => {
+                            ai.scoretype = ScoreType::Upperbound;
 
-                            self.ponder = UciBuff::from(token);
+                            keep_state = true
+                        }
+                        "lowerbound" => {
+                            ai.scoretype = ScoreType::Lowerbound;
 
-                            ps = ParsingState::PvRest
+                            keep_state = true
                         }
-                        ParsingState::PvRest => pv_buff = pv_buff + " " + token,
                         _ => {
-                            // should not happen
+                            let score = self.number(token)?;
+
+                            ai.score = match self.ps {
+                                ParsingState::ScoreCp => Score::Cp(score),
+                                _ => Score::Mate(score),
+                            };
                         }
+                    },
+                    ParsingState::Currmove => {
+                        ai.currmove.set(token);
                     }
+                    ParsingState::Currmovenumber => ai.currmovenumber = self.number(token)?,
+                    ParsingState::Hashfull => ai.hashfull = self.number(token)?,
+                    ParsingState::Nps => ai.nps = self.number(token)?,
+                    ParsingState::Tbhits => ai.tbhits = self.number(token)?,
+                    ParsingState::Cpuload => ai.cpuload = self.number(token)?,
+                    ParsingState::PvBestmove => {
+                        // token is a subslice of the line
+                        self.pv_start = Some(token.as_ptr() as usize - self.info.as_ptr() as usize);
 
-                    // anything from key pv onwards should be added to pv
-                    // otherwise switch back to parsing key
-                    if (!pv_on) && (!keep_state) {
-                        ps = ParsingState::Key;
+                        ai.bestmove = UciBuff::from(token);
+
+                        ai.ponder.reset();
+
+                        self.ps = ParsingState::PvPonder
+                    }
+                    ParsingState::PvPonder => {
+                        ai.ponder = UciBuff::from(token);
+
+                        self.ps = ParsingState::PvRest
+                    }
+                    ParsingState::PvRest => {}
+                    _ => {
+                        // should not happen
                     }
                 }
+
+                // anything from key pv onwards should be added to pv
+                // otherwise switch back to parsing key
+                if self.pv_start.is_none() && (!keep_state) {
+                    self.ps = ParsingState::Key;
+                }
             }
         }
 
-        self.pv.set_trim(pv_buff, ' ');
-
-        Ok(())
+        Ok(true)
     }
 }
 
@@ -722,3 +908,111 @@ fn parse_error() {
     assert_eq!(format!("{:?}", ai.score), format!("{:?}", Score::Mate(5)));
     assert_eq!(format!("{:?}", ai.ponder()), format!("{:?}", Some("e7e5")));
 }
+
+#[test]
+fn parser_config() {
+    let mut ai = AnalysisInfo::new();
+
+    assert!(ai.parse("info depth 5 wdl 300 400 300 nodes 100").is_err());
+
+    let config = ParserConfig::new()
+        .unknown_key(UnknownKeyPolicy::Skip)
+        .arity("wdl", 3);
+
+    assert!(ai
+        .parse_with(&config, "info depth 5 wdl 300 400 300 nodes 100")
+        .is_ok());
+    assert_eq!(ai.nodes, 100);
+
+    assert!(ai.parse("info depth x seldepth 7").is_err());
+
+    let config = config.strict(false);
+
+    assert!(ai.parse_with(&config, "info depth x seldepth 7").is_ok());
+    assert_eq!(ai.seldepth, 7);
+
+    let mut stats = ParseStats::default();
+
+    stats.record(&ai.parse("info depth 1"));
+    stats.record(&ai.parse("info foo 1"));
+
+    assert_eq!((stats.lines, stats.ok, stats.failed), (2, 1, 1));
+}
+
+#[test]
+fn parse_malformed_input() {
+    // deterministic fuzzing with tokens of info lines and random garbage
+    let tokens = [
+        "info",
+        "depth",
+        "seldepth",
+        "score",
+        "cp",
+        "mate",
+        "lowerbound",
+        "upperbound",
+        "pv",
+        "currmove",
+        "nodes",
+        "multipv",
+        "string",
+        "-",
+        "0",
+        "-1",
+        "99999999999999999999",
+        "e2e4",
+        "",
+        " ",
+        "é",
+        "ééé",
+        "\u{0}",
+        "wdl",
+        "e7e8q",
+    ];
+
+    let configs = [
+        ParserConfig::new(),
+        ParserConfig::new()
+            .unknown_key(UnknownKeyPolicy::Skip)
+            .arity("wdl", 3)
+            .strict(false),
+    ];
+
+    let mut seed: u64 = 0x2545_f491_4f6c_dd1d;
+
+    let mut next = move || {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+
+        seed
+    };
+
+    let mut ai = AnalysisInfo::new();
+
+    for _ in 0..20000 {
+        let mut line = String::new();
+
+        for _ in 0..(next() % 16) {
+            if next() % 8 == 0 {
+                line.push(std::char::from_u32((next() % 0x800) as u32).unwrap_or('x'));
+            } else {
+                line += tokens[(next() as usize) % tokens.len()];
+            }
+
+            if next() % 6 != 0 {
+                line.push(' ');
+            }
+        }
+
+        for config in &configs {
+            let _ = ai.parse_with(config, &line);
+
+            let _ = (ai.pv(), ai.bestmove(), ai.ponder(), ai.currmove());
+        }
+    }
+
+    ai.parse("info depth 1 pv e2e4").unwrap();
+
+    assert_eq!(ai.pv(), Some("e2e4".to_string()));
+}
@@ -0,0 +1,300 @@
+use log::{debug, log_enabled, Level};
+
+use shakmaty::{Chess, Color, Position};
+
+use thiserror::Error;
+
+use crate::analysis::Score;
+use crate::pgn::*;
+use crate::uciengine::{GoJob, UciEngine};
+
+pub use crate::uciengine::SearchLimit;
+
+/// centipawn value of a mate score, reduced by the distance to mate
+const MATE_CP: i32 = 10000;
+/// evaluations are clamped to this many centipawns when measuring eval drops
+const MAX_DROP_CP: i32 = 1000;
+
+/// nag for dubious move ( ?! )
+pub const NAG_INACCURACY: u8 = 6;
+/// nag for mistake ( ? )
+pub const NAG_MISTAKE: u8 = 2;
+/// nag for blunder ( ?? )
+pub const NAG_BLUNDER: u8 = 4;
+
+/// AnnotateError captures possible game annotation errors
+#[derive(Error, Debug)]
+pub enum AnnotateError {
+    #[error("{0}")]
+    PgnError(#[from] PgnError),
+    #[error("engine closed before returning a result")]
+    EngineClosed,
+}
+
+/// evaluation of a position
+#[derive(Debug, Clone)]
+struct Evaluation {
+    /// score from the point of view of the side to move
+    score: Score,
+    /// true if the position is game over and was not searched
+    game_over: bool,
+    /// best move
+    bestmove: Option<String>,
+    /// principal variation
+    pv: Option<String>,
+}
+
+/// game annotator, searches every position of a game and writes evaluations,
+/// nags and best move variations into the game
+#[derive(Debug, Clone)]
+pub struct Annotator {
+    /// search limit
+    limit: SearchLimit,
+    /// uci options sent with every search
+    uci_options: Vec<(String, String)>,
+    /// eval drop in centipawns from which a move is an inaccuracy
+    inaccuracy: i32,
+    /// eval drop in centipawns from which a move is a mistake
+    mistake: i32,
+    /// eval drop in centipawns from which a move is a blunder
+    blunder: i32,
+}
+
+/// default annotator
+impl Default for Annotator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// centipawn value of score, mate scores are mapped beyond any centipawn score
+pub fn score_cp(score: Score) -> i32 {
+    match score {
+        Score::Cp(cp) => cp,
+        Score::Mate(mate) if mate > 0 => MATE_CP - mate,
+        Score::Mate(mate) => -MATE_CP - mate,
+    }
+}
+
+/// pgn eval comment value of score given from white's point of view
+fn eval_str(score: Score) -> String {
+    match score {
+        Score::Cp(cp) => format!("{:.2}", cp as f64 / 100.0),
+        Score::Mate(mate) => format!("#{}", mate),
+    }
+}
+
+/// negate score ( switch point of view )
+fn negate(score: Score) -> Score {
+    match score {
+        Score::Cp(cp) => Score::Cp(-cp),
+        Score::Mate(mate) => Score::Mate(-mate),
+    }
+}
+
+/// annotator implementation
+impl Annotator {
+    /// create new annotator, searching to depth 12 with 50 / 100 / 300
+    /// centipawn thresholds for inaccuracy / mistake / blunder
+    pub fn new() -> Self {
+        Self {
+            limit: SearchLimit::Depth(12),
+            uci_options: vec![],
+            inaccuracy: 50,
+            mistake: 100,
+            blunder: 300,
+        }
+    }
+
+    /// set search limit and return self
+    pub fn limit(mut self, limit: SearchLimit) -> Self {
+        self.limit = limit;
+
+        self
+    }
+
+    /// set uci option sent with every search and return self
+    pub fn uci_opt<K, V>(mut self, key: K, value: V) -> Self
+    where
+        K: core::fmt::Display,
+        V: core::fmt::Display,
+    {
+        self.uci_options
+            .push((format!("{}", key), format!("{}", value)));
+
+        self
+    }
+
+    /// set eval drop thresholds in centipawns and return self
+    pub fn thresholds(mut self, inaccuracy: i32, mistake: i32, blunder: i32) -> Self {
+        self.inaccuracy = inaccuracy;
+        self.mistake = mistake;
+        self.blunder = blunder;
+
+        self
+    }
+
+    /// go job for position reached by moves from start fen
+    pub fn go_job(&self, start_fen: &str, moves: &[&str]) -> GoJob {
+        let mut go_job = GoJob::new().pos_fen(start_fen);
+
+        if !moves.is_empty() {
+            go_job = go_job.pos_moves(moves.join(" "));
+        }
+
+        for (key, value) in &self.uci_options {
+            go_job = go_job.uci_opt(key, value);
+        }
+
+        go_job.limit(self.limit)
+    }
+
+    /// nag for eval drop in centipawns, None if the move is good enough
+    pub fn nag(&self, drop: i32) -> Option<u8> {
+        if drop >= self.blunder {
+            Some(NAG_BLUNDER)
+        } else if drop >= self.mistake {
+            Some(NAG_MISTAKE)
+        } else if drop >= self.inaccuracy {
+            Some(NAG_INACCURACY)
+        } else {
+            None
+        }
+    }
+
+    /// evaluate position, positions that are game over are not searched
+    async fn evaluate(
+        &self,
+        engine: &UciEngine,
+        pos: &Chess,
+        go_job: GoJob,
+    ) -> Result<Evaluation, AnnotateError> {
+        if pos.is_game_over() {
+            // the side to move is checkmated or it is a draw
+            let score = if pos.is_checkmate() {
+                Score::Cp(-MATE_CP)
+            } else {
+                Score::Cp(0)
+            };
+
+            return Ok(Evaluation {
+                score,
+                game_over: true,
+                bestmove: None,
+                pv: None,
+            });
+        }
+
+        let go_result = engine
+            .go(go_job)
+            .await
+            .map_err(|_| AnnotateError::EngineClosed)?;
+
+        if log_enabled!(Level::Debug) {
+            debug!("annotate search result {:?}", go_result);
+        }
+
+        Ok(Evaluation {
+            score: go_result.ai.score,
+            game_over: false,
+            bestmove: go_result.bestmove,
+            pv: go_result.ai.pv(),
+        })
+    }
+
+    /// annotate game, returns annotated copy of game
+    pub async fn annotate_game(
+        &self,
+        engine: &UciEngine,
+        game: &PgnGame,
+    ) -> Result<PgnGame, AnnotateError> {
+        let positions = game.positions()?;
+
+        let start_fen = to_fen(&positions[0]);
+
+        let ucis: Vec<&str> = game.moves.iter().map(|m| m.uci.as_str()).collect();
+
+        let mut evaluations = vec![];
+
+        for (ply, pos) in positions.iter().enumerate() {
+            let go_job = self.go_job(&start_fen, &ucis[0..ply]);
+
+            evaluations.push(self.evaluate(engine, pos, go_job).await?);
+        }
+
+        let mut annotated = game.clone();
+
+        for (ply, m) in annotated.moves.iter_mut().enumerate() {
+            let before = &evaluations[ply];
+            let after = &evaluations[ply + 1];
+
+            // after score is from the point of view of the opponent
+            let after_mover = negate(after.score);
+
+            if !after.game_over {
+                let white_pov = match positions[ply + 1].turn() {
+                    Color::White => after.score,
+                    Color::Black => after_mover,
+                };
+
+                let eval_comment = format!("[%eval {}]", eval_str(white_pov));
+
+                m.comment = Some(match m.comment.take() {
+                    Some(comment) => format!("{} {}", eval_comment, comment),
+                    _ => eval_comment,
+                });
+            }
+
+            let drop = score_cp(before.score).clamp(-MAX_DROP_CP, MAX_DROP_CP)
+                - score_cp(after_mover).clamp(-MAX_DROP_CP, MAX_DROP_CP);
+
+            if let Some(nag) = self.nag(drop) {
+                m.nags.retain(|nag| !matches!(*nag, 1..=6));
+                m.nags.push(nag);
+
+                if before.bestmove.as_deref() != Some(m.uci.as_str()) {
+                    if let Some(pv) = &before.pv {
+                        let pv: Vec<&str> = pv.split(' ').collect();
+
+                        m.variation = uci_to_san(&positions[ply], &pv);
+                    }
+                }
+            }
+        }
+
+        Ok(annotated)
+    }
+
+    /// annotate all games of pgn text, returns annotated pgn text
+    pub async fn annotate_pgn(
+        &self,
+        engine: &UciEngine,
+        pgn: &str,
+    ) -> Result<String, AnnotateError> {
+        let mut annotated = String::new();
+
+        for game in read_games(pgn)? {
+            if !annotated.is_empty() {
+                annotated.push('\n');
+            }
+
+            annotated.push_str(&self.annotate_game(engine, &game).await?.to_string());
+        }
+
+        Ok(annotated)
+    }
+}
+
+#[test]
+fn nag_thresholds() {
+    let annotator = Annotator::new().thresholds(40, 90, 200);
+
+    assert_eq!(annotator.nag(39), None);
+    assert_eq!(annotator.nag(40), Some(NAG_INACCURACY));
+    assert_eq!(annotator.nag(150), Some(NAG_MISTAKE));
+    assert_eq!(annotator.nag(200), Some(NAG_BLUNDER));
+
+    assert_eq!(score_cp(Score::Mate(3)), 9997);
+    assert_eq!(score_cp(Score::Mate(-2)), -9998);
+    assert_eq!(eval_str(Score::Cp(-35)), "-0.35");
+}
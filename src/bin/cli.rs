@@ -0,0 +1,548 @@
+//! command line tool for one-off engine queries and benchmarking
+//!
+//! usage : uciengine SUBCOMMAND [--config FILE] [--engine PATH] [--option NAME=VALUE]..
+//!         [--fen FEN] [--moves MOVES] [--depth N] [--nodes N] [--movetime MS]
+//!
+//! subcommands :
+//!   info                 print engine id and options
+//!   go                   search the position, printing the live pv and the bestmove
+//!   bench FILE           search the fen / epd positions of file and report nps
+//!   perft-check          compare engine go perft with a reference perft
+//!
+//! the config file is toml with engine, args and an options table, flags override it :
+//!
+//!   engine = "./stockfish12"
+//!   args = []
+//!   [options]
+//!   Hash = 128
+//!   Threads = 4
+
+use serde::Deserialize;
+
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+use shakmaty::uci::UciMove;
+use shakmaty::{CastlingMode, Chess, Position};
+
+use uciengine::enginematch::read_fen_openings;
+use uciengine::protocol::{EngineToGui, GuiToEngine};
+use uciengine::uciengine::{GoJob, UciEngine};
+
+/// engine startup timeout
+const TIMEOUT: Duration = Duration::from_millis(10000);
+
+/// cli configuration, from toml file and flags
+#[derive(Debug, Default, Deserialize)]
+struct CliConfig {
+    /// engine path
+    engine: Option<String>,
+    /// engine arguments
+    #[serde(default)]
+    args: Vec<String>,
+    /// uci options
+    #[serde(default)]
+    options: BTreeMap<String, toml::Value>,
+}
+
+/// command line arguments following the subcommand
+#[derive(Debug, Default)]
+struct CliArgs {
+    config: CliConfig,
+    fen: Option<String>,
+    moves: Option<String>,
+    depth: Option<u64>,
+    nodes: Option<u64>,
+    movetime: Option<u64>,
+    positional: Vec<String>,
+}
+
+/// print usage and exit
+fn usage(message: &str) -> ! {
+    eprintln!("{}", message);
+    eprintln!(
+        "usage : uciengine info|go|bench FILE|perft-check [--config FILE] [--engine PATH] \
+         [--option NAME=VALUE].. [--fen FEN] [--moves MOVES] [--depth N] [--nodes N] \
+         [--movetime MS]"
+    );
+
+    std::process::exit(2);
+}
+
+/// parse arguments, the config file is read first wherever it is given
+fn parse_args(args: Vec<String>) -> CliArgs {
+    let mut cli = CliArgs::default();
+
+    if let Some(index) = args.iter().position(|arg| arg == "--config") {
+        let path = args.get(index + 1).unwrap_or_else(|| usage("missing FILE"));
+
+        let text = std::fs::read_to_string(path)
+            .unwrap_or_else(|err| usage(&format!("reading {} failed : {}", path, err)));
+
+        cli.config = toml::from_str(&text)
+            .unwrap_or_else(|err| usage(&format!("invalid config {} : {}", path, err)));
+    }
+
+    let mut args = args.into_iter();
+
+    let value = |args: &mut std::vec::IntoIter<String>, name: &str| {
+        args.next()
+            .unwrap_or_else(|| usage(&format!("missing value of {}", name)))
+    };
+
+    let number = |value: String| {
+        value
+            .parse::<u64>()
+            .unwrap_or_else(|_| usage(&format!("invalid number {}", value)))
+    };
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--config" => {
+                value(&mut args, &arg);
+            }
+            "--engine" => cli.config.engine = Some(value(&mut args, &arg)),
+            "--option" => {
+                let option = value(&mut args, &arg);
+
+                let (name, option_value) = option
+                    .split_once('=')
+                    .unwrap_or_else(|| usage(&format!("invalid NAME=VALUE {}", option)));
+
+                cli.config.options.insert(
+                    name.to_string(),
+                    toml::Value::String(option_value.to_string()),
+                );
+            }
+            "--fen" => cli.fen = Some(value(&mut args, &arg)),
+            "--moves" => cli.moves = Some(value(&mut args, &arg)),
+            "--depth" => cli.depth = Some(number(value(&mut args, &arg))),
+            "--nodes" => cli.nodes = Some(number(value(&mut args, &arg))),
+            "--movetime" => cli.movetime = Some(number(value(&mut args, &arg))),
+            _ if arg.starts_with("--") => usage(&format!("unknown option {}", arg)),
+            _ => cli.positional.push(arg),
+        }
+    }
+
+    cli
+}
+
+/// option value as sent in setoption
+fn option_value(value: &toml::Value) -> String {
+    match value {
+        toml::Value::String(value) => value.to_string(),
+        value => value.to_string(),
+    }
+}
+
+/// cli arguments implementation
+impl CliArgs {
+    /// start engine
+    fn engine(&self) -> std::sync::Arc<UciEngine> {
+        let path = self
+            .config
+            .engine
+            .as_ref()
+            .unwrap_or_else(|| usage("missing engine, give --engine or set engine in config"));
+
+        UciEngine::new_with_args(path, &self.config.args)
+    }
+
+    /// go job with position of fen or the starting position and limits,
+    /// default_limit is used as go option if no limit is given
+    fn go_job(&self, fen: Option<&str>, default_limit: (&str, u64)) -> GoJob {
+        let mut go_job = match fen.or(self.fen.as_deref()) {
+            Some(fen) => GoJob::new().pos_fen(fen),
+            _ => GoJob::new().pos_startpos(),
+        };
+
+        if let Some(moves) = &self.moves {
+            go_job = go_job.pos_moves(moves);
+        }
+
+        let limits = [
+            ("depth", self.depth),
+            ("nodes", self.nodes),
+            ("movetime", self.movetime),
+        ];
+
+        let mut has_limit = false;
+
+        for (key, limit) in limits.iter() {
+            if let Some(limit) = limit {
+                go_job = go_job.go_opt(key, limit);
+
+                has_limit = true;
+            }
+        }
+
+        if !has_limit {
+            go_job = go_job.go_opt(default_limit.0, default_limit.1);
+        }
+
+        go_job
+    }
+
+    /// send options to engine and wait until it is ready, false if it does not answer
+    async fn send_options(&self, engine: &UciEngine) -> bool {
+        for (name, value) in &self.config.options {
+            engine.go(GoJob::new().custom(GuiToEngine::SetOption {
+                name: name.to_string(),
+                value: Some(option_value(value)),
+            }));
+        }
+
+        engine.is_ready(TIMEOUT).await
+    }
+
+    /// chess position of fen and moves
+    fn position(&self) -> Chess {
+        self.go_job(None, ("depth", 1))
+            .position()
+            .unwrap_or_else(|| usage("invalid position"))
+    }
+}
+
+/// print engine id and options
+async fn info(cli: &CliArgs) -> i32 {
+    let engine = cli.engine();
+
+    let engine_id = match engine.identify(TIMEOUT).await {
+        Some(engine_id) => engine_id,
+        _ => {
+            eprintln!("engine did not answer uci");
+
+            return 1;
+        }
+    };
+
+    println!("name {}", engine_id.name.unwrap_or_default());
+    println!("author {}", engine_id.author.unwrap_or_default());
+
+    for option in engine_id.options {
+        println!("{}", EngineToGui::Option(option));
+    }
+
+    engine.shutdown(TIMEOUT).await;
+
+    0
+}
+
+/// search position, printing the live pv and the bestmove
+async fn go(cli: &CliArgs) -> i32 {
+    let engine = cli.engine();
+
+    if !cli.send_options(&engine).await {
+        eprintln!("engine did not answer isready");
+
+        return 1;
+    }
+
+    let mut arx = engine.atx.subscribe();
+
+    let printer = tokio::spawn(async move {
+        while let Ok(ai) = arx.recv().await {
+            if ai.done {
+                break;
+            }
+
+            if ai.pv().is_some() {
+                println!("{}", EngineToGui::from_analysis_info(&ai));
+            }
+        }
+    });
+
+    let go_result = engine.go(cli.go_job(None, ("movetime", 1000))).await;
+
+    let _ = printer.await;
+
+    let code = match go_result {
+        Ok(go_result) => {
+            println!(
+                "{}",
+                EngineToGui::BestMove {
+                    bestmove: go_result.bestmove.unwrap_or_else(|| "(none)".to_string()),
+                    ponder: go_result.ponder,
+                }
+            );
+
+            0
+        }
+        _ => {
+            eprintln!("engine terminated");
+
+            1
+        }
+    };
+
+    engine.shutdown(TIMEOUT).await;
+
+    code
+}
+
+/// search positions of file and report nps
+async fn bench(cli: &CliArgs) -> i32 {
+    let path = cli
+        .positional
+        .get(1)
+        .unwrap_or_else(|| usage("missing positions FILE"));
+
+    let text = std::fs::read_to_string(path)
+        .unwrap_or_else(|err| usage(&format!("reading {} failed : {}", path, err)));
+
+    let openings = read_fen_openings(&text)
+        .unwrap_or_else(|err| usage(&format!("invalid positions : {}", err)));
+
+    let engine = cli.engine();
+
+    // options are set once, resending them per position would reallocate the hash
+    if !cli.send_options(&engine).await {
+        eprintln!("engine did not answer isready");
+
+        return 1;
+    }
+
+    let start = Instant::now();
+
+    let mut total_nodes: u64 = 0;
+
+    for (index, opening) in openings.iter().enumerate() {
+        let go_job = cli.go_job(opening.fen.as_deref(), ("depth", 12));
+
+        let go_result = match engine.go(go_job).await {
+            Ok(go_result) => go_result,
+            _ => {
+                eprintln!("engine terminated");
+
+                return 1;
+            }
+        };
+
+        total_nodes += go_result.ai.nodes;
+
+        println!(
+            "position {} / {} : bestmove {} nodes {} time {}",
+            index + 1,
+            openings.len(),
+            go_result.bestmove.unwrap_or_default(),
+            go_result.ai.nodes,
+            go_result.ai.time
+        );
+    }
+
+    let elapsed = start.elapsed().as_millis().max(1) as u64;
+
+    println!("positions {}", openings.len());
+    println!("nodes {}", total_nodes);
+    println!("time {} ms", elapsed);
+    println!("nps {}", total_nodes * 1000 / elapsed);
+
+    engine.shutdown(TIMEOUT).await;
+
+    0
+}
+
+/// reference perft of every legal move of position
+fn reference_divide(pos: &Chess, depth: u32) -> BTreeMap<String, u64> {
+    pos.legal_moves()
+        .iter()
+        .map(|m| {
+            let mut after = pos.clone();
+
+            after.play_unchecked(*m);
+
+            (
+                m.to_uci(CastlingMode::Standard).to_string(),
+                shakmaty::perft(&after, depth - 1),
+            )
+        })
+        .collect()
+}
+
+/// compare engine go perft with reference perft, lines of the form move: nodes
+/// are compared per move and Nodes searched: nodes as total
+async fn perft_check(cli: &CliArgs) -> i32 {
+    let depth = cli.depth.unwrap_or(4).max(1) as u32;
+
+    let pos = cli.position();
+
+    let reference = reference_divide(&pos, depth);
+
+    let engine = cli.engine();
+
+    let mut lrx = engine.subscribe_lines();
+
+    let mut position = match &cli.fen {
+        Some(fen) => format!("position fen {}", fen),
+        _ => "position startpos".to_string(),
+    };
+
+    if let Some(moves) = &cli.moves {
+        position = format!("{} moves {}", position, moves);
+    }
+
+    if !cli.send_options(&engine).await {
+        eprintln!("engine did not answer isready");
+
+        return 1;
+    }
+
+    engine.go(GoJob::new().custom(position));
+    engine.go(GoJob::new().custom(format!("go perft {}", depth)));
+
+    let mut divide = BTreeMap::new();
+
+    let collect = async {
+        while let Ok(line) = lrx.recv().await {
+            if let Some(total) = line.strip_prefix("Nodes searched:") {
+                return total.trim().parse::<u64>().ok();
+            }
+
+            if let Some((m, nodes)) = line.split_once(':') {
+                if let (Ok(_), Ok(nodes)) = (
+                    UciMove::from_ascii(m.trim().as_bytes()),
+                    nodes.trim().parse::<u64>(),
+                ) {
+                    divide.insert(m.trim().to_string(), nodes);
+                }
+            }
+        }
+
+        None
+    };
+
+    let total = tokio::time::timeout(Duration::from_secs(600), collect)
+        .await
+        .ok()
+        .flatten();
+
+    engine.shutdown(TIMEOUT).await;
+
+    let reference_total: u64 = reference.values().sum();
+
+    let moves: std::collections::BTreeSet<&String> =
+        reference.keys().chain(divide.keys()).collect();
+
+    let mut mismatches = 0;
+
+    // engines without per move output are only checked on the total
+    if !divide.is_empty() {
+        for m in moves {
+            let (expected, got) = (reference.get(m), divide.get(m));
+
+            if expected != got {
+                mismatches += 1;
+
+                println!("{} : reference {:?} , engine {:?}", m, expected, got);
+            }
+        }
+    }
+
+    println!(
+        "perft {} : reference {} , engine {}",
+        depth,
+        reference_total,
+        total.map_or("none".to_string(), |total| total.to_string())
+    );
+
+    if total == Some(reference_total) && mismatches == 0 {
+        println!("ok");
+
+        0
+    } else {
+        println!("mismatch");
+
+        1
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    let cli = parse_args(args);
+
+    let code = match cli.positional.first().map(|command| command.as_str()) {
+        Some("info") => info(&cli).await,
+        Some("go") => go(&cli).await,
+        Some("bench") => bench(&cli).await,
+        Some("perft-check") => perft_check(&cli).await,
+        Some(command) => usage(&format!("unknown subcommand {}", command)),
+        _ => usage("missing subcommand"),
+    };
+
+    std::process::exit(code);
+}
+
+#[test]
+fn parse_args_config_and_flags() {
+    let path = std::env::temp_dir().join(format!("uciengine_cli_{}.toml", std::process::id()));
+
+    std::fs::write(
+        &path,
+        "engine = \"./stockfish12\"\nargs = [\"--uci\"]\n[options]\nHash = 128\nThreads = 4\n",
+    )
+    .unwrap();
+
+    let args: Vec<String> = [
+        "bench",
+        "positions.epd",
+        "--option",
+        "Hash=256",
+        "--config",
+        path.to_str().unwrap(),
+        "--fen",
+        "8/8/8/8/8/8/8/K1k5 w - - 0 1",
+        "--depth",
+        "14",
+    ]
+    .iter()
+    .map(|arg| arg.to_string())
+    .collect();
+
+    let cli = parse_args(args);
+
+    let _ = std::fs::remove_file(&path);
+
+    assert_eq!(cli.positional, vec!["bench", "positions.epd"]);
+    assert_eq!(cli.config.engine.as_deref(), Some("./stockfish12"));
+    assert_eq!(cli.config.args, vec!["--uci"]);
+    assert_eq!(cli.fen.as_deref(), Some("8/8/8/8/8/8/8/K1k5 w - - 0 1"));
+    assert_eq!(cli.depth, Some(14));
+    assert_eq!(cli.nodes, None);
+
+    // flags override the config file wherever it is given
+    assert_eq!(option_value(&cli.config.options["Hash"]), "256");
+    assert_eq!(option_value(&cli.config.options["Threads"]), "4");
+
+    // options are sent once per engine, not with every job
+    assert_eq!(
+        cli.go_job(None, ("movetime", 1000)).to_commands(),
+        vec![
+            "position fen 8/8/8/8/8/8/8/K1k5 w - - 0 1".to_string(),
+            "go depth 14".to_string()
+        ]
+    );
+}
+
+#[test]
+fn reference_divide_startpos() {
+    let divide = reference_divide(&Chess::default(), 3);
+
+    assert_eq!(divide.len(), 20);
+    assert_eq!(divide["e2e4"], 600);
+    assert_eq!(divide["g1f3"], 440);
+    assert_eq!(divide.values().sum::<u64>(), 8902);
+
+    // castling is given as king move
+    let pos: Chess = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1"
+        .parse::<shakmaty::fen::Fen>()
+        .unwrap()
+        .into_position(CastlingMode::Standard)
+        .unwrap();
+
+    let divide = reference_divide(&pos, 1);
+
+    assert!(divide.contains_key("e1g1"));
+    assert!(divide.values().all(|nodes| *nodes == 1));
+}
@@ -0,0 +1,24 @@
+//! scripted mock uci engine for tests
+//!
+//! the script is given as first argument, either as json or as path of a json file,
+//! without argument the default script is run
+
+use uciengine::mockengine::MockScript;
+
+fn main() {
+    let script = match std::env::args().nth(1) {
+        Some(arg) if arg.trim_start().starts_with('{') => {
+            MockScript::from_json(&arg).expect("invalid mock script json")
+        }
+        Some(path) => MockScript::from_json(
+            &std::fs::read_to_string(path).expect("failed to read mock script"),
+        )
+        .expect("invalid mock script json"),
+        _ => MockScript::new(),
+    };
+
+    let stdin = std::io::stdin();
+    let stdout = std::io::stdout();
+
+    std::process::exit(script.run(stdin.lock(), stdout.lock()));
+}
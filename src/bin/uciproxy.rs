@@ -0,0 +1,94 @@
+//! uci proxy between a gui and an engine
+//!
+//! usage : uciproxy [--transcript PATH] [--force NAME=VALUE].. [--cap NAME=MAX].. [--max-time MS]
+//!         ENGINE [ENGINE ARGS]..
+//!
+//! the proxy speaks uci on stdin / stdout and forwards to the engine, forced options
+//! replace the values set by the gui, capped numeric options are limited to the cap and
+//! searches running longer than max time are stopped
+
+use uciengine::proxy::{ProxyConfig, UciProxy};
+use uciengine::uciengine::UciEngine;
+
+/// print usage and exit
+fn usage(message: &str) -> ! {
+    eprintln!("{}", message);
+    eprintln!(
+        "usage : uciproxy [--transcript PATH] [--force NAME=VALUE].. [--cap NAME=MAX].. \
+         [--max-time MS] ENGINE [ENGINE ARGS].."
+    );
+
+    std::process::exit(2);
+}
+
+/// split NAME=VALUE argument
+fn name_value(arg: Option<String>) -> (String, String) {
+    let arg = arg.unwrap_or_else(|| usage("missing NAME=VALUE"));
+
+    match arg.split_once('=') {
+        Some((name, value)) => (name.to_string(), value.to_string()),
+        _ => usage(&format!("invalid NAME=VALUE {}", arg)),
+    }
+}
+
+#[tokio::main]
+async fn main() -> std::io::Result<()> {
+    let mut args = std::env::args().skip(1);
+
+    let mut config = ProxyConfig::new();
+
+    let mut transcript = None;
+
+    let engine_path = loop {
+        match args.next() {
+            Some(arg) if arg == "--transcript" => {
+                transcript = Some(args.next().unwrap_or_else(|| usage("missing PATH")))
+            }
+            Some(arg) if arg == "--force" => {
+                let (name, value) = name_value(args.next());
+
+                config = config.force(name, value);
+            }
+            Some(arg) if arg == "--cap" => {
+                let (name, max) = name_value(args.next());
+
+                let max = max
+                    .parse()
+                    .unwrap_or_else(|_| usage(&format!("invalid cap {}", max)));
+
+                config = config.cap(name, max);
+            }
+            Some(arg) if arg == "--max-time" => {
+                let max_time = args.next().unwrap_or_else(|| usage("missing MS"));
+
+                config = config.max_time(
+                    max_time
+                        .parse()
+                        .unwrap_or_else(|_| usage(&format!("invalid max time {}", max_time))),
+                );
+            }
+            Some(arg) if arg.starts_with("--") => usage(&format!("unknown option {}", arg)),
+            Some(path) => break path,
+            _ => usage("missing ENGINE"),
+        }
+    };
+
+    let engine_args: Vec<String> = args.collect();
+
+    let engine = UciEngine::new_with_args(engine_path, &engine_args);
+
+    if let Some(path) = transcript {
+        engine.record_transcript(path)?;
+    }
+
+    let run_result = UciProxy::new(engine.clone(), config)
+        .run(tokio::io::stdin(), tokio::io::stdout())
+        .await;
+
+    // the engine has quit by now, this waits for the transcript to be written
+    engine
+        .shutdown(std::time::Duration::from_millis(1000))
+        .await;
+
+    run_result
+}
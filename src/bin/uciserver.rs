@@ -0,0 +1,77 @@
+//! http / websocket analysis server backed by a pool of uci engines
+//!
+//! usage : uciserver [--addr ADDR] [--engines N] [--default-movetime MS] [--max-movetime MS]
+//!         ENGINE [ENGINE ARGS]..
+//!
+//! POST /analysis with {"fen":..,"moves":..,"depth":..,"nodes":..,"movetime":..} returns
+//! the go result as json, GET /ws opens a websocket streaming analysis info
+
+use uciengine::pool::EnginePool;
+use uciengine::server::{serve, ServerConfig};
+use uciengine::uciengine::UciEngine;
+
+/// print usage and exit
+fn usage(message: &str) -> ! {
+    eprintln!("{}", message);
+    eprintln!(
+        "usage : uciserver [--addr ADDR] [--engines N] [--default-movetime MS] \
+         [--max-movetime MS] ENGINE [ENGINE ARGS].."
+    );
+
+    std::process::exit(2);
+}
+
+/// parse number argument
+fn number(arg: Option<String>) -> usize {
+    let arg = arg.unwrap_or_else(|| usage("missing number"));
+
+    arg.parse()
+        .unwrap_or_else(|_| usage(&format!("invalid number {}", arg)))
+}
+
+#[tokio::main]
+async fn main() -> std::io::Result<()> {
+    let mut args = std::env::args().skip(1);
+
+    let mut addr = "127.0.0.1:8080".to_string();
+    let mut engines = 1;
+    let mut config = ServerConfig::new();
+
+    let engine_path = loop {
+        match args.next() {
+            Some(arg) if arg == "--addr" => {
+                addr = args.next().unwrap_or_else(|| usage("missing ADDR"))
+            }
+            Some(arg) if arg == "--engines" => engines = number(args.next()),
+            Some(arg) if arg == "--default-movetime" => {
+                config = config.default_movetime(number(args.next()))
+            }
+            Some(arg) if arg == "--max-movetime" => {
+                config = config.max_movetime(number(args.next()))
+            }
+            Some(arg) if arg.starts_with("--") => usage(&format!("unknown option {}", arg)),
+            Some(path) => break path,
+            _ => usage("missing ENGINE"),
+        }
+    };
+
+    let engine_args: Vec<String> = args.collect();
+
+    // crashed engines are restarted when next acquired
+    let pool = EnginePool::respawning(
+        (0..engines)
+            .map(|_| UciEngine::new_with_args(&engine_path, &engine_args))
+            .collect(),
+        move |_| {
+            let mut command = tokio::process::Command::new(&engine_path);
+
+            command.args(&engine_args);
+
+            UciEngine::spawn(command)
+        },
+    );
+
+    let listener = tokio::net::TcpListener::bind(&addr).await?;
+
+    serve(listener, pool, config).await
+}
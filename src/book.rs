@@ -0,0 +1,291 @@
+use log::{debug, log_enabled, Level};
+
+use shakmaty::uci::UciMove;
+use shakmaty::zobrist::Zobrist64;
+use shakmaty::{CastlingMode, Chess, EnPassantMode, Position, Role, Square};
+
+use std::convert::TryInto;
+use std::hash::{BuildHasher, Hasher};
+use std::path::Path;
+
+/// size of a polyglot book entry in bytes
+const ENTRY_SIZE: usize = 16;
+
+/// polyglot book entry, as stored in the book file
+#[derive(Debug, Clone, Copy)]
+pub struct PolyglotEntry {
+    /// polyglot zobrist key of position
+    pub key: u64,
+    /// move in polyglot encoding
+    pub mv: u16,
+    /// weight of move
+    pub weight: u16,
+    /// learn data
+    pub learn: u32,
+}
+
+/// book move
+#[derive(Debug, Clone)]
+pub struct BookMove {
+    /// move in uci notation
+    pub uci: String,
+    /// weight of move
+    pub weight: u16,
+}
+
+/// how to select a move from the moves stored for a position
+#[derive(Debug, Clone, Copy)]
+pub enum BookSelection {
+    /// move with the highest weight
+    Best,
+    /// random move with probability proportional to weight
+    WeightedRandom,
+}
+
+/// polyglot opening book
+#[derive(Debug)]
+pub struct PolyglotBook {
+    /// entries sorted by key
+    entries: Vec<PolyglotEntry>,
+    /// move selection
+    selection: BookSelection,
+}
+
+/// polyglot zobrist key of position
+///
+/// polyglot hashes the en passant file whenever a pawn stands next to the pushed pawn,
+/// even if the capture would be illegal
+pub fn polyglot_key(pos: &Chess) -> u64 {
+    pos.zobrist_hash::<Zobrist64>(EnPassantMode::PseudoLegal).0
+}
+
+/// decode polyglot move for position, None if the move is not legal
+fn decode_move(pos: &Chess, mv: u16) -> Option<String> {
+    let mv = mv as u32;
+
+    let to = Square::new(mv & 0x3f);
+    let from = Square::new((mv >> 6) & 0x3f);
+
+    let promotion = match (mv >> 12) & 0x7 {
+        1 => Some(Role::Knight),
+        2 => Some(Role::Bishop),
+        3 => Some(Role::Rook),
+        4 => Some(Role::Queen),
+        _ => None,
+    };
+
+    // castling is stored as king takes own rook, to_move understands that
+    let m = UciMove::Normal {
+        from,
+        to,
+        promotion,
+    }
+    .to_move(pos)
+    .ok()?;
+
+    Some(m.to_uci(CastlingMode::Standard).to_string())
+}
+
+/// random number, seeded from the process wide random state and current time
+fn random_u64() -> u64 {
+    let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+
+    if let Ok(elapsed) = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH) {
+        hasher.write_u128(elapsed.as_nanos());
+    }
+
+    hasher.finish()
+}
+
+/// polyglot book implementation
+impl PolyglotBook {
+    /// create book from raw file contents
+    pub fn from_bytes(bytes: &[u8]) -> std::io::Result<Self> {
+        if !bytes.len().is_multiple_of(ENTRY_SIZE) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "polyglot book size is not a multiple of entry size",
+            ));
+        }
+
+        let mut entries: Vec<PolyglotEntry> = bytes
+            .chunks_exact(ENTRY_SIZE)
+            .map(|chunk| PolyglotEntry {
+                key: u64::from_be_bytes(chunk[0..8].try_into().unwrap()),
+                mv: u16::from_be_bytes(chunk[8..10].try_into().unwrap()),
+                weight: u16::from_be_bytes(chunk[10..12].try_into().unwrap()),
+                learn: u32::from_be_bytes(chunk[12..16].try_into().unwrap()),
+            })
+            .collect();
+
+        // books are sorted by key, but do not rely on it
+        entries.sort_by_key(|entry| entry.key);
+
+        Ok(Self {
+            entries,
+            selection: BookSelection::WeightedRandom,
+        })
+    }
+
+    /// open book file
+    pub fn open<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        Self::from_bytes(&std::fs::read(path)?)
+    }
+
+    /// set move selection and return self
+    pub fn selection(mut self, selection: BookSelection) -> Self {
+        self.selection = selection;
+
+        self
+    }
+
+    /// number of entries in book
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// true if book has no entries
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// book moves for position, illegal and zero weight moves are left out
+    pub fn moves(&self, pos: &Chess) -> Vec<BookMove> {
+        let key = polyglot_key(pos);
+
+        let start = self.entries.partition_point(|entry| entry.key < key);
+
+        self.entries[start..]
+            .iter()
+            .take_while(|entry| entry.key == key)
+            .filter(|entry| entry.weight > 0)
+            .filter_map(|entry| {
+                decode_move(pos, entry.mv).map(|uci| BookMove {
+                    uci,
+                    weight: entry.weight,
+                })
+            })
+            .collect()
+    }
+
+    /// select book move for position, None if out of book
+    pub fn probe(&self, pos: &Chess) -> Option<String> {
+        let moves = self.moves(pos);
+
+        let selected = match self.selection {
+            BookSelection::Best => moves.into_iter().max_by_key(|m| m.weight),
+            BookSelection::WeightedRandom => {
+                let total: u64 = moves.iter().map(|m| m.weight as u64).sum();
+
+                if total == 0 {
+                    return None;
+                }
+
+                let mut pick = random_u64() % total;
+
+                moves.into_iter().find(|m| {
+                    let weight = m.weight as u64;
+
+                    if pick < weight {
+                        return true;
+                    }
+
+                    pick -= weight;
+
+                    false
+                })
+            }
+        };
+
+        if log_enabled!(Level::Debug) {
+            debug!("book probe {:?}", selected);
+        }
+
+        selected.map(|m| m.uci)
+    }
+}
+
+#[cfg(test)]
+fn test_book(entries: &[(u64, u16, u16)]) -> PolyglotBook {
+    let mut bytes = vec![];
+
+    for (key, mv, weight) in entries {
+        bytes.extend_from_slice(&key.to_be_bytes());
+        bytes.extend_from_slice(&mv.to_be_bytes());
+        bytes.extend_from_slice(&weight.to_be_bytes());
+        bytes.extend_from_slice(&0_u32.to_be_bytes());
+    }
+
+    PolyglotBook::from_bytes(&bytes).unwrap()
+}
+
+#[test]
+fn probe_startpos() {
+    // e2e4 ( from e2 = 12, to e4 = 28 ) and d2d4 ( from d2 = 11, to d4 = 27 )
+    let book = test_book(&[
+        (0x463b96181691fc9c, (12 << 6) | 28, 10),
+        (0x463b96181691fc9c, (11 << 6) | 27, 5),
+        (0x1234, (11 << 6) | 27, 5),
+    ]);
+
+    let pos = Chess::default();
+
+    assert_eq!(book.moves(&pos).len(), 2);
+
+    let book = book.selection(BookSelection::Best);
+
+    assert_eq!(book.probe(&pos), Some("e2e4".to_string()));
+}
+
+#[test]
+fn decode_castling() {
+    let pos: Chess = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1"
+        .parse::<shakmaty::fen::Fen>()
+        .unwrap()
+        .into_position(CastlingMode::Standard)
+        .unwrap();
+
+    // e1h1 ( from e1 = 4, to h1 = 7 )
+    assert_eq!(decode_move(&pos, (4 << 6) | 7), Some("e1g1".to_string()));
+}
+
+#[test]
+fn probe_go_job_position() {
+    // e7e5 ( from e7 = 52, to e5 = 36 )
+    let book = test_book(&[(0x823c9b50fd114196, (52 << 6) | 36, 1)]);
+
+    let go_job = crate::uciengine::GoJob::new()
+        .pos_startpos()
+        .pos_moves("e2e4");
+
+    assert_eq!(
+        book.probe(&go_job.position().unwrap()),
+        Some("e7e5".to_string())
+    );
+}
+
+#[test]
+fn key_en_passant() {
+    let mut pos = Chess::default();
+
+    for uci in ["e2e4", "d7d5", "e4e5", "f7f5"] {
+        let m = uci.parse::<UciMove>().unwrap().to_move(&pos).unwrap();
+
+        pos.play_unchecked(m);
+    }
+
+    // reference key from the polyglot specification
+    assert_eq!(polyglot_key(&pos), 0x22a48b5a8e47ff78);
+
+    // bxc6 would expose the king to the rook, polyglot still hashes the c file
+    let pos: Chess = "8/8/8/KPp4r/8/8/8/4k3 w - c6 0 2"
+        .parse::<shakmaty::fen::Fen>()
+        .unwrap()
+        .into_position(CastlingMode::Standard)
+        .unwrap();
+
+    assert_eq!(
+        polyglot_key(&pos),
+        pos.zobrist_hash::<Zobrist64>(EnPassantMode::Legal).0 ^ 0x003a93d8b2806962
+    );
+}
@@ -0,0 +1,499 @@
+use log::{debug, log_enabled, warn, Level};
+
+use serde::{Deserialize, Serialize};
+
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use shakmaty::fen::Fen;
+use shakmaty::EnPassantMode;
+
+use crate::analysis::AnalysisInfo;
+use crate::uciengine::{GoJob, GoResult, GoResultSerde, MoveSource, SearchLimit};
+
+/// uci options that do not change what an engine finds and are left out of cache keys
+pub const IGNORED_OPTIONS: [&str; 5] = ["Hash", "Threads", "Ponder", "Clear Hash", "Move Overhead"];
+
+/// cache lookup of a go job, key and the limits the cached search has to reach
+#[derive(Debug, Clone, PartialEq)]
+pub struct CacheQuery {
+    /// engine identity, normalized fen ( without move counters ) and relevant options
+    pub key: String,
+    /// search limits of go job
+    pub limits: Vec<SearchLimit>,
+}
+
+/// cache query implementation
+impl CacheQuery {
+    /// create query for go job of engine identity, None if the go job is not cacheable
+    /// ( no search, invalid or variant position, time control, infinite or other go options )
+    pub fn new<T: core::fmt::Display>(identity: T, go_job: &GoJob) -> Option<Self> {
+        if !go_job.is_search() {
+            return None;
+        }
+
+        let mut limits = vec![];
+
+        for (key, value) in &go_job.go_options {
+            limits.push(match key.as_str() {
+                "depth" => SearchLimit::Depth(value.parse().ok()?),
+                "nodes" => SearchLimit::Nodes(value.parse().ok()?),
+                "movetime" => SearchLimit::Movetime(value.parse().ok()?),
+                _ => return None,
+            });
+        }
+
+        if limits.is_empty() {
+            return None;
+        }
+
+        let fen = Fen::from_position(&go_job.position()?, EnPassantMode::Legal).to_string();
+
+        let fen: Vec<&str> = fen.split_whitespace().take(4).collect();
+
+        let mut options: Vec<String> = go_job
+            .uci_options
+            .iter()
+            .filter(|(name, _)| !IGNORED_OPTIONS.contains(&name.as_str()))
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect();
+
+        options.sort();
+
+        Some(Self {
+            key: format!("{}|{}|{}", identity, fen.join(" "), options.join(";")),
+            limits,
+        })
+    }
+
+    /// true if analysis info of a search run with stored limits is deep enough for the query
+    fn satisfied_by(&self, ai: &AnalysisInfo, stored: &[SearchLimit]) -> bool {
+        self.limits.iter().all(|limit| match *limit {
+            SearchLimit::Depth(depth) => {
+                ai.depth >= depth
+                    || stored
+                        .iter()
+                        .any(|s| matches!(*s, SearchLimit::Depth(d) if d >= depth))
+            }
+            SearchLimit::Nodes(nodes) => {
+                ai.nodes >= nodes
+                    || stored
+                        .iter()
+                        .any(|s| matches!(*s, SearchLimit::Nodes(n) if n >= nodes))
+            }
+            SearchLimit::Movetime(movetime) => {
+                ai.time >= movetime
+                    || stored
+                        .iter()
+                        .any(|s| matches!(*s, SearchLimit::Movetime(t) if t >= movetime))
+            }
+        })
+    }
+}
+
+/// cache log line
+#[derive(Debug, Serialize, Deserialize)]
+struct CacheRecord {
+    /// cache key
+    key: String,
+    /// unix time in seconds when stored
+    stored: u64,
+    /// search limits the result was searched with
+    limits: Vec<SearchLimit>,
+    /// go result
+    result: GoResultSerde,
+}
+
+/// cached go result
+#[derive(Debug, Clone)]
+struct CacheEntry {
+    stored: u64,
+    /// store sequence number, orders entries stored within the same second
+    seq: u64,
+    limits: Vec<SearchLimit>,
+    result: GoResult,
+}
+
+/// log file
+#[derive(Debug)]
+struct CacheLog {
+    file: File,
+    /// lines in log file, compaction drops superseded lines
+    records: usize,
+    /// sequence number of the next store
+    seq: u64,
+}
+
+/// persistent analysis cache, an append-only jsonl log of go results that is
+/// loaded on open and compacted when it holds too many superseded or evicted entries
+///
+/// entries and log have separate locks, so that probes never wait for file io,
+/// stores do file io and are best run on a blocking thread
+#[derive(Debug)]
+pub struct AnalysisCache {
+    path: PathBuf,
+    entries: std::sync::Mutex<HashMap<String, CacheEntry>>,
+    /// taken before entries when both are needed, keeps the log in store order
+    log: std::sync::Mutex<CacheLog>,
+    /// entries older than ttl are neither returned nor kept on compaction
+    ttl: Option<Duration>,
+    /// oldest entries are evicted above this size
+    max_entries: usize,
+}
+
+/// unix time in seconds
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since| since.as_secs())
+        .unwrap_or(0)
+}
+
+/// analysis cache implementation
+impl AnalysisCache {
+    /// open cache file, creating it if it does not exist,
+    /// unreadable lines ( e.g. of an interrupted write ) are skipped
+    pub fn open<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+
+        let mut entries = HashMap::new();
+
+        let mut records = 0;
+
+        if path.exists() {
+            for line in BufReader::new(File::open(&path)?).lines() {
+                let line = line?;
+
+                match serde_json::from_str::<CacheRecord>(&line) {
+                    Ok(record) => {
+                        // the log is in store order
+                        entries.insert(
+                            record.key,
+                            CacheEntry {
+                                stored: record.stored,
+                                seq: records as u64,
+                                limits: record.limits,
+                                result: GoResult::from_serde(record.result),
+                            },
+                        );
+
+                        records += 1;
+                    }
+                    Err(err) => warn!("skipping cache line {} : {}", line, err),
+                }
+            }
+        }
+
+        if log_enabled!(Level::Debug) {
+            debug!("loaded {} cache entries from {:?}", entries.len(), path);
+        }
+
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+
+        Ok(Self {
+            path,
+            entries: std::sync::Mutex::new(entries),
+            log: std::sync::Mutex::new(CacheLog {
+                file,
+                records,
+                seq: records as u64,
+            }),
+            ttl: None,
+            max_entries: 100000,
+        })
+    }
+
+    /// set time to live and return self
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+
+        self
+    }
+
+    /// set maximum number of entries and return self
+    pub fn max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = max_entries.max(1);
+
+        self
+    }
+
+    /// number of entries, including expired ones not yet compacted away
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    /// true if cache has no entries
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// true if entry stored at unix time stored has expired
+    fn expired(&self, stored: u64) -> bool {
+        match self.ttl {
+            Some(ttl) => now().saturating_sub(stored) > ttl.as_secs(),
+            _ => false,
+        }
+    }
+
+    /// cached go result deep enough for query, with source set to cache
+    pub fn probe(&self, query: &CacheQuery) -> Option<GoResult> {
+        let entries = self.entries.lock().unwrap();
+
+        let entry = entries.get(&query.key)?;
+
+        if self.expired(entry.stored) || !query.satisfied_by(&entry.result.ai, &entry.limits) {
+            return None;
+        }
+
+        let mut go_result = entry.result.clone();
+
+        go_result.source = MoveSource::Cache;
+
+        Some(go_result)
+    }
+
+    /// store engine go result of query, unless a deeper result is already cached
+    pub fn store(&self, query: &CacheQuery, go_result: &GoResult) {
+        if go_result.bestmove.is_none() || go_result.source != MoveSource::Engine {
+            return;
+        }
+
+        let mut log = self.log.lock().unwrap();
+
+        let record = CacheRecord {
+            key: query.key.clone(),
+            stored: now(),
+            limits: query.limits.clone(),
+            result: go_result.to_serde(),
+        };
+
+        let compacted = {
+            let mut entries = self.entries.lock().unwrap();
+
+            if let Some(entry) = entries.get(&query.key) {
+                if !self.expired(entry.stored) && entry.result.ai.depth > go_result.ai.depth {
+                    return;
+                }
+            }
+
+            entries.insert(
+                record.key.clone(),
+                CacheEntry {
+                    stored: record.stored,
+                    seq: log.seq,
+                    limits: record.limits.clone(),
+                    result: go_result.clone(),
+                },
+            );
+
+            log.records += 1;
+
+            log.seq += 1;
+
+            let evict = entries.len() > self.max_entries;
+
+            if evict || log.records > 2 * entries.len() + 1000 {
+                Some(self.compact_entries(&mut entries))
+            } else {
+                None
+            }
+        };
+
+        // the rewritten log already holds the new entry
+        let write_result = match compacted {
+            Some(compacted) => self.rewrite(&mut log, &compacted),
+            _ => serde_json::to_string(&record)
+                .map_err(std::io::Error::from)
+                .and_then(|json| writeln!(log.file, "{}", json)),
+        };
+
+        if let Err(err) = write_result {
+            warn!("writing cache failed : {}", err);
+        }
+    }
+
+    /// drop expired entries, evict oldest entries above the size limit
+    /// and rewrite the log with the remaining entries only
+    pub fn compact(&self) -> std::io::Result<()> {
+        let mut log = self.log.lock().unwrap();
+
+        let compacted = self.compact_entries(&mut self.entries.lock().unwrap());
+
+        self.rewrite(&mut log, &compacted)
+    }
+
+    /// drop expired and evicted entries, returns the remaining entries in store order
+    fn compact_entries(
+        &self,
+        entries: &mut HashMap<String, CacheEntry>,
+    ) -> Vec<(String, CacheEntry)> {
+        let mut compacted: Vec<(String, CacheEntry)> = entries
+            .drain()
+            .filter(|(_, entry)| !self.expired(entry.stored))
+            .collect();
+
+        compacted.sort_by_key(|(_, entry)| entry.seq);
+
+        // evict down to nine tenths of the limit, so that eviction does not run on every store
+        if compacted.len() > self.max_entries {
+            let evicted = compacted.len() - (self.max_entries * 9 / 10).max(1);
+
+            compacted.drain(..evicted);
+        }
+
+        entries.extend(compacted.iter().cloned());
+
+        compacted
+    }
+
+    /// rewrite log with entries
+    fn rewrite(&self, log: &mut CacheLog, entries: &[(String, CacheEntry)]) -> std::io::Result<()> {
+        let tmp_path = self.path.with_extension("tmp");
+
+        let mut tmp = File::create(&tmp_path)?;
+
+        for (key, entry) in entries {
+            let record = CacheRecord {
+                key: key.clone(),
+                stored: entry.stored,
+                limits: entry.limits.clone(),
+                result: entry.result.to_serde(),
+            };
+
+            writeln!(tmp, "{}", serde_json::to_string(&record)?)?;
+        }
+
+        tmp.sync_all()?;
+
+        std::fs::rename(&tmp_path, &self.path)?;
+
+        log.file = OpenOptions::new().append(true).open(&self.path)?;
+        log.records = entries.len();
+
+        if log_enabled!(Level::Debug) {
+            debug!("compacted cache to {} entries", log.records);
+        }
+
+        Ok(())
+    }
+}
+
+#[test]
+fn cache_store_probe_reload() {
+    let path = std::env::temp_dir().join(format!("uciengine_cache_{}.jsonl", std::process::id()));
+
+    let _ = std::fs::remove_file(&path);
+
+    let job = |depth: usize| {
+        GoJob::new()
+            .pos_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1")
+            .uci_opt("Hash", 16)
+            .go_opt("depth", depth)
+    };
+
+    let query = CacheQuery::new("engine", &job(10)).unwrap();
+
+    // move counters and ignored options are not part of the key
+    let other = GoJob::new()
+        .pos_startpos()
+        .pos_moves("e2e4")
+        .uci_opt("Hash", 256)
+        .go_opt("depth", 10);
+
+    assert_eq!(CacheQuery::new("engine", &other).unwrap().key, query.key);
+    assert!(CacheQuery::new("engine", &job(10).go_opt("wtime", 1000)).is_none());
+
+    let mut ai = AnalysisInfo::new();
+
+    ai.depth = 10;
+
+    let go_result = GoResult {
+        bestmove: Some("e7e5".to_string()),
+        ponder: None,
+        ai,
+        source: MoveSource::Engine,
+    };
+
+    {
+        let cache = AnalysisCache::open(&path).unwrap();
+
+        cache.store(&query, &go_result);
+    }
+
+    let cache = AnalysisCache::open(&path).unwrap();
+
+    let cached = cache.probe(&query).unwrap();
+
+    assert_eq!(cached.bestmove, Some("e7e5".to_string()));
+    assert_eq!(cached.source, MoveSource::Cache);
+
+    assert!(cache
+        .probe(&CacheQuery::new("engine", &job(8)).unwrap())
+        .is_some());
+    assert!(cache
+        .probe(&CacheQuery::new("engine", &job(12)).unwrap())
+        .is_none());
+    assert!(cache
+        .probe(&CacheQuery::new("other", &job(8)).unwrap())
+        .is_none());
+
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn cache_evict_compact() {
+    let path = std::env::temp_dir().join(format!(
+        "uciengine_cache_evict_{}.jsonl",
+        std::process::id()
+    ));
+
+    let _ = std::fs::remove_file(&path);
+
+    let go_result = GoResult {
+        bestmove: Some("e2e4".to_string()),
+        ponder: None,
+        ai: AnalysisInfo::new(),
+        source: MoveSource::Engine,
+    };
+
+    let query = |index: usize| {
+        CacheQuery::new(
+            format!("engine{}", index),
+            &GoJob::new().pos_startpos().go_opt("depth", 1),
+        )
+        .unwrap()
+    };
+
+    let cached = |cache: &AnalysisCache| -> Vec<usize> {
+        (1..=5)
+            .filter(|index| cache.probe(&query(*index)).is_some())
+            .collect()
+    };
+
+    {
+        let cache = AnalysisCache::open(&path).unwrap().max_entries(3);
+
+        // all stores fall within the same second
+        for index in 1..=4 {
+            cache.store(&query(index), &go_result);
+        }
+
+        // the fourth store evicts the oldest entries down to nine tenths of the limit
+        assert_eq!(cached(&cache), vec![3, 4]);
+    }
+
+    // the rewritten log keeps the store order
+    let cache = AnalysisCache::open(&path).unwrap().max_entries(2);
+
+    assert_eq!(cached(&cache), vec![3, 4]);
+
+    cache.store(&query(5), &go_result);
+
+    assert_eq!(cached(&cache), vec![5]);
+    assert_eq!(cached(&AnalysisCache::open(&path).unwrap()), vec![5]);
+
+    let _ = std::fs::remove_file(&path);
+}
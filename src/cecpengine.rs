@@ -0,0 +1,630 @@
+use log::{debug, error, info, log_enabled, warn, Level};
+
+use std::collections::HashMap;
+use std::process::Stdio;
+use std::time::Duration;
+
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader, Lines};
+use tokio::process::{Child, Command};
+use tokio::sync::*;
+
+use shakmaty::san::{San, SanPlus};
+use shakmaty::uci::UciMove;
+use shakmaty::{CastlingMode, Chess, Color, Position};
+
+use crate::analysis::*;
+use crate::protocol;
+use crate::uciengine::{GoJob, GoResult, MoveSource, PosSpec};
+
+/// features announced by a cecp engine in reply to protover 2
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CecpFeatures {
+    /// feature values as sent, string values without quotes
+    pub values: HashMap<String, String>,
+}
+
+/// cecp features implementation
+impl CecpFeatures {
+    /// true if boolean feature is set to 1
+    pub fn enabled(&self, name: &str) -> bool {
+        self.values.get(name).map(|value| value == "1") == Some(true)
+    }
+
+    /// engine name ( myname feature )
+    pub fn name(&self) -> Option<String> {
+        self.values.get("myname").cloned()
+    }
+
+    /// true if engine supports variant
+    pub fn supports_variant(&self, variant: &str) -> bool {
+        self.values
+            .get("variants")
+            .map(|variants| variants.split(',').any(|v| v.trim() == variant))
+            == Some(true)
+    }
+
+    /// parse feature line, returns the names of the features parsed
+    pub fn parse(&mut self, line: &str) -> Vec<String> {
+        let mut names = vec![];
+
+        let mut rest = line
+            .trim()
+            .strip_prefix("feature")
+            .unwrap_or("")
+            .trim_start();
+
+        while let Some(eq) = rest.find('=') {
+            let name = rest[..eq].trim().to_string();
+
+            rest = &rest[eq + 1..];
+
+            let value = if let Some(quoted) = rest.strip_prefix('"') {
+                let end = quoted.find('"').unwrap_or(quoted.len());
+
+                rest = quoted.get(end + 1..).unwrap_or("");
+
+                quoted[..end].to_string()
+            } else {
+                let end = rest.find(' ').unwrap_or(rest.len());
+
+                let value = rest[..end].to_string();
+
+                rest = &rest[end..];
+
+                value
+            };
+
+            rest = rest.trim_start();
+
+            self.values.insert(name.clone(), value);
+
+            names.push(name);
+        }
+
+        names
+    }
+}
+
+/// move in uci notation, from uci or san notation
+fn to_uci(pos: &Chess, m: &str) -> Option<String> {
+    if let Ok(uci) = UciMove::from_ascii(m.as_bytes()) {
+        if uci.to_move(pos).is_ok() {
+            return Some(m.to_string());
+        }
+    }
+
+    SanPlus::from_ascii(m.as_bytes())
+        .ok()
+        .and_then(|san_plus| san_plus.san.to_move(pos).ok())
+        .map(|m| m.to_uci(CastlingMode::Standard).to_string())
+}
+
+/// parse thinking output ( ply score time nodes pv ) into analysis info,
+/// the pv is converted to uci notation if the position is known, None if not thinking output
+pub fn parse_thinking(line: &str, pos: Option<&Chess>) -> Option<AnalysisInfo> {
+    let mut words = line.split_whitespace();
+
+    let mut number = || words.next()?.parse::<i64>().ok();
+
+    let (depth, score, time, nodes) = (number()?, number()?, number()?, number()?);
+
+    let mut ai = AnalysisInfo::new();
+
+    ai.depth = depth.max(0) as usize;
+    // mate scores are sent as 100000 + moves to mate
+    ai.score = if score.abs() > 100000 - 1000 && score.abs() < 100000 + 1000 {
+        Score::Mate((score.signum() * (score.abs() - 100000).abs()) as i32)
+    } else {
+        Score::Cp(score as i32)
+    };
+    ai.time = time.max(0) as usize * 10;
+    ai.nodes = nodes.max(0) as u64;
+
+    if ai.time > 0 {
+        ai.nps = ai.nodes * 1000 / ai.time as u64;
+    }
+
+    let pv: Vec<String> = match pos {
+        Some(pos) => {
+            let mut pos = pos.clone();
+
+            let mut pv = vec![];
+
+            // move numbers and annotations are skipped, the pv ends at the first illegal move
+            for word in words.filter(|word| !word.ends_with('.') && !word.starts_with(['<', '('])) {
+                let uci = match to_uci(&pos, word) {
+                    Some(uci) => uci,
+                    _ => break,
+                };
+
+                let m = UciMove::from_ascii(uci.as_bytes())
+                    .ok()?
+                    .to_move(&pos)
+                    .ok()?;
+
+                pos.play_unchecked(m);
+
+                pv.push(uci);
+            }
+
+            pv
+        }
+        _ => words.map(|word| word.to_string()).collect(),
+    };
+
+    if !pv.is_empty() {
+        ai.set_pv(pv.join(" "));
+    }
+
+    Some(ai)
+}
+
+/// cecp ( xboard ) engine, driven with go jobs like UciEngine
+///
+/// uci options other than UCI_Variant, ponder jobs and go options other than depth,
+/// movetime and the time control are not supported
+pub struct CecpEngine {
+    gtx: mpsc::UnboundedSender<GoJob>,
+    ktx: std::sync::Mutex<Option<oneshot::Sender<()>>>,
+    features: std::sync::Arc<std::sync::Mutex<CecpFeatures>>,
+    pub ai: std::sync::Arc<std::sync::Mutex<AnalysisInfo>>,
+    pub atx: std::sync::Arc<broadcast::Sender<AnalysisInfo>>,
+}
+
+/// cecp engine implementation
+impl CecpEngine {
+    /// create new cecp engine
+    pub fn new<T>(path: T) -> std::sync::Arc<CecpEngine>
+    where
+        T: core::fmt::Display,
+    {
+        Self::spawn(Command::new(path.to_string())).expect("failed to spawn engine")
+    }
+
+    /// create new cecp engine from command, its stdin and stdout are piped to the engine
+    pub fn spawn(mut command: Command) -> std::io::Result<std::sync::Arc<CecpEngine>> {
+        let mut child = command
+            .stdout(Stdio::piped())
+            .stdin(Stdio::piped())
+            .spawn()?;
+
+        let stdout = child.stdout.take().unwrap();
+        let stdin = child.stdin.take().unwrap();
+
+        Ok(Self::start(stdout, stdin, Some(child)))
+    }
+
+    /// create new cecp engine talking over reader and writer
+    pub fn from_io<R, W>(reader: R, writer: W) -> std::sync::Arc<CecpEngine>
+    where
+        R: AsyncRead + Unpin + Send + 'static,
+        W: AsyncWrite + Unpin + Send + 'static,
+    {
+        Self::start(reader, writer, None)
+    }
+
+    /// start session task on transport, child is the engine process if any
+    fn start<R, W>(reader: R, writer: W, child: Option<Child>) -> std::sync::Arc<CecpEngine>
+    where
+        R: AsyncRead + Unpin + Send + 'static,
+        W: AsyncWrite + Unpin + Send + 'static,
+    {
+        let (gtx, grx) = mpsc::unbounded_channel::<GoJob>();
+
+        let (ktx, krx) = oneshot::channel::<()>();
+
+        let (atx, _) = broadcast::channel::<AnalysisInfo>(20);
+
+        let session = CecpSession {
+            lines: BufReader::new(reader).lines(),
+            writer,
+            features: std::sync::Arc::new(std::sync::Mutex::new(CecpFeatures::default())),
+            ai: std::sync::Arc::new(std::sync::Mutex::new(AnalysisInfo::new())),
+            atx: std::sync::Arc::new(atx),
+        };
+
+        let engine = CecpEngine {
+            gtx,
+            ktx: std::sync::Mutex::new(Some(ktx)),
+            features: session.features.clone(),
+            ai: session.ai.clone(),
+            atx: session.atx.clone(),
+        };
+
+        tokio::spawn(async move {
+            let mut child = child;
+
+            tokio::select! {
+                result = session.run(grx) => {
+                    if let Err(err) = result {
+                        if log_enabled!(Level::Error) {
+                            error!("cecp session failed {:?}", err);
+                        }
+                    }
+                }
+                _ = krx => {
+                    if log_enabled!(Level::Info) {
+                        info!("killing cecp engine");
+                    }
+                }
+            }
+
+            if let Some(child) = &mut child {
+                let _ = child.kill().await;
+            }
+        });
+
+        std::sync::Arc::new(engine)
+    }
+
+    /// features announced by the engine, empty until the handshake is done
+    pub fn features(&self) -> CecpFeatures {
+        self.features.lock().unwrap().clone()
+    }
+
+    /// get analysis info
+    pub fn get_ai(&self) -> AnalysisInfo {
+        *self.ai.lock().unwrap()
+    }
+
+    /// issue go job, custom commands are sent as they are
+    pub fn go(&self, go_job: GoJob) -> oneshot::Receiver<GoResult> {
+        let mut go_job = go_job;
+
+        let (rtx, rrx) = oneshot::channel();
+
+        go_job.rtx = Some(rtx);
+
+        let send_result = self.gtx.send(go_job);
+
+        if log_enabled!(Level::Debug) {
+            debug!("send cecp go job result {:?}", send_result);
+        }
+
+        rrx
+    }
+
+    /// quit engine
+    pub fn quit(&self) {
+        self.go(GoJob::new().custom("quit"));
+    }
+
+    /// kill engine process, or close the connection if there is no process
+    pub fn kill(&self) {
+        if let Some(ktx) = self.ktx.lock().unwrap().take() {
+            let _ = ktx.send(());
+        }
+    }
+}
+
+/// kill engine process when the handle goes away, so that it does not outlive it
+impl Drop for CecpEngine {
+    fn drop(&mut self) {
+        self.kill();
+    }
+}
+
+/// cecp session, owns the transport
+struct CecpSession<R, W> {
+    lines: Lines<BufReader<R>>,
+    writer: W,
+    features: std::sync::Arc<std::sync::Mutex<CecpFeatures>>,
+    ai: std::sync::Arc<std::sync::Mutex<AnalysisInfo>>,
+    atx: std::sync::Arc<broadcast::Sender<AnalysisInfo>>,
+}
+
+/// cecp session implementation
+impl<R, W> CecpSession<R, W>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    /// send command
+    async fn send<T: core::fmt::Display>(&mut self, command: T) -> std::io::Result<()> {
+        let command = format!("{}", command);
+
+        if log_enabled!(Level::Debug) {
+            debug!("cecp engine in : {}", command);
+        }
+
+        self.writer
+            .write_all(format!("{}\n", command).as_bytes())
+            .await?;
+
+        self.writer.flush().await
+    }
+
+    /// read line, error if engine output closed
+    async fn read_line(&mut self) -> std::io::Result<String> {
+        let line = self.lines.next_line().await?.ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "engine output closed")
+        })?;
+
+        if log_enabled!(Level::Debug) {
+            debug!("cecp engine out : {}", line);
+        }
+
+        Ok(line)
+    }
+
+    /// negotiate features, waits for done=1, or two seconds for engines not sending done
+    async fn handshake(&mut self) -> std::io::Result<()> {
+        self.send("xboard").await?;
+        self.send("protover 2").await?;
+
+        let mut timeout = Some(Duration::from_millis(2000));
+
+        loop {
+            let line = match timeout {
+                Some(duration) => match tokio::time::timeout(duration, self.read_line()).await {
+                    Ok(line) => line?,
+                    _ => break,
+                },
+                _ => self.read_line().await?,
+            };
+
+            if !line.starts_with("feature ") {
+                continue;
+            }
+
+            let names = self.features.lock().unwrap().parse(&line);
+
+            for name in names {
+                self.send(format!("accepted {}", name)).await?;
+            }
+
+            let done = self.features.lock().unwrap().values.get("done").cloned();
+
+            match done.as_deref() {
+                Some("1") => break,
+                // done=0 asks for more time
+                Some("0") => timeout = None,
+                _ => {}
+            }
+        }
+
+        // no pondering, the engine only thinks on go
+        self.send("easy").await
+    }
+
+    /// run session, processing go jobs until the job channel or the engine output closes
+    async fn run(mut self, grx: mpsc::UnboundedReceiver<GoJob>) -> std::io::Result<()> {
+        let mut grx = grx;
+
+        self.handshake().await?;
+
+        while let Some(go_job) = grx.recv().await {
+            if let Some(command) = &go_job.custom_command {
+                self.send(command).await?;
+
+                continue;
+            }
+
+            let go_result = if go_job.is_search() {
+                self.search(&go_job).await?
+            } else {
+                warn!("cecp engine does not support ponder jobs");
+
+                None
+            };
+
+            if let (Some(rtx), Some(go_result)) = (go_job.rtx, go_result) {
+                let _ = rtx.send(go_result);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// set up position and time control, then search, None if the position could not be set
+    async fn search(&mut self, go_job: &GoJob) -> std::io::Result<Option<GoResult>> {
+        let features = self.features.lock().unwrap().clone();
+
+        self.send("new").await?;
+        self.send("force").await?;
+
+        if let Some(variant) = go_job.uci_options.get("UCI_Variant") {
+            if variant != "chess" {
+                if !features.supports_variant(variant) {
+                    warn!("cecp engine does not support variant {}", variant);
+                }
+
+                self.send(format!("variant {}", variant)).await?;
+            }
+        }
+
+        let start = match go_job.pos_spec {
+            PosSpec::Fen => protocol::Position::Fen(go_job.pos_fen.clone().unwrap_or_default()),
+            _ => protocol::Position::Startpos,
+        };
+
+        if let protocol::Position::Fen(fen) = &start {
+            if !features.enabled("setboard") {
+                warn!("cecp engine does not support setboard");
+
+                return Ok(None);
+            }
+
+            self.send(format!("setboard {}", fen)).await?;
+        }
+
+        // moves are played on a chess position to convert them to san if the engine asks for it
+        let mut pos = start.to_chess(&[]);
+
+        let moves: Vec<String> = go_job
+            .pos_moves
+            .as_deref()
+            .unwrap_or("")
+            .split_whitespace()
+            .map(|m| m.to_string())
+            .collect();
+
+        for uci in moves {
+            let m = pos
+                .as_ref()
+                .and_then(|pos| UciMove::from_ascii(uci.as_bytes()).ok()?.to_move(pos).ok());
+
+            let notation = match (&m, &pos) {
+                (Some(m), Some(pos)) if features.enabled("san") => {
+                    San::from_move(pos, *m).to_string()
+                }
+                _ => uci.to_string(),
+            };
+
+            pos = match (m, pos) {
+                (Some(m), Some(mut pos)) => {
+                    pos.play_unchecked(m);
+
+                    Some(pos)
+                }
+                _ => None,
+            };
+
+            if features.enabled("usermove") {
+                self.send(format!("usermove {}", notation)).await?;
+            } else {
+                self.send(notation).await?;
+            }
+        }
+
+        for command in time_commands(go_job, pos.as_ref().map(|pos| pos.turn())) {
+            self.send(command).await?;
+        }
+
+        self.send("post").await?;
+        self.send("go").await?;
+
+        *self.ai.lock().unwrap() = AnalysisInfo::new();
+
+        loop {
+            let line = self.read_line().await?;
+
+            if let Some(m) = line.strip_prefix("move ") {
+                let bestmove = match &pos {
+                    Some(pos) => to_uci(pos, m.trim()),
+                    _ => Some(m.trim().to_string()),
+                };
+
+                let mut ai = self.ai.lock().unwrap();
+
+                ai.done = true;
+
+                let _ = self.atx.send(*ai);
+
+                return Ok(Some(GoResult {
+                    bestmove,
+                    ponder: None,
+                    ai: *ai,
+                    source: MoveSource::Engine,
+                }));
+            }
+
+            if line.starts_with("resign")
+                || line.starts_with("1-0")
+                || line.starts_with("0-1")
+                || line.starts_with("1/2-1/2")
+            {
+                return Ok(Some(GoResult {
+                    bestmove: None,
+                    ponder: None,
+                    ai: *self.ai.lock().unwrap(),
+                    source: MoveSource::Engine,
+                }));
+            }
+
+            if line.starts_with("Illegal move") || line.starts_with("Error") {
+                warn!("cecp engine error : {}", line);
+
+                continue;
+            }
+
+            if let Some(ai) = parse_thinking(&line, pos.as_ref()) {
+                *self.ai.lock().unwrap() = ai;
+
+                let _ = self.atx.send(ai);
+            }
+        }
+    }
+}
+
+/// time control commands of go job for engine playing color,
+/// depth maps to sd, movetime to st and the clock to level, time and otim
+fn time_commands(go_job: &GoJob, color: Option<Color>) -> Vec<String> {
+    let option = |key: &str| {
+        go_job
+            .go_options
+            .get(key)
+            .and_then(|value| value.trim().parse::<u64>().ok())
+    };
+
+    let mut commands = vec![];
+
+    if let Some(depth) = option("depth") {
+        commands.push(format!("sd {}", depth));
+    }
+
+    if let Some(movetime) = option("movetime") {
+        // st is given in whole seconds
+        commands.push(format!("st {}", movetime.div_ceil(1000).max(1)));
+
+        return commands;
+    }
+
+    let white = color.unwrap_or(Color::White) == Color::White;
+
+    let (time, inc, otime) = if white {
+        (option("wtime"), option("winc"), option("btime"))
+    } else {
+        (option("btime"), option("binc"), option("wtime"))
+    };
+
+    if let Some(time) = time {
+        let inc = inc.unwrap_or(0);
+
+        let seconds = time / 1000;
+
+        // base is given as minutes:seconds and increment in seconds
+        commands.push(format!(
+            "level {} {}:{:02} {}",
+            option("movestogo").unwrap_or(0),
+            seconds / 60,
+            seconds % 60,
+            inc as f64 / 1000.0
+        ));
+
+        commands.push(format!("time {}", time / 10));
+
+        if let Some(otime) = otime {
+            commands.push(format!("otim {}", otime / 10));
+        }
+    }
+
+    commands
+}
+
+#[test]
+fn parse_features_and_thinking() {
+    let mut features = CecpFeatures::default();
+
+    let names = features.parse(
+        r#"feature myname="Fairy Max 5.0" setboard=1 san=0 variants="normal,shatranj" done=1"#,
+    );
+
+    assert_eq!(names, vec!["myname", "setboard", "san", "variants", "done"]);
+    assert_eq!(features.name(), Some("Fairy Max 5.0".to_string()));
+    assert!(features.enabled("setboard"));
+    assert!(!features.enabled("san"));
+    assert!(features.supports_variant("shatranj"));
+
+    let ai = parse_thinking("9 156 1084 48000 1. Nf3 Nc6", Some(&Chess::default())).unwrap();
+
+    assert_eq!(ai.depth, 9);
+    assert_eq!(ai.time, 10840);
+    assert_eq!(ai.pv(), Some("g1f3 b8c6".to_string()));
+    assert_eq!(ai.bestmove(), Some("g1f3".to_string()));
+
+    let ai = parse_thinking("12 -100003 50 100 e2e4", None).unwrap();
+
+    assert_eq!(ai.score, Score::Mate(-3));
+    assert!(parse_thinking("move e2e4", None).is_none());
+}
@@ -0,0 +1,466 @@
+use log::{info, log_enabled, Level};
+
+use serde::{Deserialize, Serialize};
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use thiserror::Error;
+
+use crate::pool::EnginePool;
+use crate::protocol::{GuiToEngine, OptionType, UciOption};
+use crate::uciengine::{EngineId, GoJob, UciEngine};
+
+/// ConfigError captures possible engine configuration errors
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    #[error("reading config failed : {0}")]
+    Io(#[from] std::io::Error),
+    #[error("invalid toml config : {0}")]
+    Toml(#[from] toml::de::Error),
+    #[error("invalid json config : {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("unknown profile '{0}'")]
+    UnknownProfile(String),
+    #[error("engine '{0}' did not answer uci")]
+    NoUciOk(String),
+    #[error("engine '{0}' did not answer isready")]
+    NoReadyOk(String),
+    #[error("engine does not have option '{0}'")]
+    UnknownOption(String),
+    #[error("invalid value '{1}' of option '{0}' : {2}")]
+    InvalidValue(String, String, String),
+    #[error("engine does not support variant '{0}'")]
+    UnsupportedVariant(String),
+    #[error("cpu affinity is only supported on linux")]
+    AffinityUnsupported,
+}
+
+/// option or go limit value, as written in the config file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ConfigValue {
+    /// boolean, for check options
+    Bool(bool),
+    /// integer, for spin options
+    Int(i64),
+    /// float, passed on as written
+    Float(f64),
+    /// string, for combo and string options
+    String(String),
+}
+
+/// display config value as sent to the engine
+impl std::fmt::Display for ConfigValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigValue::Bool(value) => write!(f, "{}", value),
+            ConfigValue::Int(value) => write!(f, "{}", value),
+            ConfigValue::Float(value) => write!(f, "{}", value),
+            ConfigValue::String(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+/// engine profile
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EngineProfile {
+    /// engine executable
+    pub path: String,
+    /// engine command line arguments
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// uci options
+    #[serde(default)]
+    pub options: BTreeMap<String, ConfigValue>,
+    /// default go options, e.g. depth or movetime
+    #[serde(default)]
+    pub go: BTreeMap<String, ConfigValue>,
+    /// variant, sent as UCI_Variant unless standard chess
+    #[serde(default)]
+    pub variant: Option<String>,
+    /// cpus the engine may run on, all cpus the process may run on if None
+    #[serde(default)]
+    pub cpus: Option<Vec<usize>>,
+    /// pin each engine of a pool to cpus of its own, as many as its Threads option
+    #[serde(default)]
+    pub pin_threads: bool,
+}
+
+/// named engine profiles
+///
+/// ### Example
+/// ```
+/// use uciengine::config::EngineConfig;
+///
+/// let config = EngineConfig::from_toml(r#"
+/// [engines.stockfish]
+/// path = "./stockfish12"
+/// [engines.stockfish.options]
+/// Hash = 128
+/// Threads = 4
+/// [engines.stockfish.go]
+/// depth = 20
+/// "#).unwrap();
+///
+/// assert_eq!(config.profile("stockfish").unwrap().path, "./stockfish12");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct EngineConfig {
+    /// profiles by name
+    #[serde(default)]
+    pub engines: BTreeMap<String, EngineProfile>,
+}
+
+/// true if config value is valid for uci option type, error reason otherwise
+fn check_value(option_type: &OptionType, value: &ConfigValue) -> Result<(), String> {
+    let text = value.to_string();
+
+    match option_type {
+        OptionType::Check { .. } => match text.as_str() {
+            "true" | "false" => Ok(()),
+            _ => Err("expected true or false".to_string()),
+        },
+        OptionType::Spin { min, max, .. } => match text.parse::<i64>() {
+            Ok(value) if value >= *min && value <= *max => Ok(()),
+            Ok(_) => Err(format!("out of range {} - {}", min, max)),
+            _ => Err("expected integer".to_string()),
+        },
+        OptionType::Combo { vars, .. } => {
+            if vars.iter().any(|var| var.eq_ignore_ascii_case(&text)) {
+                Ok(())
+            } else {
+                Err(format!("expected one of {}", vars.join(", ")))
+            }
+        }
+        OptionType::Button | OptionType::String { .. } => Ok(()),
+    }
+}
+
+/// option of engine, option names are case insensitive
+fn find_option<'a>(engine_id: &'a EngineId, name: &str) -> Option<&'a UciOption> {
+    engine_id
+        .options
+        .iter()
+        .find(|option| option.name.eq_ignore_ascii_case(name))
+}
+
+/// engine profile implementation
+impl EngineProfile {
+    /// create new profile of engine path
+    pub fn new<T: core::fmt::Display>(path: T) -> Self {
+        Self {
+            path: path.to_string(),
+            args: vec![],
+            options: BTreeMap::new(),
+            go: BTreeMap::new(),
+            variant: None,
+            cpus: None,
+            pin_threads: false,
+        }
+    }
+
+    /// go job with options, variant and default go options of profile,
+    /// options and go options set later on the job override them
+    pub fn go_job(&self) -> GoJob {
+        let mut go_job = GoJob::new();
+
+        for (name, value) in &self.options {
+            go_job = go_job.uci_opt(name, value);
+        }
+
+        if let Some(variant) = self.variant.as_ref().filter(|variant| *variant != "chess") {
+            go_job = go_job.uci_opt("UCI_Variant", variant);
+        }
+
+        for (key, value) in &self.go {
+            go_job = go_job.go_opt(key, value);
+        }
+
+        go_job
+    }
+
+    /// check options and variant against the options declared by the engine
+    pub fn validate(&self, engine_id: &EngineId) -> Result<(), ConfigError> {
+        for (name, value) in &self.options {
+            let option = find_option(engine_id, name)
+                .ok_or_else(|| ConfigError::UnknownOption(name.to_string()))?;
+
+            check_value(&option.option_type, value).map_err(|reason| {
+                ConfigError::InvalidValue(name.to_string(), value.to_string(), reason)
+            })?;
+        }
+
+        if let Some(variant) = &self.variant {
+            let supported = match find_option(engine_id, "UCI_Variant") {
+                Some(option) => check_value(
+                    &option.option_type,
+                    &ConfigValue::String(variant.to_string()),
+                )
+                .is_ok(),
+                _ => variant == "chess",
+            };
+
+            if !supported {
+                return Err(ConfigError::UnsupportedVariant(variant.to_string()));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// start engine, validate the profile against its option list and set the options,
+    /// the engine is shut down if it does not answer uci or isready within timeout
+    /// or validation fails
+    pub async fn start(&self, timeout: Duration) -> Result<Arc<UciEngine>, ConfigError> {
+        self.start_on(self.cpus.clone(), timeout).await
+    }
+
+    /// spawn engine process, pinned to cpus if given
+    fn spawn(&self, cpus: Option<Vec<usize>>) -> Result<Arc<UciEngine>, ConfigError> {
+        let mut command = tokio::process::Command::new(&self.path);
+
+        command.args(&self.args);
+
+        match cpus {
+            #[cfg(target_os = "linux")]
+            Some(cpus) => Ok(UciEngine::spawn_sandboxed(
+                command,
+                &crate::sandbox::SpawnOptions::new().cpus(cpus),
+            )?),
+            #[cfg(not(target_os = "linux"))]
+            Some(_) => Err(ConfigError::AffinityUnsupported),
+            _ => Ok(UciEngine::spawn(command)?),
+        }
+    }
+
+    /// send options and variant of profile to engine
+    fn send_options(&self, engine: &UciEngine) {
+        for command in self.go_job().option_commands() {
+            engine.go(GoJob::new().custom(command));
+        }
+    }
+
+    /// spawn engine replacing a crashed one, the options are sent without validation
+    fn respawn(&self, cpus: Option<Vec<usize>>) -> Result<Arc<UciEngine>, ConfigError> {
+        let engine = self.spawn(cpus)?;
+
+        engine.go(GoJob::new().custom(GuiToEngine::Uci));
+
+        self.send_options(&engine);
+
+        Ok(engine)
+    }
+
+    /// value of Threads option, 1 if not set
+    #[cfg(target_os = "linux")]
+    fn threads(&self) -> usize {
+        self.options
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case("Threads"))
+            .and_then(|(_, value)| value.to_string().parse().ok())
+            .unwrap_or(1)
+    }
+
+    /// cpus of each engine of a pool of size engines, None for all cpus
+    fn pool_cpus(&self, size: usize) -> Result<Vec<Option<Vec<usize>>>, ConfigError> {
+        if !self.pin_threads {
+            return Ok(vec![self.cpus.clone(); size]);
+        }
+
+        #[cfg(target_os = "linux")]
+        {
+            let cpus = match &self.cpus {
+                Some(cpus) => cpus.clone(),
+                _ => crate::sandbox::read_affinity(0)?,
+            };
+
+            Ok(crate::sandbox::partition_cpus(
+                &cpus,
+                &crate::sandbox::numa_nodes(),
+                &vec![self.threads(); size],
+            )
+            .into_iter()
+            .map(Some)
+            .collect())
+        }
+
+        #[cfg(not(target_os = "linux"))]
+        Err(ConfigError::AffinityUnsupported)
+    }
+
+    /// start engine pinned to cpus, see start
+    async fn start_on(
+        &self,
+        cpus: Option<Vec<usize>>,
+        timeout: Duration,
+    ) -> Result<Arc<UciEngine>, ConfigError> {
+        let engine = self.spawn(cpus)?;
+
+        let validated = match engine.identify(timeout).await {
+            Some(engine_id) => self.validate(&engine_id),
+            _ => Err(ConfigError::NoUciOk(self.path.to_string())),
+        };
+
+        // options are set on the engine, jobs need not be built from the profile
+        let configured = match validated {
+            Ok(_) => {
+                self.send_options(&engine);
+
+                if engine.is_ready(timeout).await {
+                    Ok(())
+                } else {
+                    Err(ConfigError::NoReadyOk(self.path.to_string()))
+                }
+            }
+            err => err,
+        };
+
+        if let Err(err) = configured {
+            engine.shutdown(timeout).await;
+
+            return Err(err);
+        }
+
+        if log_enabled!(Level::Info) {
+            info!("started engine {} with validated profile", self.path);
+        }
+
+        Ok(engine)
+    }
+
+    /// start pool of size engines, see start, with pin_threads the cpus
+    /// are partitioned among the engines ( keeping each engine on one numa node if possible ),
+    /// crashed engines are restarted on the same cpus when next acquired
+    pub async fn start_pool(
+        &self,
+        size: usize,
+        timeout: Duration,
+    ) -> Result<Arc<EnginePool>, ConfigError> {
+        let pool_cpus = self.pool_cpus(size)?;
+
+        let mut engines = vec![];
+
+        for cpus in pool_cpus.iter().cloned() {
+            match self.start_on(cpus, timeout).await {
+                Ok(engine) => engines.push(engine),
+                Err(err) => {
+                    for engine in engines {
+                        engine.shutdown(timeout).await;
+                    }
+
+                    return Err(err);
+                }
+            }
+        }
+
+        let profile = self.clone();
+
+        Ok(EnginePool::respawning(engines, move |index| {
+            profile
+                .respawn(pool_cpus[index].clone())
+                .map_err(|err| std::io::Error::other(err.to_string()))
+        }))
+    }
+}
+
+/// engine config implementation
+impl EngineConfig {
+    /// parse toml config
+    pub fn from_toml(toml: &str) -> Result<Self, ConfigError> {
+        Ok(toml::from_str(toml)?)
+    }
+
+    /// parse json config
+    pub fn from_json(json: &str) -> Result<Self, ConfigError> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// load config file, json if the extension is json, toml otherwise
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
+        let text = std::fs::read_to_string(&path)?;
+
+        match path.as_ref().extension().and_then(|ext| ext.to_str()) {
+            Some("json") => Self::from_json(&text),
+            _ => Self::from_toml(&text),
+        }
+    }
+
+    /// profile of name
+    pub fn profile(&self, name: &str) -> Result<&EngineProfile, ConfigError> {
+        self.engines
+            .get(name)
+            .ok_or_else(|| ConfigError::UnknownProfile(name.to_string()))
+    }
+
+    /// start engine of profile name, see EngineProfile::start
+    pub async fn start(
+        &self,
+        name: &str,
+        timeout: Duration,
+    ) -> Result<Arc<UciEngine>, ConfigError> {
+        self.profile(name)?.start(timeout).await
+    }
+
+    /// start pool of size engines of profile name, see EngineProfile::start
+    pub async fn start_pool(
+        &self,
+        name: &str,
+        size: usize,
+        timeout: Duration,
+    ) -> Result<Arc<EnginePool>, ConfigError> {
+        self.profile(name)?.start_pool(size, timeout).await
+    }
+}
+
+#[test]
+fn validate_profile() {
+    let json = r#"{"engines":{"mock":{"path":"mock","options":{"Hash":64,"Ponder":false},
+        "go":{"depth":12},"variant":"atomic"}}}"#;
+
+    let profile = EngineConfig::from_json(json)
+        .unwrap()
+        .profile("mock")
+        .unwrap()
+        .clone();
+
+    let option = |line: &str| match line.parse() {
+        Ok(crate::protocol::EngineToGui::Option(option)) => option,
+        _ => panic!("invalid option {}", line),
+    };
+
+    let mut engine_id = EngineId {
+        name: Some("mock".to_string()),
+        author: None,
+        options: vec![
+            option("option name Hash type spin default 16 min 1 max 128"),
+            option("option name Ponder type check default false"),
+            option("option name UCI_Variant type combo default chess var chess var atomic"),
+        ],
+    };
+
+    assert!(profile.validate(&engine_id).is_ok());
+
+    let go_commands = profile.go_job().pos_startpos().to_commands();
+
+    assert!(go_commands.contains(&"setoption name UCI_Variant value atomic".to_string()));
+    assert_eq!(go_commands.last().unwrap(), "go depth 12");
+
+    engine_id.options[0] = option("option name Hash type spin default 16 min 1 max 32");
+
+    assert!(matches!(
+        profile.validate(&engine_id),
+        Err(ConfigError::InvalidValue(..))
+    ));
+
+    engine_id.options[0] = option("option name Hash type spin default 16 min 1 max 128");
+
+    engine_id.options.truncate(2);
+
+    assert!(matches!(
+        profile.validate(&engine_id),
+        Err(ConfigError::UnsupportedVariant(..))
+    ));
+}
@@ -0,0 +1,333 @@
+use log::{debug, log_enabled, warn, Level};
+
+use std::io::{BufRead, Write};
+use std::sync::{Arc, Mutex};
+
+use crate::analysis::AnalysisInfo;
+use crate::protocol::*;
+
+/// output of an engine implementation to the gui, clones share the same writer
+/// so that search threads can send info and bestmove lines
+#[derive(Clone)]
+pub struct UciOutput {
+    writer: Arc<Mutex<Box<dyn Write + Send>>>,
+}
+
+/// debug uci output
+impl std::fmt::Debug for UciOutput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "UciOutput")
+    }
+}
+
+/// uci output implementation
+impl UciOutput {
+    /// create output writing to writer
+    pub fn from_writer<W: Write + Send + 'static>(writer: W) -> Self {
+        Self {
+            writer: Arc::new(Mutex::new(Box::new(writer))),
+        }
+    }
+
+    /// create output writing to stdout
+    pub fn stdout() -> Self {
+        Self::from_writer(std::io::stdout())
+    }
+
+    /// send message, every message is flushed
+    pub fn send(&self, message: &EngineToGui) {
+        let line = message.to_string();
+
+        if log_enabled!(Level::Debug) {
+            debug!("engine impl out : {}", line);
+        }
+
+        let mut writer = self.writer.lock().unwrap();
+
+        let write_result = writeln!(writer, "{}", line).and_then(|_| writer.flush());
+
+        if let Err(err) = write_result {
+            warn!("writing engine output failed {:?}", err);
+        }
+    }
+
+    /// send info line of analysis info
+    pub fn info(&self, ai: &AnalysisInfo) {
+        self.send(&EngineToGui::from_analysis_info(ai));
+    }
+
+    /// send info string
+    pub fn info_string<T: std::fmt::Display>(&self, string: T) {
+        self.send(&EngineToGui::Info(vec![InfoItem::String(format!(
+            "{}",
+            string
+        ))]));
+    }
+
+    /// send bestmove
+    pub fn bestmove<T: std::fmt::Display>(&self, bestmove: T, ponder: Option<T>) {
+        self.send(&EngineToGui::BestMove {
+            bestmove: format!("{}", bestmove),
+            ponder: ponder.map(|ponder| format!("{}", ponder)),
+        });
+    }
+}
+
+/// hooks of an engine speaking uci through UciDriver
+///
+/// go may search synchronously and send bestmove before it returns, then stop is never
+/// seen during the search; or it may start a search thread and return at once, then stop
+/// must make the search send its bestmove
+pub trait UciEngineImpl {
+    /// engine name sent as id name
+    fn name(&self) -> String;
+
+    /// engine author sent as id author
+    fn author(&self) -> String;
+
+    /// options announced after id
+    fn options(&self) -> Vec<UciOption> {
+        vec![]
+    }
+
+    /// set option, an error is reported to the gui as info string
+    fn set_option(&mut self, _name: &str, _value: Option<&str>) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// start new game
+    fn new_game(&mut self) {}
+
+    /// set position, use Position::to_chess to get a standard chess position
+    fn set_position(&mut self, position: &Position, moves: &[String]);
+
+    /// start search, bestmove is sent through output
+    fn go(&mut self, params: &GoParams, output: &UciOutput);
+
+    /// stop search, a running search has to send bestmove
+    fn stop(&mut self);
+
+    /// opponent played the expected move, the ponder search goes on as normal search
+    fn ponderhit(&mut self) {}
+}
+
+/// uci protocol loop reading gui commands and dispatching them to engine hooks
+#[derive(Debug)]
+pub struct UciDriver<E: UciEngineImpl> {
+    /// engine implementation
+    engine: E,
+    /// output to gui
+    output: UciOutput,
+    /// debug mode, set by the gui, reports invalid commands as info string
+    debug: bool,
+}
+
+/// uci driver implementation
+impl<E: UciEngineImpl> UciDriver<E> {
+    /// create new driver of engine writing to stdout
+    pub fn new(engine: E) -> Self {
+        Self {
+            engine,
+            output: UciOutput::stdout(),
+            debug: false,
+        }
+    }
+
+    /// set output and return self
+    pub fn output(mut self, output: UciOutput) -> Self {
+        self.output = output;
+
+        self
+    }
+
+    /// engine implementation
+    pub fn engine(&self) -> &E {
+        &self.engine
+    }
+
+    /// handle command, returns false on quit
+    pub fn handle(&mut self, command: &GuiToEngine) -> bool {
+        match command {
+            GuiToEngine::Uci => {
+                self.output
+                    .send(&EngineToGui::Id(Id::Name(self.engine.name())));
+                self.output
+                    .send(&EngineToGui::Id(Id::Author(self.engine.author())));
+
+                for option in self.engine.options() {
+                    self.output.send(&EngineToGui::Option(option));
+                }
+
+                self.output.send(&EngineToGui::UciOk);
+            }
+            GuiToEngine::Debug(debug) => self.debug = *debug,
+            GuiToEngine::IsReady => self.output.send(&EngineToGui::ReadyOk),
+            GuiToEngine::SetOption { name, value } => {
+                if let Err(err) = self.engine.set_option(name, value.as_deref()) {
+                    self.output
+                        .info_string(format!("setting option {} failed : {}", name, err));
+                }
+            }
+            GuiToEngine::Register(_) => self.output.send(&EngineToGui::Registration(Status::Ok)),
+            GuiToEngine::UciNewGame => self.engine.new_game(),
+            GuiToEngine::Position { position, moves } => self.engine.set_position(position, moves),
+            GuiToEngine::Go(params) => self.engine.go(params, &self.output),
+            GuiToEngine::Stop => self.engine.stop(),
+            GuiToEngine::PonderHit => self.engine.ponderhit(),
+            GuiToEngine::Quit => {
+                self.engine.stop();
+
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// run protocol loop on input until quit or end of input
+    pub fn run<R: BufRead>(&mut self, input: R) -> std::io::Result<()> {
+        for line in input.lines() {
+            let line = line?;
+
+            if log_enabled!(Level::Debug) {
+                debug!("engine impl in : {}", line);
+            }
+
+            match line.parse::<GuiToEngine>() {
+                Ok(command) => {
+                    if !self.handle(&command) {
+                        break;
+                    }
+                }
+                // empty lines are allowed by the protocol
+                Err(ProtocolError::Empty) => {}
+                Err(err) => {
+                    warn!("invalid command {} : {}", line, err);
+
+                    if self.debug {
+                        self.output.info_string(err);
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// run protocol loop on stdin
+    pub fn run_stdio(&mut self) -> std::io::Result<()> {
+        let stdin = std::io::stdin();
+
+        let input = stdin.lock();
+
+        self.run(input)
+    }
+}
+
+#[cfg(test)]
+#[derive(Clone, Default)]
+struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+#[cfg(test)]
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn driver_dispatch() {
+    use shakmaty::Position as _;
+
+    struct FirstMove {
+        pos: Option<shakmaty::Chess>,
+        hash: i64,
+    }
+
+    impl UciEngineImpl for FirstMove {
+        fn name(&self) -> String {
+            "FirstMove".to_string()
+        }
+
+        fn author(&self) -> String {
+            "uciengine".to_string()
+        }
+
+        fn options(&self) -> Vec<UciOption> {
+            vec![UciOption {
+                name: "Hash".to_string(),
+                option_type: OptionType::Spin {
+                    default: 16,
+                    min: 1,
+                    max: 1024,
+                },
+            }]
+        }
+
+        fn set_option(&mut self, name: &str, value: Option<&str>) -> Result<(), String> {
+            match (name, value.map(|value| value.parse::<i64>())) {
+                ("Hash", Some(Ok(hash))) => self.hash = hash,
+                _ => return Err("invalid option".to_string()),
+            }
+
+            Ok(())
+        }
+
+        fn set_position(&mut self, position: &Position, moves: &[String]) {
+            self.pos = position.to_chess(moves);
+        }
+
+        fn go(&mut self, _params: &GoParams, output: &UciOutput) {
+            let m = self.pos.as_ref().unwrap().legal_moves()[0]
+                .to_uci(shakmaty::CastlingMode::Standard)
+                .to_string();
+
+            let mut ai = AnalysisInfo::new();
+
+            ai.depth = 1;
+            ai.set_pv(&m);
+
+            output.info(&ai);
+            output.bestmove(m, None);
+        }
+
+        fn stop(&mut self) {}
+    }
+
+    let buffer = SharedBuffer::default();
+
+    let mut driver = UciDriver::new(FirstMove {
+        pos: None,
+        hash: 16,
+    })
+    .output(UciOutput::from_writer(buffer.clone()));
+
+    let input = "uci\nsetoption name Hash value 64\nsetoption name Foo\nisready\n\n\
+                 position startpos moves e2e4\ngo depth 1\nquit\ngo depth 1\n";
+
+    driver.run(input.as_bytes()).unwrap();
+
+    assert_eq!(driver.engine().hash, 64);
+
+    let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+
+    let lines: Vec<&str> = output.lines().collect();
+
+    assert_eq!(
+        lines,
+        vec![
+            "id name FirstMove",
+            "id author uciengine",
+            "option name Hash type spin default 16 min 1 max 1024",
+            "uciok",
+            "info string setting option Foo failed : invalid option",
+            "readyok",
+            "info depth 1 score cp 0 pv a7a6",
+            "bestmove a7a6",
+        ]
+    );
+}
@@ -0,0 +1,632 @@
+use log::{debug, info, log_enabled, Level};
+
+use serde::{Deserialize, Serialize};
+
+use shakmaty::zobrist::Zobrist64;
+use shakmaty::{Chess, Color, EnPassantMode, Position};
+
+use std::time::Instant;
+
+use crate::analysis::Score;
+use crate::pgn::*;
+use crate::sprt::{Pentanomial, Sprt, SprtResult, SprtStatus, Trinomial};
+use crate::uciengine::{GoJob, Timecontrol, UciEngine};
+
+/// game outcome
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum GameOutcome {
+    /// white won
+    WhiteWins,
+    /// black won
+    BlackWins,
+    /// draw
+    Draw,
+}
+
+/// game outcome implementation
+impl GameOutcome {
+    /// outcome for the winner color
+    fn win(winner: Color) -> Self {
+        match winner {
+            Color::White => GameOutcome::WhiteWins,
+            Color::Black => GameOutcome::BlackWins,
+        }
+    }
+
+    /// pgn result string
+    pub fn as_str(self) -> &'static str {
+        match self {
+            GameOutcome::WhiteWins => "1-0",
+            GameOutcome::BlackWins => "0-1",
+            GameOutcome::Draw => "1/2-1/2",
+        }
+    }
+}
+
+/// opening to start games from
+#[derive(Debug, Clone)]
+pub struct Opening {
+    /// starting fen, None for the standard starting position
+    pub fen: Option<String>,
+    /// moves played from starting position, in uci notation
+    pub moves: Vec<String>,
+}
+
+/// read openings from text with one fen or epd per line,
+/// empty lines and lines starting with # are skipped
+pub fn read_fen_openings(text: &str) -> Result<Vec<Opening>, PgnError> {
+    text.lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+
+            // epd lines only have four fields, opcodes after them are ignored
+            let fen = match fields.len() {
+                4 => format!("{} 0 1", fields.join(" ")),
+                n if n >= 6 && fields[4].parse::<u32>().is_ok() => fields[0..6].join(" "),
+                _ => format!(
+                    "{} 0 1",
+                    fields[0..std::cmp::min(4, fields.len())].join(" ")
+                ),
+            };
+
+            parse_fen(&fen)?;
+
+            Ok(Opening {
+                fen: Some(fen),
+                moves: vec![],
+            })
+        })
+        .collect()
+}
+
+/// read openings from the mainlines of pgn games
+pub fn read_pgn_openings(pgn: &str) -> Result<Vec<Opening>, PgnError> {
+    Ok(read_games(pgn)?
+        .into_iter()
+        .map(|game| Opening {
+            fen: game.header("FEN").map(|fen| fen.to_string()),
+            moves: game.moves.iter().map(|m| m.uci.clone()).collect(),
+        })
+        .collect())
+}
+
+/// adjudication rules, scores are in centipawns from the point of view of the engine to move
+#[derive(Debug, Clone, Copy)]
+pub struct Adjudication {
+    /// an engine resigns after reporting a score at or below minus this many centipawns ...
+    pub resign_score: Option<i32>,
+    /// ... for this many consecutive moves of its own
+    pub resign_moves: usize,
+    /// the game is drawn after both engines report an absolute score at or below this ...
+    pub draw_score: Option<i32>,
+    /// ... for this many consecutive plies ...
+    pub draw_plies: usize,
+    /// ... but not before this full move number
+    pub draw_move_number: usize,
+}
+
+/// default adjudication ( none )
+impl Default for Adjudication {
+    fn default() -> Self {
+        Self {
+            resign_score: None,
+            resign_moves: 3,
+            draw_score: None,
+            draw_plies: 8,
+            draw_move_number: 40,
+        }
+    }
+}
+
+/// played game
+#[derive(Debug, Clone)]
+pub struct GameRecord {
+    /// game as pgn
+    pub pgn: PgnGame,
+    /// outcome
+    pub outcome: GameOutcome,
+    /// true if the first engine played white
+    pub first_is_white: bool,
+    /// reason the game ended
+    pub termination: String,
+}
+
+/// game record implementation
+impl GameRecord {
+    /// score of the first engine ( 1 for a win, 0.5 for a draw, 0 for a loss )
+    pub fn first_score(&self) -> f64 {
+        match (self.outcome, self.first_is_white) {
+            (GameOutcome::Draw, _) => 0.5,
+            (GameOutcome::WhiteWins, true) | (GameOutcome::BlackWins, false) => 1.0,
+            _ => 0.0,
+        }
+    }
+}
+
+/// match report, from the point of view of the first engine
+#[derive(Debug, Clone, Default)]
+pub struct MatchReport {
+    /// wins of the first engine
+    pub wins: usize,
+    /// losses of the first engine
+    pub losses: usize,
+    /// draws
+    pub draws: usize,
+    /// played games
+    pub games: Vec<GameRecord>,
+    /// last sprt result, None if the match was played without sprt
+    pub sprt: Option<SprtResult>,
+}
+
+/// match report implementation
+impl MatchReport {
+    /// add played game
+    pub fn add(&mut self, game: GameRecord) {
+        match game.first_score() {
+            score if score > 0.75 => self.wins += 1,
+            score if score < 0.25 => self.losses += 1,
+            _ => self.draws += 1,
+        }
+
+        self.games.push(game);
+    }
+
+    /// game results
+    pub fn trinomial(&self) -> Trinomial {
+        Trinomial::new(self.wins, self.losses, self.draws)
+    }
+
+    /// game pair results, games are paired in playing order
+    pub fn pentanomial(&self) -> Pentanomial {
+        let scores: Vec<f64> = self.games.iter().map(|game| game.first_score()).collect();
+
+        Pentanomial::from_scores(&scores)
+    }
+
+    /// all games as pgn text
+    pub fn to_pgn(&self) -> String {
+        self.games
+            .iter()
+            .map(|game| game.pgn.to_string())
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+}
+
+/// engine taking part in a match
+#[derive(Clone)]
+pub struct MatchEngine {
+    /// name used in pgn headers
+    pub name: String,
+    /// engine
+    pub engine: std::sync::Arc<UciEngine>,
+}
+
+/// engine vs engine match
+pub struct EngineMatch {
+    /// first engine
+    first: MatchEngine,
+    /// second engine
+    second: MatchEngine,
+    /// time control, times are the initial clock times
+    tc: Timecontrol,
+    /// openings, each is played twice with colors swapped
+    openings: Vec<Opening>,
+    /// adjudication rules
+    adjudication: Adjudication,
+    /// event name used in pgn headers
+    event: String,
+    /// sprt stopping the match when its bounds are crossed
+    sprt: Option<Sprt>,
+}
+
+/// search result of an engine move
+struct MoveSearch {
+    bestmove: Option<String>,
+    score: Score,
+    depth: usize,
+    elapsed: usize,
+}
+
+/// engine match implementation
+impl EngineMatch {
+    /// create new match between two engines, playing the standard starting position
+    /// with one minute per side and no increment until openings are set
+    pub fn new(first: MatchEngine, second: MatchEngine) -> Self {
+        Self {
+            first,
+            second,
+            tc: Timecontrol::default(),
+            openings: vec![Opening {
+                fen: None,
+                moves: vec![],
+            }],
+            adjudication: Adjudication::default(),
+            event: "Engine match".to_string(),
+            sprt: None,
+        }
+    }
+
+    /// set time control and return self
+    pub fn tc(mut self, tc: Timecontrol) -> Self {
+        self.tc = tc;
+
+        self
+    }
+
+    /// set openings and return self
+    pub fn openings(mut self, openings: Vec<Opening>) -> Self {
+        self.openings = openings;
+
+        self
+    }
+
+    /// set adjudication rules and return self
+    pub fn adjudication(mut self, adjudication: Adjudication) -> Self {
+        self.adjudication = adjudication;
+
+        self
+    }
+
+    /// set event name and return self
+    pub fn event<T: core::fmt::Display>(mut self, event: T) -> Self {
+        self.event = format!("{}", event);
+
+        self
+    }
+
+    /// set sprt and return self, the match stops after the game pair crossing its bounds
+    pub fn sprt(mut self, sprt: Sprt) -> Self {
+        self.sprt = Some(sprt);
+
+        self
+    }
+
+    /// number of games in match
+    pub fn num_games(&self) -> usize {
+        self.openings.len() * 2
+    }
+
+    /// search a move and measure the time used
+    async fn search(engine: &UciEngine, go_job: GoJob) -> Option<MoveSearch> {
+        let start = Instant::now();
+
+        let go_result = engine.go(go_job).await.ok()?;
+
+        Some(MoveSearch {
+            bestmove: go_result.bestmove,
+            score: go_result.ai.score,
+            depth: go_result.ai.depth,
+            elapsed: start.elapsed().as_millis() as usize,
+        })
+    }
+
+    /// play game from opening, round is used in the pgn headers
+    pub async fn play_game(
+        &self,
+        opening: &Opening,
+        first_is_white: bool,
+        round: usize,
+    ) -> Result<GameRecord, PgnError> {
+        let (white, black) = if first_is_white {
+            (&self.first, &self.second)
+        } else {
+            (&self.second, &self.first)
+        };
+
+        let mut pgn = PgnGame::new();
+
+        pgn.set_header("Event", &self.event);
+        pgn.set_header("Site", "?");
+        pgn.set_header("Round", round);
+        pgn.set_header("White", &white.name);
+        pgn.set_header("Black", &black.name);
+        pgn.set_header("Result", "*");
+        pgn.set_header(
+            "TimeControl",
+            format!("{}+{}", self.tc.wtime / 1000, self.tc.winc / 1000),
+        );
+
+        let mut pos = match &opening.fen {
+            Some(fen) => {
+                pgn.set_header("SetUp", "1");
+                pgn.set_header("FEN", fen);
+
+                parse_fen(fen)?
+            }
+            _ => Chess::default(),
+        };
+
+        let start_fen = to_fen(&pos);
+
+        for uci in &opening.moves {
+            pos = pgn.push_uci(&pos, uci)?;
+        }
+
+        for engine in &[white, black] {
+            drop(engine.engine.go(GoJob::new().custom("ucinewgame")));
+        }
+
+        let mut clock = Timecontrol {
+            wtime: self.tc.wtime,
+            winc: self.tc.winc,
+            btime: self.tc.btime,
+            binc: self.tc.binc,
+        };
+
+        let mut history: Vec<Zobrist64> = vec![pos.zobrist_hash(EnPassantMode::Legal)];
+        let mut resign_counts = [0_usize; 2];
+        let mut draw_count = 0_usize;
+
+        let (outcome, termination) = loop {
+            if let Some(ended) = Self::rules_outcome(&pos, &history) {
+                break ended;
+            }
+
+            let turn = pos.turn();
+
+            let mover = match turn {
+                Color::White => white,
+                Color::Black => black,
+            };
+
+            let mut go_job = GoJob::new().pos_fen(&start_fen).tc(Timecontrol {
+                wtime: clock.wtime,
+                winc: clock.winc,
+                btime: clock.btime,
+                binc: clock.binc,
+            });
+
+            if !pgn.moves.is_empty() {
+                let moves: Vec<&str> = pgn.moves.iter().map(|m| m.uci.as_str()).collect();
+
+                go_job = go_job.pos_moves(moves.join(" "));
+            }
+
+            let search = match Self::search(&mover.engine, go_job).await {
+                Some(search) => search,
+                _ => break (GameOutcome::win(!turn), "engine closed".to_string()),
+            };
+
+            if log_enabled!(Level::Debug) {
+                debug!(
+                    "{} played {:?} in {} ms",
+                    mover.name, search.bestmove, search.elapsed
+                );
+            }
+
+            let (time, inc) = match turn {
+                Color::White => (&mut clock.wtime, clock.winc),
+                Color::Black => (&mut clock.btime, clock.binc),
+            };
+
+            if search.elapsed > *time {
+                // the opponent can not win on time without mating material
+                if pos.has_insufficient_material(!turn) {
+                    break (GameOutcome::Draw, "time forfeit".to_string());
+                }
+
+                break (GameOutcome::win(!turn), "time forfeit".to_string());
+            }
+
+            *time = *time - search.elapsed + inc;
+
+            let after = match &search.bestmove {
+                Some(bestmove) => pgn.push_uci(&pos, bestmove).ok(),
+                _ => None,
+            };
+
+            pos = match after {
+                Some(after) => after,
+                _ => break (GameOutcome::win(!turn), "illegal move".to_string()),
+            };
+
+            if let Some(m) = pgn.moves.last_mut() {
+                m.comment = Some(format!(
+                    "{}/{} {:.3}s",
+                    score_str(search.score),
+                    search.depth,
+                    search.elapsed as f64 / 1000.0
+                ));
+            }
+
+            if pos.halfmoves() == 0 {
+                history.clear();
+            }
+
+            history.push(pos.zobrist_hash(EnPassantMode::Legal));
+
+            if let Some(adjudicated) = self.adjudicate(
+                turn,
+                search.score,
+                pos.fullmoves().get() as usize,
+                &mut resign_counts,
+                &mut draw_count,
+            ) {
+                break adjudicated;
+            }
+        };
+
+        pgn.result = outcome.as_str().to_string();
+        pgn.set_header("Result", outcome.as_str());
+        pgn.set_header("Termination", &termination);
+
+        if log_enabled!(Level::Info) {
+            info!(
+                "round {} {} - {} {} ( {} )",
+                round,
+                white.name,
+                black.name,
+                outcome.as_str(),
+                termination
+            );
+        }
+
+        Ok(GameRecord {
+            pgn,
+            outcome,
+            first_is_white,
+            termination,
+        })
+    }
+
+    /// outcome by the rules of chess, None if the game goes on
+    fn rules_outcome(pos: &Chess, history: &[Zobrist64]) -> Option<(GameOutcome, String)> {
+        if pos.is_checkmate() {
+            return Some((GameOutcome::win(!pos.turn()), "checkmate".to_string()));
+        }
+
+        if pos.is_stalemate() {
+            return Some((GameOutcome::Draw, "stalemate".to_string()));
+        }
+
+        if pos.is_insufficient_material() {
+            return Some((GameOutcome::Draw, "insufficient material".to_string()));
+        }
+
+        if pos.halfmoves() >= 100 {
+            return Some((GameOutcome::Draw, "fifty move rule".to_string()));
+        }
+
+        if let Some(current) = history.last() {
+            if history.iter().filter(|key| *key == current).count() >= 3 {
+                return Some((GameOutcome::Draw, "threefold repetition".to_string()));
+            }
+        }
+
+        None
+    }
+
+    /// adjudicate game after a move, None if the game goes on
+    fn adjudicate(
+        &self,
+        mover: Color,
+        score: Score,
+        move_number: usize,
+        resign_counts: &mut [usize; 2],
+        draw_count: &mut usize,
+    ) -> Option<(GameOutcome, String)> {
+        let cp = crate::annotate::score_cp(score);
+
+        if let Some(resign_score) = self.adjudication.resign_score {
+            let count = &mut resign_counts[mover as usize];
+
+            *count = if cp <= -resign_score { *count + 1 } else { 0 };
+
+            if *count >= self.adjudication.resign_moves {
+                return Some((GameOutcome::win(!mover), "adjudication".to_string()));
+            }
+        }
+
+        if let Some(draw_score) = self.adjudication.draw_score {
+            *draw_count = if cp.abs() <= draw_score {
+                *draw_count + 1
+            } else {
+                0
+            };
+
+            if *draw_count >= self.adjudication.draw_plies
+                && move_number >= self.adjudication.draw_move_number
+            {
+                return Some((GameOutcome::Draw, "adjudication".to_string()));
+            }
+        }
+
+        None
+    }
+
+    /// play all games of the match, each opening with colors swapped,
+    /// stops early if sprt is set and decided
+    pub async fn run(&self) -> Result<MatchReport, PgnError> {
+        let mut report = MatchReport::default();
+
+        for (index, opening) in self.openings.iter().enumerate() {
+            for (pair_index, first_is_white) in [true, false].iter().enumerate() {
+                let round = index * 2 + pair_index + 1;
+
+                report.add(self.play_game(opening, *first_is_white, round).await?);
+            }
+
+            if let Some(sprt) = &self.sprt {
+                let sprt_result = sprt.test_pentanomial(&report.pentanomial());
+
+                report.sprt = Some(sprt_result);
+
+                if sprt_result.status != SprtStatus::Continue {
+                    if log_enabled!(Level::Info) {
+                        info!("sprt stopped match {:?}", sprt_result);
+                    }
+
+                    break;
+                }
+            }
+        }
+
+        Ok(report)
+    }
+}
+
+/// score as shown in move comments, in pawns or mate distance
+fn score_str(score: Score) -> String {
+    match score {
+        Score::Cp(cp) => format!("{:+.2}", cp as f64 / 100.0),
+        Score::Mate(mate) if mate > 0 => format!("+M{}", mate),
+        Score::Mate(mate) => format!("-M{}", -mate),
+    }
+}
+
+#[test]
+fn rules_outcome() {
+    let mate = parse_fen("7k/6Q1/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+
+    assert_eq!(
+        EngineMatch::rules_outcome(&mate, &[]),
+        Some((GameOutcome::WhiteWins, "checkmate".to_string()))
+    );
+
+    let mut pos = Chess::default();
+    let mut history = vec![pos.zobrist_hash(EnPassantMode::Legal)];
+
+    for uci in &[
+        "g1f3", "g8f6", "f3g1", "f6g8", "g1f3", "g8f6", "f3g1", "f6g8",
+    ] {
+        let mut pgn = PgnGame::new();
+
+        pos = pgn.push_uci(&pos, uci).unwrap();
+
+        history.push(pos.zobrist_hash(EnPassantMode::Legal));
+    }
+
+    assert_eq!(
+        EngineMatch::rules_outcome(&pos, &history),
+        Some((GameOutcome::Draw, "threefold repetition".to_string()))
+    );
+
+    let fifty = parse_fen("8/8/8/8/8/2k5/8/K6Q w - - 100 80").unwrap();
+
+    assert_eq!(
+        EngineMatch::rules_outcome(&fifty, &[]),
+        Some((GameOutcome::Draw, "fifty move rule".to_string()))
+    );
+}
+
+#[test]
+fn read_openings() {
+    let openings = read_fen_openings(
+        "# openings\nrnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1\n8/8/8/8/8/2k5/8/K6Q w - - id \"x\";\n",
+    )
+    .unwrap();
+
+    assert_eq!(openings.len(), 2);
+    assert_eq!(
+        openings[1].fen,
+        Some("8/8/8/8/8/2k5/8/K6Q w - - 0 1".to_string())
+    );
+
+    let openings = read_pgn_openings("1. e4 e5 2. Nf3 *\n\n1. d4 d5 *\n").unwrap();
+
+    assert_eq!(openings.len(), 2);
+    assert_eq!(openings[0].moves, vec!["e2e4", "e7e5", "g1f3"]);
+}
@@ -0,0 +1,418 @@
+use log::{debug, log_enabled, Level};
+
+use serde::{Deserialize, Serialize};
+
+use shakmaty::san::SanPlus;
+use shakmaty::{CastlingMode, Chess};
+
+use thiserror::Error;
+
+use crate::analysis::AnalysisInfo;
+use crate::pgn::parse_fen;
+use crate::uciengine::{GoJob, GoResult, SearchLimit, UciEngine};
+
+/// EpdError captures possible epd parsing and running errors
+#[derive(Error, Debug)]
+pub enum EpdError {
+    #[error("invalid epd '{0}'")]
+    InvalidEpd(String),
+    #[error("illegal move '{1}' in epd '{0}'")]
+    IllegalMove(String, String),
+    #[error("engine closed before returning a result")]
+    EngineClosed,
+}
+
+/// epd test position
+#[derive(Debug, Clone)]
+pub struct EpdPosition {
+    /// fen of position ( epd fields completed with hmvc and fmvn operations or defaults )
+    pub fen: String,
+    /// best moves in uci notation ( bm operation )
+    pub bm: Vec<String>,
+    /// avoid moves in uci notation ( am operation )
+    pub am: Vec<String>,
+    /// id operation
+    pub id: Option<String>,
+    /// c0 comment operation
+    pub c0: Option<String>,
+    /// all operations as opcode and operands
+    pub operations: Vec<(String, Vec<String>)>,
+}
+
+/// result of running an epd position
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EpdResult {
+    /// id of position, or its number in the suite if it has no id
+    pub id: String,
+    /// fen of position
+    pub fen: String,
+    /// expected moves, as given in the epd ( bm, or am prefixed with ! )
+    pub expected: Vec<String>,
+    /// move found by the engine
+    pub bestmove: Option<String>,
+    /// true if the move found is a best move and not an avoid move
+    pub solved: bool,
+    /// search time in milliseconds after which the engine stuck to a solution
+    pub time_to_solution: Option<usize>,
+    /// depth reached
+    pub depth: usize,
+    /// nodes searched
+    pub nodes: u64,
+}
+
+/// report of running an epd suite
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EpdReport {
+    /// results in suite order
+    pub results: Vec<EpdResult>,
+    /// number of solved positions
+    pub solved: usize,
+    /// number of positions
+    pub total: usize,
+}
+
+/// split text on separator, except inside double quotes
+fn split_unquoted(text: &str, separator: fn(char) -> bool) -> Vec<String> {
+    let mut parts = vec![];
+    let mut current = String::new();
+    let mut quoted = false;
+
+    for c in text.chars() {
+        if c == '"' {
+            quoted = !quoted;
+        } else if !quoted && separator(c) {
+            if !current.trim().is_empty() {
+                parts.push(current.trim().to_string());
+            }
+
+            current.clear();
+        } else {
+            current.push(c);
+        }
+    }
+
+    if !current.trim().is_empty() {
+        parts.push(current.trim().to_string());
+    }
+
+    parts
+}
+
+/// convert san operands to uci moves
+fn san_operands(line: &str, pos: &Chess, operands: &[String]) -> Result<Vec<String>, EpdError> {
+    operands
+        .iter()
+        .map(|san| {
+            let trimmed = san.trim_end_matches(['!', '?']);
+
+            SanPlus::from_ascii(trimmed.as_bytes())
+                .ok()
+                .and_then(|san_plus| san_plus.san.to_move(pos).ok())
+                .map(|m| m.to_uci(CastlingMode::Standard).to_string())
+                .ok_or_else(|| EpdError::IllegalMove(line.to_string(), san.to_string()))
+        })
+        .collect()
+}
+
+/// epd position implementation
+impl EpdPosition {
+    /// parse epd line
+    pub fn parse(line: &str) -> Result<Self, EpdError> {
+        let line = line.trim();
+
+        let fields: Vec<&str> = line.splitn(5, char::is_whitespace).collect();
+
+        if fields.len() < 4 {
+            return Err(EpdError::InvalidEpd(line.to_string()));
+        }
+
+        let operations: Vec<(String, Vec<String>)> =
+            split_unquoted(fields.get(4).unwrap_or(&""), |c| c == ';')
+                .iter()
+                .map(|operation| {
+                    let mut tokens = split_unquoted(operation, char::is_whitespace).into_iter();
+
+                    let opcode = tokens.next().unwrap_or_default();
+
+                    (opcode, tokens.collect())
+                })
+                .collect();
+
+        let operand = |opcode: &str| {
+            operations
+                .iter()
+                .find(|(key, _)| key == opcode)
+                .map(|(_, operands)| operands.clone())
+        };
+
+        let hmvc = operand("hmvc").and_then(|operands| operands.first().cloned());
+        let fmvn = operand("fmvn").and_then(|operands| operands.first().cloned());
+
+        let fen = format!(
+            "{} {} {} {} {} {}",
+            fields[0],
+            fields[1],
+            fields[2],
+            fields[3],
+            hmvc.unwrap_or_else(|| "0".to_string()),
+            fmvn.unwrap_or_else(|| "1".to_string())
+        );
+
+        let pos = parse_fen(&fen).map_err(|_| EpdError::InvalidEpd(line.to_string()))?;
+
+        Ok(Self {
+            bm: san_operands(line, &pos, &operand("bm").unwrap_or_default())?,
+            am: san_operands(line, &pos, &operand("am").unwrap_or_default())?,
+            id: operand("id").map(|operands| operands.join(" ")),
+            c0: operand("c0").map(|operands| operands.join(" ")),
+            fen,
+            operations,
+        })
+    }
+
+    /// true if move solves the position
+    pub fn is_solution(&self, uci: &str) -> bool {
+        (self.bm.is_empty() || self.bm.iter().any(|bm| bm == uci))
+            && !self.am.iter().any(|am| am == uci)
+    }
+}
+
+/// read epd suite, empty lines and lines starting with # are skipped
+pub fn read_epd(text: &str) -> Result<Vec<EpdPosition>, EpdError> {
+    text.lines()
+        .filter(|line| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+        .map(EpdPosition::parse)
+        .collect()
+}
+
+/// epd suite runner, searches every position under a fixed budget
+#[derive(Debug, Clone)]
+pub struct EpdRunner {
+    /// search limit
+    limit: SearchLimit,
+    /// uci options sent with every search
+    uci_options: Vec<(String, String)>,
+}
+
+/// default epd runner
+impl Default for EpdRunner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// epd runner implementation
+impl EpdRunner {
+    /// create new epd runner, searching one second per position
+    pub fn new() -> Self {
+        Self {
+            limit: SearchLimit::Movetime(1000),
+            uci_options: vec![],
+        }
+    }
+
+    /// set search limit and return self
+    pub fn limit(mut self, limit: SearchLimit) -> Self {
+        self.limit = limit;
+
+        self
+    }
+
+    /// set uci option sent with every search and return self
+    pub fn uci_opt<K, V>(mut self, key: K, value: V) -> Self
+    where
+        K: core::fmt::Display,
+        V: core::fmt::Display,
+    {
+        self.uci_options
+            .push((format!("{}", key), format!("{}", value)));
+
+        self
+    }
+
+    /// go job for epd position
+    pub fn go_job(&self, epd: &EpdPosition) -> GoJob {
+        let mut go_job = GoJob::new().pos_fen(&epd.fen);
+
+        for (key, value) in &self.uci_options {
+            go_job = go_job.uci_opt(key, value);
+        }
+
+        go_job.limit(self.limit)
+    }
+
+    /// search epd position and score it
+    pub async fn run_position(
+        &self,
+        engine: &UciEngine,
+        epd: &EpdPosition,
+        index: usize,
+    ) -> Result<EpdResult, EpdError> {
+        // analysis info of this search only, without losing any
+        let (mut rrx, mut irx) = engine.go_analysis(self.go_job(epd));
+
+        let mut solved_since: Option<usize> = None;
+
+        let mut track = |ai: &AnalysisInfo| {
+            if let Some(bestmove) = ai.bestmove() {
+                if !epd.is_solution(&bestmove) {
+                    solved_since = None;
+                } else if solved_since.is_none() {
+                    solved_since = Some(ai.time);
+                }
+            }
+        };
+
+        let go_result: GoResult = loop {
+            tokio::select! {
+                biased;
+                Some(ai) = irx.recv() => track(&ai),
+                go_result = &mut rrx => match go_result {
+                    Ok(go_result) => break go_result,
+                    _ => return Err(EpdError::EngineClosed),
+                },
+            }
+        };
+
+        while let Ok(ai) = irx.try_recv() {
+            track(&ai);
+        }
+
+        if log_enabled!(Level::Debug) {
+            debug!("epd search result {:?}", go_result);
+        }
+
+        let solved = go_result
+            .bestmove
+            .as_ref()
+            .map(|bestmove| epd.is_solution(bestmove))
+            .unwrap_or(false);
+
+        let expected = epd
+            .bm
+            .iter()
+            .cloned()
+            .chain(epd.am.iter().map(|am| format!("!{}", am)))
+            .collect();
+
+        Ok(EpdResult {
+            id: epd.id.clone().unwrap_or_else(|| format!("{}", index + 1)),
+            fen: epd.fen.clone(),
+            expected,
+            bestmove: go_result.bestmove,
+            solved,
+            time_to_solution: if solved {
+                Some(solved_since.unwrap_or(go_result.ai.time))
+            } else {
+                None
+            },
+            depth: go_result.ai.depth,
+            nodes: go_result.ai.nodes,
+        })
+    }
+
+    /// run epd suite
+    pub async fn run(
+        &self,
+        engine: &UciEngine,
+        suite: &[EpdPosition],
+    ) -> Result<EpdReport, EpdError> {
+        let mut results = vec![];
+
+        for (index, epd) in suite.iter().enumerate() {
+            results.push(self.run_position(engine, epd, index).await?);
+        }
+
+        Ok(EpdReport::new(results))
+    }
+}
+
+/// epd report implementation
+impl EpdReport {
+    /// create report from results
+    pub fn new(results: Vec<EpdResult>) -> Self {
+        Self {
+            solved: results.iter().filter(|result| result.solved).count(),
+            total: results.len(),
+            results,
+        }
+    }
+
+    /// to json
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string(self)
+    }
+
+    /// to text table
+    pub fn to_table(&self) -> String {
+        let mut table = format!(
+            "{:<16} {:<8} {:<8} {:<16} {:>8} {:>6} {:>12}\n",
+            "id", "result", "move", "expected", "time", "depth", "nodes"
+        );
+
+        for result in &self.results {
+            table.push_str(&format!(
+                "{:<16} {:<8} {:<8} {:<16} {:>8} {:>6} {:>12}\n",
+                result.id,
+                if result.solved { "solved" } else { "failed" },
+                result.bestmove.as_deref().unwrap_or("-"),
+                result.expected.join(" "),
+                result
+                    .time_to_solution
+                    .map(|time| time.to_string())
+                    .unwrap_or_else(|| "-".to_string()),
+                result.depth,
+                result.nodes
+            ));
+        }
+
+        table.push_str(&format!("solved {} / {}\n", self.solved, self.total));
+
+        table
+    }
+}
+
+#[test]
+fn parse_epd() {
+    let epd = EpdPosition::parse(
+        "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id \"WAC.001\"; c0 \"mate; in 3\";",
+    )
+    .unwrap();
+
+    assert_eq!(epd.bm, vec!["g3g6".to_string()]);
+    assert_eq!(epd.id, Some("WAC.001".to_string()));
+    assert_eq!(epd.c0, Some("mate; in 3".to_string()));
+    assert_eq!(
+        epd.fen,
+        "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - 0 1"
+    );
+    assert!(epd.is_solution("g3g6"));
+    assert!(!epd.is_solution("g3g7"));
+
+    let epd = EpdPosition::parse("8/8/8/8/8/2k5/8/K6Q w - - am Qh8+; hmvc 3; fmvn 40;").unwrap();
+
+    assert_eq!(epd.am, vec!["h1h8".to_string()]);
+    assert_eq!(epd.fen, "8/8/8/8/8/2k5/8/K6Q w - - 3 40");
+    assert!(epd.is_solution("h1h2"));
+    assert!(!epd.is_solution("h1h8"));
+
+    assert!(EpdPosition::parse("8/8/8/8 w").is_err());
+    assert!(EpdPosition::parse("8/8/8/8/8/2k5/8/K6Q w - - bm Qh9;").is_err());
+}
+
+#[test]
+fn report_table() {
+    let report = EpdReport::new(vec![EpdResult {
+        id: "WAC.001".to_string(),
+        fen: "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - 0 1".to_string(),
+        expected: vec!["g3g6".to_string()],
+        bestmove: Some("g3g6".to_string()),
+        solved: true,
+        time_to_solution: Some(20),
+        depth: 8,
+        nodes: 1000,
+    }]);
+
+    assert!(report.to_table().ends_with("solved 1 / 1\n"));
+    assert!(report.to_json().unwrap().contains("\"solved\":1"));
+}
@@ -2,7 +2,7 @@
 //! # Examples
 //!
 //!
-//!```
+//!```no_run
 //!extern crate env_logger;
 //!
 //!use uciengine::uciengine::*;
@@ -50,11 +50,12 @@
 //!    // wait enough for the go commands to complete in the background
 //!    tokio::time::sleep(tokio::time::Duration::from_millis(20000)).await;
 //!
-//!    // quit engine
-//!    engine.quit();
+//!    // quit engine, kill it if it does not exit within 3 seconds
+//!    let shutdown_status = engine
+//!        .shutdown(tokio::time::Duration::from_millis(3000))
+//!        .await;
 //!
-//!    // wait for engine to quit gracefully
-//!    tokio::time::sleep(tokio::time::Duration::from_millis(3000)).await;
+//!    println!("shutdown status {:?}", shutdown_status);
 //!
 //!    Ok(())
 //!}
@@ -62,4 +63,26 @@
 
 // lib
 pub mod analysis;
+pub mod annotate;
+pub mod book;
+pub mod cache;
+pub mod cecpengine;
+pub mod config;
+pub mod engineimpl;
+pub mod enginematch;
+pub mod epd;
+pub mod metrics;
+pub mod mockengine;
+pub mod pgn;
+pub mod pool;
+pub mod protocol;
+pub mod proxy;
+#[cfg(target_os = "linux")]
+pub mod sandbox;
+#[cfg(feature = "server")]
+pub mod server;
+pub mod sprt;
+pub mod tablebase;
+pub mod timemanager;
+pub mod transcript;
 pub mod uciengine;
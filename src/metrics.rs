@@ -0,0 +1,192 @@
+use serde::{Deserialize, Serialize};
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// engine activity counters, updated by the engine tasks
+#[derive(Debug, Default)]
+pub struct EngineMetrics {
+    jobs: AtomicU64,
+    restarts: AtomicU64,
+    parse_failures: AtomicU64,
+    nodes: AtomicU64,
+    search_ms: AtomicU64,
+    queue_wait_ms: AtomicU64,
+}
+
+/// engine metrics at one point in time
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct MetricsSnapshot {
+    /// searches run
+    pub jobs: u64,
+    /// engine restarts, counted on the engine that replaced a terminated one
+    pub restarts: u64,
+    /// engine output lines that failed to parse
+    pub parse_failures: u64,
+    /// nodes searched, as reported in the last info of each search
+    pub nodes: u64,
+    /// total search time in milliseconds
+    pub search_ms: u64,
+    /// total time jobs waited in the go job queue in milliseconds
+    pub queue_wait_ms: u64,
+}
+
+/// engine metrics implementation
+impl EngineMetrics {
+    /// create new engine metrics
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// record finished search
+    pub fn record_job(&self, queue_wait: Duration, search: Duration, nodes: u64) {
+        self.jobs.fetch_add(1, Ordering::Relaxed);
+        self.nodes.fetch_add(nodes, Ordering::Relaxed);
+        self.search_ms
+            .fetch_add(search.as_millis() as u64, Ordering::Relaxed);
+        self.queue_wait_ms
+            .fetch_add(queue_wait.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    /// record engine output line that failed to parse
+    pub fn record_parse_failure(&self) {
+        self.parse_failures.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// record engine restart
+    pub fn record_restart(&self) {
+        self.restarts.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// current values
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        MetricsSnapshot {
+            jobs: self.jobs.load(Ordering::Relaxed),
+            restarts: self.restarts.load(Ordering::Relaxed),
+            parse_failures: self.parse_failures.load(Ordering::Relaxed),
+            nodes: self.nodes.load(Ordering::Relaxed),
+            search_ms: self.search_ms.load(Ordering::Relaxed),
+            queue_wait_ms: self.queue_wait_ms.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// metrics snapshot implementation
+impl MetricsSnapshot {
+    /// average nodes per second over all searches, 0 if nothing was searched
+    pub fn nps(&self) -> u64 {
+        match self.search_ms {
+            0 => 0,
+            search_ms => self.nodes * 1000 / search_ms,
+        }
+    }
+}
+
+/// sum of two snapshots, e.g. of the engines of a pool
+impl std::ops::Add for MetricsSnapshot {
+    type Output = MetricsSnapshot;
+
+    fn add(self, other: MetricsSnapshot) -> MetricsSnapshot {
+        MetricsSnapshot {
+            jobs: self.jobs + other.jobs,
+            restarts: self.restarts + other.restarts,
+            parse_failures: self.parse_failures + other.parse_failures,
+            nodes: self.nodes + other.nodes,
+            search_ms: self.search_ms + other.search_ms,
+            queue_wait_ms: self.queue_wait_ms + other.queue_wait_ms,
+        }
+    }
+}
+
+/// escape prometheus label value
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// name, type, help and value of an exported metric
+type MetricSpec = (
+    &'static str,
+    &'static str,
+    &'static str,
+    fn(&MetricsSnapshot) -> u64,
+);
+
+/// encode snapshots in the prometheus text exposition format,
+/// one sample per metric and engine, labelled with the engine name
+pub fn encode_prometheus(engines: &[(&str, MetricsSnapshot)]) -> String {
+    let metrics: [MetricSpec; 7] = [
+        ("jobs_total", "counter", "searches run", |m| m.jobs),
+        ("restarts_total", "counter", "engine restarts", |m| {
+            m.restarts
+        }),
+        (
+            "parse_failures_total",
+            "counter",
+            "engine output lines that failed to parse",
+            |m| m.parse_failures,
+        ),
+        ("nodes_total", "counter", "nodes searched", |m| m.nodes),
+        (
+            "search_milliseconds_total",
+            "counter",
+            "time spent searching",
+            |m| m.search_ms,
+        ),
+        (
+            "queue_wait_milliseconds_total",
+            "counter",
+            "time go jobs waited in the queue",
+            |m| m.queue_wait_ms,
+        ),
+        ("nps", "gauge", "average nodes per second", |m| m.nps()),
+    ];
+
+    let mut text = String::new();
+
+    for (name, kind, help, value) in metrics.iter() {
+        text.push_str(&format!("# HELP uciengine_{} {}\n", name, help));
+        text.push_str(&format!("# TYPE uciengine_{} {}\n", name, kind));
+
+        for (engine, snapshot) in engines {
+            text.push_str(&format!(
+                "uciengine_{}{{engine=\"{}\"}} {}\n",
+                name,
+                escape_label(engine),
+                value(snapshot)
+            ));
+        }
+    }
+
+    text
+}
+
+#[test]
+fn prometheus_text() {
+    let metrics = EngineMetrics::new();
+
+    metrics.record_job(
+        Duration::from_millis(5),
+        Duration::from_millis(500),
+        1000000,
+    );
+    metrics.record_job(
+        Duration::from_millis(0),
+        Duration::from_millis(500),
+        1000000,
+    );
+    metrics.record_parse_failure();
+
+    let snapshot = metrics.snapshot();
+
+    assert_eq!(snapshot.nps(), 2000000);
+
+    let text = encode_prometheus(&[("sf \"12\"", snapshot)]);
+
+    assert!(text.contains("# TYPE uciengine_jobs_total counter\n"));
+    assert!(text.contains("uciengine_jobs_total{engine=\"sf \\\"12\\\"\"} 2\n"));
+    assert!(text.contains("uciengine_parse_failures_total{engine=\"sf \\\"12\\\"\"} 1\n"));
+    assert!(text.contains("uciengine_nps{engine=\"sf \\\"12\\\"\"} 2000000\n"));
+}
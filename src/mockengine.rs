@@ -0,0 +1,318 @@
+use serde::{Deserialize, Serialize};
+
+use std::io::{BufRead, Write};
+use std::time::Duration;
+
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+
+use crate::uciengine::UciEngine;
+
+/// mock engine action
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum MockAction {
+    /// write line to engine output
+    Send { line: String },
+    /// sleep, commands sent meanwhile are read after the delay
+    Delay { ms: u64 },
+    /// exit immediately with code, without finishing the search ( crash )
+    Exit { code: i32 },
+}
+
+/// mock engine rule, the actions are run for commands starting with command
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MockRule {
+    /// command prefix matched against the first word(s) of the command
+    pub command: String,
+    /// actions run in order
+    pub actions: Vec<MockAction>,
+    /// number of times the rule can match, None for any number
+    #[serde(default)]
+    pub times: Option<usize>,
+}
+
+/// mock engine script, rules are tried in order and the first matching rule
+/// that is not used up answers the command, unmatched commands are ignored
+///
+/// stop and ponderhit are ignored while no search is running, a search runs from
+/// go until a line starting with bestmove is sent
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MockScript {
+    /// rules
+    pub rules: Vec<MockRule>,
+}
+
+/// default mock script
+impl Default for MockScript {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// mock script implementation
+impl MockScript {
+    /// create new mock script answering uci, isready, go, go ponder, ponderhit,
+    /// stop and quit like a minimal engine always playing e2e4 ponder e7e5
+    pub fn new() -> Self {
+        Self::empty()
+            .on("uci", vec![send("id name MockEngine"), send("uciok")])
+            .on("isready", vec![send("readyok")])
+            .on("go ponder", vec![send("info depth 1 score cp 5 pv e2e4")])
+            .on("go", vec![send(INFO), send(BESTMOVE)])
+            .on("ponderhit", vec![send(INFO), send(BESTMOVE)])
+            .on("stop", vec![send(BESTMOVE)])
+            .on("quit", vec![MockAction::Exit { code: 0 }])
+    }
+
+    /// create new mock script without rules
+    pub fn empty() -> Self {
+        Self { rules: vec![] }
+    }
+
+    /// add rule matching any number of times and return self,
+    /// rules added earlier take precedence
+    pub fn on<T: core::fmt::Display>(self, command: T, actions: Vec<MockAction>) -> Self {
+        self.add_rule(command, actions, None)
+    }
+
+    /// add rule matching at most times times and return self
+    pub fn on_times<T: core::fmt::Display>(
+        self,
+        command: T,
+        actions: Vec<MockAction>,
+        times: usize,
+    ) -> Self {
+        self.add_rule(command, actions, Some(times))
+    }
+
+    /// add rule taking precedence over all rules and return self
+    pub fn first<T: core::fmt::Display>(mut self, command: T, actions: Vec<MockAction>) -> Self {
+        self.rules.insert(
+            0,
+            MockRule {
+                command: format!("{}", command),
+                actions,
+                times: None,
+            },
+        );
+
+        self
+    }
+
+    /// add rule and return self
+    fn add_rule<T: core::fmt::Display>(
+        mut self,
+        command: T,
+        actions: Vec<MockAction>,
+        times: Option<usize>,
+    ) -> Self {
+        self.rules.push(MockRule {
+            command: format!("{}", command),
+            actions,
+            times,
+        });
+
+        self
+    }
+
+    /// script as json
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+
+    /// script from json
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(json)
+    }
+
+    /// run script, reading commands from input and writing responses to output,
+    /// returns the exit code when input is closed or an exit action is run
+    pub fn run<R: BufRead, W: Write>(&self, input: R, mut output: W) -> i32 {
+        let mut state = MockState::new(self);
+
+        for line in input.lines() {
+            let line = match line {
+                Ok(line) => line,
+                _ => return 1,
+            };
+
+            for action in state.actions(&line) {
+                match action {
+                    MockAction::Send { line } => {
+                        if writeln!(output, "{}", line)
+                            .and_then(|_| output.flush())
+                            .is_err()
+                        {
+                            return 1;
+                        }
+                    }
+                    MockAction::Delay { ms } => std::thread::sleep(Duration::from_millis(ms)),
+                    MockAction::Exit { code } => return code,
+                }
+            }
+        }
+
+        0
+    }
+
+    /// run script asynchronously, see run
+    pub async fn run_async<R, W>(&self, input: R, mut output: W) -> i32
+    where
+        R: AsyncRead + Unpin,
+        W: AsyncWrite + Unpin,
+    {
+        let mut state = MockState::new(self);
+
+        let mut lines = BufReader::new(input).lines();
+
+        loop {
+            let line = match lines.next_line().await {
+                Ok(Some(line)) => line,
+                Ok(None) => return 0,
+                _ => return 1,
+            };
+
+            for action in state.actions(&line) {
+                match action {
+                    MockAction::Send { line } => {
+                        let line = format!("{}\n", line);
+
+                        if output.write_all(line.as_bytes()).await.is_err()
+                            || output.flush().await.is_err()
+                        {
+                            return 1;
+                        }
+                    }
+                    MockAction::Delay { ms } => tokio::time::sleep(Duration::from_millis(ms)).await,
+                    MockAction::Exit { code } => return code,
+                }
+            }
+        }
+    }
+
+    /// run script in a task and return an engine talking to it over an in-memory duplex,
+    /// no process is spawned, exit actions close the engine output
+    pub fn start_engine(self) -> std::sync::Arc<UciEngine> {
+        let (engine_io, mock_io) = tokio::io::duplex(64 * 1024);
+
+        tokio::spawn(async move {
+            let (input, output) = tokio::io::split(mock_io);
+
+            self.run_async(input, output).await
+        });
+
+        let (reader, writer) = tokio::io::split(engine_io);
+
+        UciEngine::from_io(reader, writer)
+    }
+}
+
+/// mock engine state while running a script
+struct MockState<'a> {
+    script: &'a MockScript,
+    /// number of times each rule matched
+    used: Vec<usize>,
+    /// true from go until bestmove is sent
+    searching: bool,
+}
+
+/// mock state implementation
+impl<'a> MockState<'a> {
+    /// create new state for script
+    fn new(script: &'a MockScript) -> Self {
+        Self {
+            script,
+            used: vec![0; script.rules.len()],
+            searching: false,
+        }
+    }
+
+    /// actions answering command
+    fn actions(&mut self, line: &str) -> Vec<MockAction> {
+        let command = line.trim();
+
+        if !self.searching && (command == "stop" || command == "ponderhit") {
+            return vec![];
+        }
+
+        if command.starts_with("go") {
+            self.searching = true;
+        }
+
+        let used = &self.used;
+
+        let rule = self.script.rules.iter().enumerate().find(|(index, rule)| {
+            matches_command(command, &rule.command)
+                && rule.times.is_none_or(|times| used[*index] < times)
+        });
+
+        let (index, rule) = match rule {
+            Some(rule) => rule,
+            _ => return vec![],
+        };
+
+        self.used[index] += 1;
+
+        for action in &rule.actions {
+            if let MockAction::Send { line } = action {
+                if line.starts_with("bestmove") {
+                    self.searching = false;
+                }
+            }
+        }
+
+        rule.actions.clone()
+    }
+}
+
+/// info line sent by the default script
+const INFO: &str = "info depth 1 score cp 10 pv e2e4 e7e5";
+/// bestmove line sent by the default script
+const BESTMOVE: &str = "bestmove e2e4 ponder e7e5";
+
+/// send line action
+pub fn send<T: core::fmt::Display>(line: T) -> MockAction {
+    MockAction::Send {
+        line: format!("{}", line),
+    }
+}
+
+/// delay action
+pub fn delay(ms: u64) -> MockAction {
+    MockAction::Delay { ms }
+}
+
+/// exit action
+pub fn exit(code: i32) -> MockAction {
+    MockAction::Exit { code }
+}
+
+/// true if command starts with the words of prefix
+fn matches_command(command: &str, prefix: &str) -> bool {
+    let mut words = command.split_whitespace();
+
+    prefix
+        .split_whitespace()
+        .all(|prefix_word| words.next() == Some(prefix_word))
+}
+
+#[test]
+fn run_script() {
+    let script = MockScript::new().first("go depth 5", vec![send("bestmove d2d4")]);
+
+    let script = MockScript::from_json(&script.to_json()).unwrap();
+
+    let mut output = vec![];
+
+    let code = script.run(
+        "uci\nstop\ngo depth 5\ngo ponder\nponderhit\nquit\nisready\n".as_bytes(),
+        &mut output,
+    );
+
+    assert_eq!(code, 0);
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        "id name MockEngine\nuciok\nbestmove d2d4\ninfo depth 1 score cp 5 pv e2e4\n\
+         info depth 1 score cp 10 pv e2e4 e7e5\nbestmove e2e4 ponder e7e5\n"
+    );
+}
@@ -0,0 +1,455 @@
+use pgn_reader::{Nag, Outcome, RawComment, RawTag, Reader, SanPlus, Visitor};
+
+use shakmaty::fen::Fen;
+use shakmaty::uci::UciMove;
+use shakmaty::{CastlingMode, Chess, Color, EnPassantMode, Position};
+
+use thiserror::Error;
+
+use std::ops::ControlFlow;
+
+/// maximum length of a movetext line when writing pgn
+const MAX_LINE_LENGTH: usize = 80;
+
+/// PgnError captures possible pgn reading errors
+#[derive(Error, Debug)]
+pub enum PgnError {
+    #[error("could not read pgn: {0}")]
+    ReadError(#[from] std::io::Error),
+    #[error("invalid fen '{0}'")]
+    InvalidFen(String),
+    #[error("illegal move '{0}'")]
+    IllegalMove(String),
+}
+
+/// pgn move with annotations
+#[derive(Debug, Clone)]
+pub struct PgnMove {
+    /// move in san notation
+    pub san: String,
+    /// move in uci notation
+    pub uci: String,
+    /// numeric annotation glyphs
+    pub nags: Vec<u8>,
+    /// comment after move
+    pub comment: Option<String>,
+    /// alternative line to this move, in san notation
+    pub variation: Vec<String>,
+}
+
+/// pgn game, only the mainline is kept when reading
+#[derive(Debug, Clone)]
+pub struct PgnGame {
+    /// header tags in order
+    pub headers: Vec<(String, String)>,
+    /// comment before the first move
+    pub comment: Option<String>,
+    /// mainline moves
+    pub moves: Vec<PgnMove>,
+    /// result ( 1-0, 0-1, 1/2-1/2 or * )
+    pub result: String,
+}
+
+/// default pgn game
+impl Default for PgnGame {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// pgn game implementation
+impl PgnGame {
+    /// create new empty game with unknown result
+    pub fn new() -> Self {
+        Self {
+            headers: vec![],
+            comment: None,
+            moves: vec![],
+            result: "*".to_string(),
+        }
+    }
+
+    /// get header value
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// set header value, replacing an existing header of the same name
+    pub fn set_header<K, V>(&mut self, name: K, value: V)
+    where
+        K: core::fmt::Display,
+        V: core::fmt::Display,
+    {
+        let name = format!("{}", name);
+        let value = format!("{}", value);
+
+        match self.headers.iter_mut().find(|(key, _)| *key == name) {
+            Some(header) => header.1 = value,
+            _ => self.headers.push((name, value)),
+        }
+    }
+
+    /// starting position, from FEN header if any
+    pub fn start_position(&self) -> Result<Chess, PgnError> {
+        match self.header("FEN") {
+            Some(fen) => parse_fen(fen),
+            _ => Ok(Chess::default()),
+        }
+    }
+
+    /// starting position and all positions after each mainline move
+    pub fn positions(&self) -> Result<Vec<Chess>, PgnError> {
+        let mut pos = self.start_position()?;
+
+        let mut positions = vec![pos.clone()];
+
+        for m in &self.moves {
+            let legal = UciMove::from_ascii(m.uci.as_bytes())
+                .ok()
+                .and_then(|uci| uci.to_move(&pos).ok());
+
+            match legal {
+                Some(legal) => pos.play_unchecked(legal),
+                _ => return Err(PgnError::IllegalMove(m.san.clone())),
+            }
+
+            positions.push(pos.clone());
+        }
+
+        Ok(positions)
+    }
+
+    /// append move given in uci notation, returns the position after the move
+    pub fn push_uci(&mut self, pos: &Chess, uci: &str) -> Result<Chess, PgnError> {
+        let m = UciMove::from_ascii(uci.as_bytes())
+            .ok()
+            .and_then(|uci| uci.to_move(pos).ok())
+            .ok_or_else(|| PgnError::IllegalMove(uci.to_string()))?;
+
+        let mut after = pos.clone();
+
+        let san = SanPlus::from_move_and_play_unchecked(&mut after, m);
+
+        self.moves.push(PgnMove {
+            san: san.to_string(),
+            uci: m.to_uci(CastlingMode::Standard).to_string(),
+            nags: vec![],
+            comment: None,
+            variation: vec![],
+        });
+
+        Ok(after)
+    }
+}
+
+/// parse fen into standard chess position
+pub fn parse_fen(fen: &str) -> Result<Chess, PgnError> {
+    Fen::from_ascii(fen.as_bytes())
+        .ok()
+        .and_then(|parsed| parsed.into_position(CastlingMode::Standard).ok())
+        .ok_or_else(|| PgnError::InvalidFen(fen.to_string()))
+}
+
+/// fen of position
+pub fn to_fen(pos: &Chess) -> String {
+    Fen::from_position(pos, EnPassantMode::Legal).to_string()
+}
+
+/// convert uci moves to san, stops at the first illegal move
+pub fn uci_to_san(pos: &Chess, ucis: &[&str]) -> Vec<String> {
+    let mut pos = pos.clone();
+
+    let mut sans = vec![];
+
+    for uci in ucis {
+        let legal = UciMove::from_ascii(uci.as_bytes())
+            .ok()
+            .and_then(|uci| uci.to_move(&pos).ok());
+
+        match legal {
+            Some(m) => sans.push(SanPlus::from_move_and_play_unchecked(&mut pos, m).to_string()),
+            _ => break,
+        }
+    }
+
+    sans
+}
+
+/// game being read
+struct GameBuilder {
+    game: PgnGame,
+    pos: Chess,
+}
+
+/// visitor collecting mainline of games
+struct GameVisitor;
+
+/// visitor implementation
+impl Visitor for GameVisitor {
+    type Tags = Vec<(String, String)>;
+    type Movetext = GameBuilder;
+    type Output = Result<PgnGame, PgnError>;
+
+    fn begin_tags(&mut self) -> ControlFlow<Self::Output, Self::Tags> {
+        ControlFlow::Continue(vec![])
+    }
+
+    fn tag(
+        &mut self,
+        tags: &mut Self::Tags,
+        name: &[u8],
+        value: RawTag<'_>,
+    ) -> ControlFlow<Self::Output> {
+        tags.push((
+            String::from_utf8_lossy(name).to_string(),
+            value.decode_utf8_lossy().to_string(),
+        ));
+
+        ControlFlow::Continue(())
+    }
+
+    fn begin_movetext(&mut self, tags: Self::Tags) -> ControlFlow<Self::Output, Self::Movetext> {
+        let mut game = PgnGame::new();
+
+        game.headers = tags;
+
+        if let Some(result) = game.header("Result") {
+            game.result = result.to_string();
+        }
+
+        match game.start_position() {
+            Ok(pos) => ControlFlow::Continue(GameBuilder { game, pos }),
+            Err(err) => ControlFlow::Break(Err(err)),
+        }
+    }
+
+    fn san(
+        &mut self,
+        movetext: &mut Self::Movetext,
+        san_plus: SanPlus,
+    ) -> ControlFlow<Self::Output> {
+        let m = match san_plus.san.to_move(&movetext.pos) {
+            Ok(m) => m,
+            _ => return ControlFlow::Break(Err(PgnError::IllegalMove(san_plus.to_string()))),
+        };
+
+        movetext.game.moves.push(PgnMove {
+            san: san_plus.to_string(),
+            uci: m.to_uci(CastlingMode::Standard).to_string(),
+            nags: vec![],
+            comment: None,
+            variation: vec![],
+        });
+
+        movetext.pos.play_unchecked(m);
+
+        ControlFlow::Continue(())
+    }
+
+    fn nag(&mut self, movetext: &mut Self::Movetext, nag: Nag) -> ControlFlow<Self::Output> {
+        if let Some(m) = movetext.game.moves.last_mut() {
+            m.nags.push(nag.0);
+        }
+
+        ControlFlow::Continue(())
+    }
+
+    fn comment(
+        &mut self,
+        movetext: &mut Self::Movetext,
+        comment: RawComment<'_>,
+    ) -> ControlFlow<Self::Output> {
+        let text = String::from_utf8_lossy(comment.as_bytes())
+            .trim()
+            .to_string();
+
+        let target = match movetext.game.moves.last_mut() {
+            Some(m) => &mut m.comment,
+            _ => &mut movetext.game.comment,
+        };
+
+        *target = Some(match target.take() {
+            Some(existing) => format!("{} {}", existing, text),
+            _ => text,
+        });
+
+        ControlFlow::Continue(())
+    }
+
+    fn outcome(
+        &mut self,
+        movetext: &mut Self::Movetext,
+        outcome: Outcome,
+    ) -> ControlFlow<Self::Output> {
+        movetext.game.result = outcome.as_str().to_string();
+
+        ControlFlow::Continue(())
+    }
+
+    fn end_game(&mut self, movetext: Self::Movetext) -> Self::Output {
+        Ok(movetext.game)
+    }
+}
+
+/// read all games from pgn text
+pub fn read_games(pgn: &str) -> Result<Vec<PgnGame>, PgnError> {
+    let mut reader = Reader::new(pgn.as_bytes());
+
+    let mut games = vec![];
+
+    while let Some(game) = reader.read_game(&mut GameVisitor)? {
+        games.push(game?);
+    }
+
+    Ok(games)
+}
+
+/// escape pgn tag value
+fn escape_tag(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// movetext tokens of a line of moves starting with the given side to move and move number
+fn line_tokens(turn: Color, fullmoves: u32, moves: &[PgnMove], tokens: &mut Vec<String>) {
+    let mut turn = turn;
+    let mut fullmoves = fullmoves;
+    let mut need_number = true;
+
+    for m in moves {
+        // move numbers are kept in the same token as the move, so that they are not wrapped apart
+        tokens.push(match turn {
+            Color::White => format!("{}. {}", fullmoves, m.san),
+            Color::Black if need_number => format!("{}... {}", fullmoves, m.san),
+            _ => m.san.clone(),
+        });
+
+        for nag in &m.nags {
+            tokens.push(format!("${}", nag));
+        }
+
+        need_number = false;
+
+        if let Some(comment) = &m.comment {
+            tokens.push(format!("{{ {} }}", comment.replace('}', "")));
+
+            need_number = true;
+        }
+
+        if !m.variation.is_empty() {
+            let variation: Vec<PgnMove> = m
+                .variation
+                .iter()
+                .map(|san| PgnMove {
+                    san: san.clone(),
+                    uci: String::new(),
+                    nags: vec![],
+                    comment: None,
+                    variation: vec![],
+                })
+                .collect();
+
+            let mut variation_tokens = vec![];
+
+            // the variation starts from the same move number and color as the move it replaces
+            line_tokens(turn, fullmoves, &variation, &mut variation_tokens);
+
+            if let Some(first) = variation_tokens.first_mut() {
+                *first = format!("({}", first);
+            }
+
+            if let Some(last) = variation_tokens.last_mut() {
+                last.push(')');
+            }
+
+            tokens.append(&mut variation_tokens);
+
+            need_number = true;
+        }
+
+        if turn == Color::Black {
+            fullmoves += 1;
+        }
+
+        turn = !turn;
+    }
+}
+
+/// display pgn game as pgn text
+impl std::fmt::Display for PgnGame {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (name, value) in &self.headers {
+            writeln!(f, "[{} \"{}\"]", name, escape_tag(value))?;
+        }
+
+        writeln!(f)?;
+
+        let mut tokens = vec![];
+
+        if let Some(comment) = &self.comment {
+            tokens.push(format!("{{ {} }}", comment.replace('}', "")));
+        }
+
+        let start = self.start_position().unwrap_or_default();
+
+        line_tokens(
+            start.turn(),
+            start.fullmoves().get(),
+            &self.moves,
+            &mut tokens,
+        );
+
+        tokens.push(self.result.clone());
+
+        let mut line_length = 0;
+
+        for token in tokens {
+            if line_length > 0 && line_length + 1 + token.len() > MAX_LINE_LENGTH {
+                writeln!(f)?;
+
+                line_length = 0;
+            }
+
+            if line_length > 0 {
+                write!(f, " ")?;
+
+                line_length += 1;
+            }
+
+            write!(f, "{}", token)?;
+
+            line_length += token.len();
+        }
+
+        writeln!(f)
+    }
+}
+
+#[test]
+fn read_write_pgn() {
+    let pgn = "[Event \"Test\"]\n[Result \"1-0\"]\n\n1. e4 e5 2. Qh5 $2 { risky } Nc6 (2... Nf6) 3. Bc4 Nf6 4. Qxf7# 1-0\n";
+
+    let games = read_games(pgn).unwrap();
+
+    assert_eq!(games.len(), 1);
+
+    let game = &games[0];
+
+    assert_eq!(game.header("Event"), Some("Test"));
+    assert_eq!(game.moves.len(), 7);
+    assert_eq!(game.moves[2].uci, "d1h5");
+    assert_eq!(game.moves[2].nags, vec![2]);
+    assert_eq!(game.moves[6].san, "Qxf7#");
+    assert_eq!(game.result, "1-0");
+
+    let mut game = game.clone();
+
+    game.moves[3].variation = vec!["g6".to_string(), "Qxe5+".to_string()];
+
+    assert_eq!(
+        game.to_string(),
+        "[Event \"Test\"]\n[Result \"1-0\"]\n\n1. e4 e5 2. Qh5 $2 { risky } 2... Nc6 (2... g6 3. Qxe5+) 3. Bc4 Nf6 4. Qxf7# 1-0\n"
+    );
+}
@@ -0,0 +1,223 @@
+use log::{debug, info, log_enabled, warn, Level};
+
+use std::sync::{Arc, Mutex};
+
+use thiserror::Error;
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::metrics::MetricsSnapshot;
+use crate::uciengine::UciEngine;
+
+/// PoolError captures possible engine pool errors
+#[derive(Error, Debug)]
+pub enum PoolError {
+    #[error("engine pool is shut down")]
+    Closed,
+}
+
+/// function starting an engine that replaces a terminated one,
+/// gets the index of the terminated engine in engines
+pub type Respawn = Box<dyn Fn(usize) -> std::io::Result<Arc<UciEngine>> + Send + Sync>;
+
+/// pool of engines, jobs wait in a fair queue until an engine is idle
+pub struct EnginePool {
+    /// all engines
+    engines: Mutex<Vec<Arc<UciEngine>>>,
+    /// idle engines
+    idle: Mutex<Vec<Arc<UciEngine>>>,
+    /// one permit per idle engine, closed on shutdown
+    permits: Arc<Semaphore>,
+    /// number of engines
+    size: usize,
+    /// replaces terminated engines when they are acquired, None keeps them
+    respawn: Option<Respawn>,
+    /// metrics of replaced engines
+    retired: Mutex<MetricsSnapshot>,
+}
+
+/// debug engine pool
+impl std::fmt::Debug for EnginePool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "EnginePool [size {}, idle {}, respawn {}]",
+            self.size,
+            self.idle(),
+            self.respawn.is_some()
+        )
+    }
+}
+
+/// engine taken from the pool, goes back to the pool when dropped
+#[derive(Debug)]
+pub struct PooledEngine {
+    engine: Option<Arc<UciEngine>>,
+    pool: Arc<EnginePool>,
+    _permit: OwnedSemaphorePermit,
+}
+
+/// pooled engine implementation
+impl std::ops::Deref for PooledEngine {
+    type Target = Arc<UciEngine>;
+
+    fn deref(&self) -> &Self::Target {
+        self.engine.as_ref().unwrap()
+    }
+}
+
+/// return engine to the pool
+impl Drop for PooledEngine {
+    fn drop(&mut self) {
+        if let Some(engine) = self.engine.take() {
+            self.pool.idle.lock().unwrap().push(engine);
+        }
+    }
+}
+
+/// engine pool implementation
+impl EnginePool {
+    /// create new pool of engines, terminated engines are kept
+    pub fn from_engines(engines: Vec<Arc<UciEngine>>) -> Arc<Self> {
+        Self::with_respawn(engines, None)
+    }
+
+    /// create new pool of engines, terminated engines are replaced
+    /// by engines started with respawn when they are acquired,
+    /// if respawn fails the terminated engine is kept
+    pub fn respawning<F>(engines: Vec<Arc<UciEngine>>, respawn: F) -> Arc<Self>
+    where
+        F: Fn(usize) -> std::io::Result<Arc<UciEngine>> + Send + Sync + 'static,
+    {
+        Self::with_respawn(engines, Some(Box::new(respawn)))
+    }
+
+    /// create new pool of engines with optional respawn
+    fn with_respawn(engines: Vec<Arc<UciEngine>>, respawn: Option<Respawn>) -> Arc<Self> {
+        let size = engines.len();
+
+        Arc::new(Self {
+            engines: Mutex::new(engines.clone()),
+            idle: Mutex::new(engines),
+            permits: Arc::new(Semaphore::new(size)),
+            size,
+            respawn,
+            retired: Mutex::new(MetricsSnapshot::default()),
+        })
+    }
+
+    /// create new pool of size engines started from path, terminated engines are restarted
+    pub fn new<T: core::fmt::Display>(path: T, size: usize) -> Arc<Self> {
+        let path = path.to_string();
+
+        Self::respawning(
+            (0..size).map(|_| UciEngine::new(&path)).collect(),
+            move |_| UciEngine::spawn(tokio::process::Command::new(&path)),
+        )
+    }
+
+    /// number of engines
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// all engines, idle or not
+    pub fn engines(&self) -> Vec<Arc<UciEngine>> {
+        self.engines.lock().unwrap().clone()
+    }
+
+    /// number of idle engines
+    pub fn idle(&self) -> usize {
+        self.permits.available_permits()
+    }
+
+    /// metrics of all engines added up, including replaced engines
+    pub fn metrics(&self) -> MetricsSnapshot {
+        let retired = *self.retired.lock().unwrap();
+
+        self.engines
+            .lock()
+            .unwrap()
+            .iter()
+            .fold(retired, |sum, engine| sum + engine.metrics.snapshot())
+    }
+
+    /// replace engine if it terminated and the pool respawns, returns engine to use
+    fn revive(&self, engine: Arc<UciEngine>) -> Arc<UciEngine> {
+        let respawn = match &self.respawn {
+            Some(respawn) if engine.is_terminated() => respawn,
+            _ => return engine,
+        };
+
+        let index = match self
+            .engines
+            .lock()
+            .unwrap()
+            .iter()
+            .position(|pooled| Arc::ptr_eq(pooled, &engine))
+        {
+            Some(index) => index,
+            _ => return engine,
+        };
+
+        if log_enabled!(Level::Info) {
+            info!("replacing terminated pooled engine {}", index);
+        }
+
+        let fresh = match respawn(index) {
+            Ok(fresh) => fresh,
+            Err(err) => {
+                warn!("restarting pooled engine {} failed : {}", index, err);
+
+                return engine;
+            }
+        };
+
+        fresh.metrics.record_restart();
+
+        {
+            let mut retired = self.retired.lock().unwrap();
+
+            *retired = *retired + engine.metrics.snapshot();
+        }
+
+        self.engines.lock().unwrap()[index] = fresh.clone();
+
+        fresh
+    }
+
+    /// wait for an idle engine, error if the pool is shut down
+    pub async fn acquire(self: &Arc<Self>) -> Result<PooledEngine, PoolError> {
+        let permit = self
+            .permits
+            .clone()
+            .acquire_owned()
+            .await
+            .map_err(|_| PoolError::Closed)?;
+
+        let engine = self.idle.lock().unwrap().pop();
+
+        let engine = engine.map(|engine| self.revive(engine));
+
+        if log_enabled!(Level::Debug) {
+            debug!("acquired pooled engine, {} idle", self.idle());
+        }
+
+        Ok(PooledEngine {
+            engine,
+            pool: self.clone(),
+            _permit: permit,
+        })
+    }
+
+    /// shut down all idle engines, later acquires fail
+    pub async fn shutdown(&self, timeout: std::time::Duration) {
+        self.permits.close();
+
+        let engines: Vec<Arc<UciEngine>> = self.idle.lock().unwrap().drain(..).collect();
+
+        for engine in engines {
+            engine.shutdown(timeout).await;
+        }
+    }
+}
@@ -0,0 +1,1041 @@
+use serde::{Deserialize, Serialize};
+
+use std::fmt;
+use std::str::FromStr;
+
+use thiserror::Error;
+
+use shakmaty::fen::Fen;
+use shakmaty::uci::UciMove;
+use shakmaty::{CastlingMode, Chess};
+
+use crate::analysis::{AnalysisInfo, Score, ScoreType};
+
+/// ProtocolError captures possible uci message parsing errors
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum ProtocolError {
+    #[error("empty message")]
+    Empty,
+    #[error("unknown command '{0}'")]
+    UnknownCommand(String),
+    #[error("malformed message '{0}'")]
+    Malformed(String),
+}
+
+/// malformed message error for line
+fn malformed(line: &str) -> ProtocolError {
+    ProtocolError::Malformed(line.to_string())
+}
+
+/// parse number token of line
+fn number<N: FromStr>(line: &str, token: Option<&str>) -> Result<N, ProtocolError> {
+    token
+        .and_then(|token| token.parse::<N>().ok())
+        .ok_or_else(|| malformed(line))
+}
+
+/// write space separated words
+fn write_words(f: &mut fmt::Formatter<'_>, words: &[String]) -> fmt::Result {
+    for word in words {
+        write!(f, " {}", word)?;
+    }
+
+    Ok(())
+}
+
+/// position of position command
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Position {
+    /// starting position
+    Startpos,
+    /// position from fen
+    Fen(String),
+}
+
+/// position implementation
+impl Position {
+    /// chess position after playing moves, None if fen or moves are invalid
+    pub fn to_chess(&self, moves: &[String]) -> Option<Chess> {
+        let mut pos: Chess = match self {
+            Position::Startpos => Chess::default(),
+            Position::Fen(fen) => Fen::from_ascii(fen.as_bytes())
+                .ok()?
+                .into_position(CastlingMode::Standard)
+                .ok()?,
+        };
+
+        for uci in moves {
+            let m = UciMove::from_ascii(uci.as_bytes())
+                .ok()?
+                .to_move(&pos)
+                .ok()?;
+
+            shakmaty::Position::play_unchecked(&mut pos, m);
+        }
+
+        Some(pos)
+    }
+}
+
+/// parameters of go command, None and false mean not given
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct GoParams {
+    /// restrict search to these moves
+    pub searchmoves: Vec<String>,
+    /// search in ponder mode
+    pub ponder: bool,
+    /// white time in milliseconds
+    pub wtime: Option<u64>,
+    /// black time in milliseconds
+    pub btime: Option<u64>,
+    /// white increment in milliseconds
+    pub winc: Option<u64>,
+    /// black increment in milliseconds
+    pub binc: Option<u64>,
+    /// moves to next time control
+    pub movestogo: Option<u64>,
+    /// search to depth
+    pub depth: Option<u64>,
+    /// search this many nodes
+    pub nodes: Option<u64>,
+    /// search for mate in this many moves
+    pub mate: Option<u64>,
+    /// search exactly this many milliseconds
+    pub movetime: Option<u64>,
+    /// search until stop
+    pub infinite: bool,
+    /// parameters not defined by uci ( e.g. perft ), passed through in order
+    #[serde(default)]
+    pub extra: Vec<(String, String)>,
+}
+
+/// go params implementation
+impl GoParams {
+    /// set parameter from key and value ( value is ignored for ponder and infinite,
+    /// searchmoves value is a space separated list of moves ), unknown keys are kept
+    /// as extra parameters
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), ProtocolError> {
+        let line = format!("{} {}", key, value);
+
+        match key {
+            "searchmoves" => {
+                self.searchmoves = value.split_whitespace().map(|m| m.to_string()).collect()
+            }
+            "ponder" => self.ponder = true,
+            "infinite" => self.infinite = true,
+            "wtime" => self.wtime = Some(number(&line, Some(value.trim()))?),
+            "btime" => self.btime = Some(number(&line, Some(value.trim()))?),
+            "winc" => self.winc = Some(number(&line, Some(value.trim()))?),
+            "binc" => self.binc = Some(number(&line, Some(value.trim()))?),
+            "movestogo" => self.movestogo = Some(number(&line, Some(value.trim()))?),
+            "depth" => self.depth = Some(number(&line, Some(value.trim()))?),
+            "nodes" => self.nodes = Some(number(&line, Some(value.trim()))?),
+            "mate" => self.mate = Some(number(&line, Some(value.trim()))?),
+            "movetime" => self.movetime = Some(number(&line, Some(value.trim()))?),
+            _ => self.extra.push((key.to_string(), value.trim().to_string())),
+        }
+
+        Ok(())
+    }
+}
+
+/// register command
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Register {
+    /// register later
+    Later,
+    /// register with name and / or code
+    With {
+        name: Option<String>,
+        code: Option<String>,
+    },
+}
+
+/// message sent from gui to engine
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum GuiToEngine {
+    /// uci
+    Uci,
+    /// debug on / off
+    Debug(bool),
+    /// isready
+    IsReady,
+    /// setoption name <name> [ value <value> ]
+    SetOption { name: String, value: Option<String> },
+    /// register
+    Register(Register),
+    /// ucinewgame
+    UciNewGame,
+    /// position startpos | fen <fen> [ moves <moves> ]
+    Position {
+        position: Position,
+        moves: Vec<String>,
+    },
+    /// go
+    Go(GoParams),
+    /// stop
+    Stop,
+    /// ponderhit
+    PonderHit,
+    /// quit
+    Quit,
+}
+
+/// display gui to engine message as uci command
+impl fmt::Display for GuiToEngine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GuiToEngine::Uci => write!(f, "uci"),
+            GuiToEngine::Debug(on) => write!(f, "debug {}", if *on { "on" } else { "off" }),
+            GuiToEngine::IsReady => write!(f, "isready"),
+            GuiToEngine::SetOption { name, value } => {
+                write!(f, "setoption name {}", name)?;
+
+                if let Some(value) = value {
+                    write!(f, " value {}", value)?;
+                }
+
+                Ok(())
+            }
+            GuiToEngine::Register(Register::Later) => write!(f, "register later"),
+            GuiToEngine::Register(Register::With { name, code }) => {
+                write!(f, "register")?;
+
+                if let Some(name) = name {
+                    write!(f, " name {}", name)?;
+                }
+
+                if let Some(code) = code {
+                    write!(f, " code {}", code)?;
+                }
+
+                Ok(())
+            }
+            GuiToEngine::UciNewGame => write!(f, "ucinewgame"),
+            GuiToEngine::Position { position, moves } => {
+                match position {
+                    Position::Startpos => write!(f, "position startpos")?,
+                    Position::Fen(fen) => write!(f, "position fen {}", fen)?,
+                }
+
+                if !moves.is_empty() {
+                    write!(f, " moves")?;
+
+                    write_words(f, moves)?;
+                }
+
+                Ok(())
+            }
+            GuiToEngine::Go(params) => {
+                write!(f, "go")?;
+
+                if params.ponder {
+                    write!(f, " ponder")?;
+                }
+
+                let numbers = [
+                    ("wtime", params.wtime),
+                    ("btime", params.btime),
+                    ("winc", params.winc),
+                    ("binc", params.binc),
+                    ("movestogo", params.movestogo),
+                    ("depth", params.depth),
+                    ("nodes", params.nodes),
+                    ("mate", params.mate),
+                    ("movetime", params.movetime),
+                ];
+
+                for (key, value) in numbers.iter() {
+                    if let Some(value) = value {
+                        write!(f, " {} {}", key, value)?;
+                    }
+                }
+
+                if params.infinite {
+                    write!(f, " infinite")?;
+                }
+
+                for (key, value) in &params.extra {
+                    write!(f, " {}", key)?;
+
+                    if !value.is_empty() {
+                        write!(f, " {}", value)?;
+                    }
+                }
+
+                // searchmoves goes last, its move list runs until the next keyword
+                if !params.searchmoves.is_empty() {
+                    write!(f, " searchmoves")?;
+
+                    write_words(f, &params.searchmoves)?;
+                }
+
+                Ok(())
+            }
+            GuiToEngine::Stop => write!(f, "stop"),
+            GuiToEngine::PonderHit => write!(f, "ponderhit"),
+            GuiToEngine::Quit => write!(f, "quit"),
+        }
+    }
+}
+
+/// go command keywords
+const GO_KEYWORDS: [&str; 12] = [
+    "searchmoves",
+    "ponder",
+    "wtime",
+    "btime",
+    "winc",
+    "binc",
+    "movestogo",
+    "depth",
+    "nodes",
+    "mate",
+    "movetime",
+    "infinite",
+];
+
+/// split words at keyword, returns words before and after it
+fn split_at_keyword<'a>(
+    words: &'a [&'a str],
+    keyword: &str,
+) -> (&'a [&'a str], Option<&'a [&'a str]>) {
+    match words.iter().position(|word| *word == keyword) {
+        Some(index) => (&words[0..index], Some(&words[index + 1..])),
+        _ => (words, None),
+    }
+}
+
+/// parse gui to engine message from uci command
+impl FromStr for GuiToEngine {
+    type Err = ProtocolError;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+
+        let (command, args) = match words.split_first() {
+            Some((command, args)) => (*command, args),
+            _ => return Err(ProtocolError::Empty),
+        };
+
+        Ok(match command {
+            "uci" => GuiToEngine::Uci,
+            "debug" => match args.first() {
+                Some(&"on") => GuiToEngine::Debug(true),
+                Some(&"off") => GuiToEngine::Debug(false),
+                _ => return Err(malformed(line)),
+            },
+            "isready" => GuiToEngine::IsReady,
+            "setoption" => {
+                let (_, name) = split_at_keyword(args, "name");
+
+                let (name, value) = split_at_keyword(name.ok_or_else(|| malformed(line))?, "value");
+
+                if name.is_empty() {
+                    return Err(malformed(line));
+                }
+
+                GuiToEngine::SetOption {
+                    name: name.join(" "),
+                    value: value.map(|value| value.join(" ")),
+                }
+            }
+            "register" => match args {
+                ["later"] => GuiToEngine::Register(Register::Later),
+                _ => {
+                    let (before_code, code) = split_at_keyword(args, "code");
+                    let (_, name) = split_at_keyword(before_code, "name");
+
+                    if name.is_none() && code.is_none() {
+                        return Err(malformed(line));
+                    }
+
+                    GuiToEngine::Register(Register::With {
+                        name: name.map(|name| name.join(" ")),
+                        code: code.map(|code| code.join(" ")),
+                    })
+                }
+            },
+            "ucinewgame" => GuiToEngine::UciNewGame,
+            "position" => {
+                let (spec, moves) = split_at_keyword(args, "moves");
+
+                let position = match spec.split_first() {
+                    Some((&"startpos", [])) => Position::Startpos,
+                    Some((&"fen", fen)) if !fen.is_empty() => Position::Fen(fen.join(" ")),
+                    _ => return Err(malformed(line)),
+                };
+
+                GuiToEngine::Position {
+                    position,
+                    moves: moves
+                        .unwrap_or_default()
+                        .iter()
+                        .map(|m| m.to_string())
+                        .collect(),
+                }
+            }
+            "go" => {
+                let mut params = GoParams::default();
+
+                let mut index = 0;
+
+                while index < args.len() {
+                    let key = args[index];
+
+                    let value_end = match key {
+                        "ponder" | "infinite" => index + 1,
+                        "searchmoves" => args[index + 1..]
+                            .iter()
+                            .position(|word| GO_KEYWORDS.contains(word))
+                            .map_or(args.len(), |pos| index + 1 + pos),
+                        _ => index + 2,
+                    };
+
+                    if value_end > args.len() {
+                        return Err(malformed(line));
+                    }
+
+                    params.set(key, &args[index + 1..value_end].join(" "))?;
+
+                    index = value_end;
+                }
+
+                GuiToEngine::Go(params)
+            }
+            "stop" => GuiToEngine::Stop,
+            "ponderhit" => GuiToEngine::PonderHit,
+            "quit" => GuiToEngine::Quit,
+            _ => return Err(ProtocolError::UnknownCommand(command.to_string())),
+        })
+    }
+}
+
+/// id sent by engine
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Id {
+    /// engine name
+    Name(String),
+    /// engine author
+    Author(String),
+}
+
+/// status of copyprotection and registration
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Status {
+    /// checking
+    Checking,
+    /// ok
+    Ok,
+    /// error
+    Error,
+}
+
+/// status display names
+impl Status {
+    /// uci name of status
+    fn as_str(self) -> &'static str {
+        match self {
+            Status::Checking => "checking",
+            Status::Ok => "ok",
+            Status::Error => "error",
+        }
+    }
+
+    /// parse status from uci name
+    fn parse(line: &str, word: Option<&&str>) -> Result<Self, ProtocolError> {
+        match word {
+            Some(&"checking") => Ok(Status::Checking),
+            Some(&"ok") => Ok(Status::Ok),
+            Some(&"error") => Ok(Status::Error),
+            _ => Err(malformed(line)),
+        }
+    }
+}
+
+/// item of info message
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum InfoItem {
+    /// depth
+    Depth(u64),
+    /// seldepth
+    SelDepth(u64),
+    /// time in milliseconds
+    Time(u64),
+    /// nodes
+    Nodes(u64),
+    /// principal variation
+    Pv(Vec<String>),
+    /// multipv
+    MultiPv(u64),
+    /// score with bound ( Exact if the score is not a bound )
+    Score { score: Score, bound: ScoreType },
+    /// currmove
+    CurrMove(String),
+    /// currmovenumber
+    CurrMoveNumber(u64),
+    /// hashfull in permill
+    HashFull(u64),
+    /// nodes per second
+    Nps(u64),
+    /// tablebase hits
+    TbHits(u64),
+    /// shredderbase hits
+    SbHits(u64),
+    /// cpu load in permill
+    CpuLoad(u64),
+    /// string, runs to the end of the line
+    String(String),
+    /// refutation, refuted move followed by the refuting line
+    Refutation(Vec<String>),
+    /// currline, with optional cpu number
+    CurrLine {
+        cpu: Option<u64>,
+        moves: Vec<String>,
+    },
+}
+
+/// info keywords
+const INFO_KEYWORDS: [&str; 17] = [
+    "depth",
+    "seldepth",
+    "time",
+    "nodes",
+    "pv",
+    "multipv",
+    "score",
+    "currmove",
+    "currmovenumber",
+    "hashfull",
+    "nps",
+    "tbhits",
+    "sbhits",
+    "cpuload",
+    "string",
+    "refutation",
+    "currline",
+];
+
+/// option type with default values
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum OptionType {
+    /// check box
+    Check { default: bool },
+    /// spin wheel
+    Spin { default: i64, min: i64, max: i64 },
+    /// combo box
+    Combo { default: String, vars: Vec<String> },
+    /// button
+    Button,
+    /// string, may be empty
+    String { default: String },
+}
+
+/// option announced by engine
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UciOption {
+    /// option name, may contain spaces
+    pub name: String,
+    /// option type
+    pub option_type: OptionType,
+}
+
+/// message sent from engine to gui
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum EngineToGui {
+    /// id name | author
+    Id(Id),
+    /// uciok
+    UciOk,
+    /// readyok
+    ReadyOk,
+    /// bestmove <move> [ ponder <move> ]
+    BestMove {
+        bestmove: String,
+        ponder: Option<String>,
+    },
+    /// copyprotection
+    CopyProtection(Status),
+    /// registration
+    Registration(Status),
+    /// info
+    Info(Vec<InfoItem>),
+    /// option
+    Option(UciOption),
+}
+
+/// engine to gui implementation
+impl EngineToGui {
+    /// info message of analysis info, depth and score are always sent,
+    /// other values only if set
+    pub fn from_analysis_info(ai: &AnalysisInfo) -> Self {
+        let mut items = vec![InfoItem::Depth(ai.depth as u64)];
+
+        let numbers = [
+            (
+                ai.seldepth as u64,
+                InfoItem::SelDepth as fn(u64) -> InfoItem,
+            ),
+            (ai.multipv as u64, InfoItem::MultiPv),
+        ];
+
+        let counters = [
+            (ai.nodes, InfoItem::Nodes as fn(u64) -> InfoItem),
+            (ai.nps, InfoItem::Nps),
+            (ai.hashfull as u64, InfoItem::HashFull),
+            (ai.tbhits, InfoItem::TbHits),
+            (ai.cpuload as u64, InfoItem::CpuLoad),
+            (ai.time as u64, InfoItem::Time),
+        ];
+
+        for (value, item) in numbers.iter() {
+            if *value > 0 {
+                items.push(item(*value));
+            }
+        }
+
+        items.push(InfoItem::Score {
+            score: ai.score,
+            bound: ai.scoretype,
+        });
+
+        for (value, item) in counters.iter() {
+            if *value > 0 {
+                items.push(item(*value));
+            }
+        }
+
+        if let Some(currmove) = ai.currmove() {
+            items.push(InfoItem::CurrMove(currmove));
+        }
+
+        if ai.currmovenumber > 0 {
+            items.push(InfoItem::CurrMoveNumber(ai.currmovenumber as u64));
+        }
+
+        if let Some(pv) = ai.pv() {
+            items.push(InfoItem::Pv(
+                pv.split_whitespace().map(|m| m.to_string()).collect(),
+            ));
+        }
+
+        EngineToGui::Info(items)
+    }
+}
+
+/// display engine to gui message as uci line
+impl fmt::Display for EngineToGui {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EngineToGui::Id(Id::Name(name)) => write!(f, "id name {}", name),
+            EngineToGui::Id(Id::Author(author)) => write!(f, "id author {}", author),
+            EngineToGui::UciOk => write!(f, "uciok"),
+            EngineToGui::ReadyOk => write!(f, "readyok"),
+            EngineToGui::BestMove { bestmove, ponder } => {
+                write!(f, "bestmove {}", bestmove)?;
+
+                if let Some(ponder) = ponder {
+                    write!(f, " ponder {}", ponder)?;
+                }
+
+                Ok(())
+            }
+            EngineToGui::CopyProtection(status) => write!(f, "copyprotection {}", status.as_str()),
+            EngineToGui::Registration(status) => write!(f, "registration {}", status.as_str()),
+            EngineToGui::Info(items) => {
+                write!(f, "info")?;
+
+                for item in items {
+                    match item {
+                        InfoItem::Depth(depth) => write!(f, " depth {}", depth)?,
+                        InfoItem::SelDepth(seldepth) => write!(f, " seldepth {}", seldepth)?,
+                        InfoItem::Time(time) => write!(f, " time {}", time)?,
+                        InfoItem::Nodes(nodes) => write!(f, " nodes {}", nodes)?,
+                        InfoItem::Pv(moves) => {
+                            write!(f, " pv")?;
+
+                            write_words(f, moves)?;
+                        }
+                        InfoItem::MultiPv(multipv) => write!(f, " multipv {}", multipv)?,
+                        InfoItem::Score { score, bound } => {
+                            match score {
+                                Score::Cp(cp) => write!(f, " score cp {}", cp)?,
+                                Score::Mate(mate) => write!(f, " score mate {}", mate)?,
+                            }
+
+                            match bound {
+                                ScoreType::Lowerbound => write!(f, " lowerbound")?,
+                                ScoreType::Upperbound => write!(f, " upperbound")?,
+                                ScoreType::Exact => {}
+                            }
+                        }
+                        InfoItem::CurrMove(m) => write!(f, " currmove {}", m)?,
+                        InfoItem::CurrMoveNumber(number) => {
+                            write!(f, " currmovenumber {}", number)?
+                        }
+                        InfoItem::HashFull(hashfull) => write!(f, " hashfull {}", hashfull)?,
+                        InfoItem::Nps(nps) => write!(f, " nps {}", nps)?,
+                        InfoItem::TbHits(tbhits) => write!(f, " tbhits {}", tbhits)?,
+                        InfoItem::SbHits(sbhits) => write!(f, " sbhits {}", sbhits)?,
+                        InfoItem::CpuLoad(cpuload) => write!(f, " cpuload {}", cpuload)?,
+                        InfoItem::String(string) => write!(f, " string {}", string)?,
+                        InfoItem::Refutation(moves) => {
+                            write!(f, " refutation")?;
+
+                            write_words(f, moves)?;
+                        }
+                        InfoItem::CurrLine { cpu, moves } => {
+                            write!(f, " currline")?;
+
+                            if let Some(cpu) = cpu {
+                                write!(f, " {}", cpu)?;
+                            }
+
+                            write_words(f, moves)?;
+                        }
+                    }
+                }
+
+                Ok(())
+            }
+            EngineToGui::Option(option) => {
+                write!(f, "option name {} type ", option.name)?;
+
+                match &option.option_type {
+                    OptionType::Check { default } => write!(f, "check default {}", default),
+                    OptionType::Spin { default, min, max } => {
+                        write!(f, "spin default {} min {} max {}", default, min, max)
+                    }
+                    OptionType::Combo { default, vars } => {
+                        write!(f, "combo default {}", default)?;
+
+                        for var in vars {
+                            write!(f, " var {}", var)?;
+                        }
+
+                        Ok(())
+                    }
+                    OptionType::Button => write!(f, "button"),
+                    OptionType::String { default } => {
+                        write!(f, "string default")?;
+
+                        if !default.is_empty() {
+                            write!(f, " {}", default)?;
+                        }
+
+                        Ok(())
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// parse info items from words following info
+fn parse_info(line: &str, words: &[&str]) -> Result<Vec<InfoItem>, ProtocolError> {
+    let mut items = vec![];
+
+    let mut index = 0;
+
+    // words from index up to the next info keyword
+    let list_end = |from: usize| {
+        words[from..]
+            .iter()
+            .position(|word| INFO_KEYWORDS.contains(word))
+            .map_or(words.len(), |pos| from + pos)
+    };
+
+    while index < words.len() {
+        let key = words[index];
+        let value = words.get(index + 1).copied();
+
+        index += 2;
+
+        let item = match key {
+            "depth" => InfoItem::Depth(number(line, value)?),
+            "seldepth" => InfoItem::SelDepth(number(line, value)?),
+            "time" => InfoItem::Time(number(line, value)?),
+            "nodes" => InfoItem::Nodes(number(line, value)?),
+            "multipv" => InfoItem::MultiPv(number(line, value)?),
+            "currmove" => InfoItem::CurrMove(value.ok_or_else(|| malformed(line))?.to_string()),
+            "currmovenumber" => InfoItem::CurrMoveNumber(number(line, value)?),
+            "hashfull" => InfoItem::HashFull(number(line, value)?),
+            "nps" => InfoItem::Nps(number(line, value)?),
+            "tbhits" => InfoItem::TbHits(number(line, value)?),
+            "sbhits" => InfoItem::SbHits(number(line, value)?),
+            "cpuload" => InfoItem::CpuLoad(number(line, value)?),
+            "score" => {
+                let score_value = number(line, words.get(index).copied())?;
+
+                let score = match value {
+                    Some("cp") => Score::Cp(score_value),
+                    Some("mate") => Score::Mate(score_value),
+                    _ => return Err(malformed(line)),
+                };
+
+                index += 1;
+
+                let bound = match words.get(index) {
+                    Some(&"lowerbound") => ScoreType::Lowerbound,
+                    Some(&"upperbound") => ScoreType::Upperbound,
+                    _ => ScoreType::Exact,
+                };
+
+                if let ScoreType::Lowerbound | ScoreType::Upperbound = bound {
+                    index += 1;
+                }
+
+                InfoItem::Score { score, bound }
+            }
+            "string" => {
+                let from = index - 1;
+
+                index = words.len();
+
+                InfoItem::String(words[from.min(index)..].join(" "))
+            }
+            "pv" | "refutation" | "currline" => {
+                let from = index - 1;
+
+                index = list_end(from);
+
+                let mut moves: Vec<String> =
+                    words[from..index].iter().map(|m| m.to_string()).collect();
+
+                match key {
+                    "pv" => InfoItem::Pv(moves),
+                    "refutation" => InfoItem::Refutation(moves),
+                    _ => {
+                        let cpu = moves.first().and_then(|cpu| cpu.parse::<u64>().ok());
+
+                        if cpu.is_some() {
+                            moves.remove(0);
+                        }
+
+                        InfoItem::CurrLine { cpu, moves }
+                    }
+                }
+            }
+            _ => return Err(malformed(line)),
+        };
+
+        items.push(item);
+    }
+
+    Ok(items)
+}
+
+/// parse option from words following option
+fn parse_option(line: &str, words: &[&str]) -> Result<UciOption, ProtocolError> {
+    let (_, rest) = split_at_keyword(words, "name");
+    let (name, rest) = split_at_keyword(rest.ok_or_else(|| malformed(line))?, "type");
+
+    let rest = rest.ok_or_else(|| malformed(line))?;
+
+    let (option_type, params) = match rest.split_first() {
+        Some((option_type, params)) if !name.is_empty() => (*option_type, params),
+        _ => return Err(malformed(line)),
+    };
+
+    // value of parameter up to the next parameter keyword
+    let param = |key: &str| -> Option<String> {
+        let from = params.iter().position(|word| *word == key)? + 1;
+
+        let to = params[from..]
+            .iter()
+            .position(|word| ["default", "min", "max", "var"].contains(word))
+            .map_or(params.len(), |pos| from + pos);
+
+        Some(params[from..to].join(" "))
+    };
+
+    let option_type = match option_type {
+        "check" => OptionType::Check {
+            default: number(line, param("default").as_deref())?,
+        },
+        "spin" => OptionType::Spin {
+            default: number(line, param("default").as_deref())?,
+            min: number(line, param("min").as_deref())?,
+            max: number(line, param("max").as_deref())?,
+        },
+        "combo" => {
+            let mut vars = vec![];
+
+            for (index, word) in params.iter().enumerate() {
+                if *word == "var" {
+                    let to = params[index + 1..]
+                        .iter()
+                        .position(|word| *word == "var" || *word == "default")
+                        .map_or(params.len(), |pos| index + 1 + pos);
+
+                    vars.push(params[index + 1..to].join(" "));
+                }
+            }
+
+            OptionType::Combo {
+                default: param("default").ok_or_else(|| malformed(line))?,
+                vars,
+            }
+        }
+        "button" => OptionType::Button,
+        "string" => {
+            // string default runs to the end of the line, <empty> is the empty string
+            let default = split_at_keyword(params, "default")
+                .1
+                .map(|default| default.join(" "))
+                .unwrap_or_default();
+
+            OptionType::String {
+                default: if default == "<empty>" {
+                    String::new()
+                } else {
+                    default
+                },
+            }
+        }
+        _ => return Err(malformed(line)),
+    };
+
+    Ok(UciOption {
+        name: name.join(" "),
+        option_type,
+    })
+}
+
+/// parse engine to gui message from uci line
+impl FromStr for EngineToGui {
+    type Err = ProtocolError;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+
+        let (command, args) = match words.split_first() {
+            Some((command, args)) => (*command, args),
+            _ => return Err(ProtocolError::Empty),
+        };
+
+        Ok(match command {
+            "id" => match args.split_first() {
+                Some((&"name", name)) => EngineToGui::Id(Id::Name(name.join(" "))),
+                Some((&"author", author)) => EngineToGui::Id(Id::Author(author.join(" "))),
+                _ => return Err(malformed(line)),
+            },
+            "uciok" => EngineToGui::UciOk,
+            "readyok" => EngineToGui::ReadyOk,
+            "bestmove" => match args {
+                [bestmove] => EngineToGui::BestMove {
+                    bestmove: bestmove.to_string(),
+                    ponder: None,
+                },
+                [bestmove, "ponder", ponder, ..] => EngineToGui::BestMove {
+                    bestmove: bestmove.to_string(),
+                    ponder: Some(ponder.to_string()),
+                },
+                // some engines send trailing words, they are ignored
+                [bestmove, ..] => EngineToGui::BestMove {
+                    bestmove: bestmove.to_string(),
+                    ponder: None,
+                },
+                _ => return Err(malformed(line)),
+            },
+            "copyprotection" => EngineToGui::CopyProtection(Status::parse(line, args.first())?),
+            "registration" => EngineToGui::Registration(Status::parse(line, args.first())?),
+            "info" => EngineToGui::Info(parse_info(line, args)?),
+            "option" => EngineToGui::Option(parse_option(line, args)?),
+            _ => return Err(ProtocolError::UnknownCommand(command.to_string())),
+        })
+    }
+}
+
+#[test]
+fn gui_to_engine_round_trip() {
+    for line in &[
+        "uci",
+        "debug on",
+        "isready",
+        "setoption name Clear Hash",
+        "setoption name Syzygy Path value /tb/wdl /tb/dtz",
+        "register later",
+        "register name Stefan MK code 4359874324",
+        "ucinewgame",
+        "position startpos",
+        "position startpos moves e2e4 e7e5",
+        "position fen 8/8/8/8/8/2k5/8/K6Q w - - 0 1 moves a1b1",
+        "go ponder wtime 60000 btime 60000 winc 1000 binc 1000 movestogo 40",
+        "go depth 12 nodes 100000 mate 3 movetime 1000 infinite searchmoves e2e4 d2d4",
+        "go perft 5",
+        "go depth 10 infinite variant 3 searchmoves e2e4",
+        "stop",
+        "ponderhit",
+        "quit",
+    ] {
+        let message = line.parse::<GuiToEngine>().unwrap();
+
+        assert_eq!(message.to_string(), *line);
+    }
+
+    assert_eq!("".parse::<GuiToEngine>(), Err(ProtocolError::Empty));
+    assert!("go depth".parse::<GuiToEngine>().is_err());
+    assert!("xyzzy".parse::<GuiToEngine>().is_err());
+}
+
+#[test]
+fn go_job_extra_params() {
+    let commands = crate::uciengine::GoJob::new()
+        .pos_startpos()
+        .go_opt("perft", 5)
+        .to_commands();
+
+    assert_eq!(commands.last().map(|c| c.as_str()), Some("go perft 5"));
+
+    assert_eq!(
+        "go perft 5".parse::<GuiToEngine>(),
+        Ok(GuiToEngine::Go(GoParams {
+            extra: vec![("perft".to_string(), "5".to_string())],
+            ..GoParams::default()
+        }))
+    );
+}
+
+#[test]
+fn engine_to_gui_round_trip() {
+    for line in &[
+        "id name Stockfish 12",
+        "id author the Stockfish developers (see AUTHORS file)",
+        "uciok",
+        "readyok",
+        "bestmove e2e4",
+        "bestmove e2e4 ponder e7e5",
+        "copyprotection checking",
+        "registration ok",
+        "info depth 20 seldepth 28 multipv 1 score cp 35 lowerbound nodes 1234567 nps 1500000 \
+         hashfull 120 tbhits 0 time 823 pv e2e4 e7e5 g1f3",
+        "info depth 5 score mate -3 pv e2e4",
+        "info currmove e2e4 currmovenumber 1 cpuload 990 sbhits 0",
+        "info refutation d1h5 g6h5",
+        "info currline 1 e2e4 e7e5",
+        "info depth 1 string NNUE evaluation using nn.nnue enabled",
+        "option name Hash type spin default 16 min 1 max 33554432",
+        "option name Ponder type check default false",
+        "option name UCI_Variant type combo default chess var chess var atomic var crazyhouse",
+        "option name Clear Hash type button",
+        "option name Debug Log File type string default",
+        "option name EvalFile type string default nn-82215d0fd0df.nnue",
+    ] {
+        let message = line.parse::<EngineToGui>().unwrap();
+
+        assert_eq!(message.to_string(), *line);
+    }
+
+    assert_eq!(
+        "bestmove (none)".parse::<EngineToGui>(),
+        Ok(EngineToGui::BestMove {
+            bestmove: "(none)".to_string(),
+            ponder: None
+        })
+    );
+    assert!("info depth x".parse::<EngineToGui>().is_err());
+    assert!("option name Hash type spin default 16"
+        .parse::<EngineToGui>()
+        .is_err());
+}
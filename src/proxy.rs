@@ -0,0 +1,314 @@
+use log::{debug, info, log_enabled, warn, Level};
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::protocol::*;
+use crate::uciengine::{GoJob, UciEngine};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+
+/// proxy configuration
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ProxyConfig {
+    /// options always set to these values, sent after uci and replacing values set by the gui
+    pub forced: HashMap<String, String>,
+    /// upper limits of numeric options
+    pub caps: HashMap<String, i64>,
+    /// maximum time of a search in milliseconds, the search is stopped when it is exceeded
+    pub max_time: Option<u64>,
+}
+
+/// proxy configuration implementation
+impl ProxyConfig {
+    /// create new proxy configuration forwarding commands unchanged
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// force option to value and return self
+    pub fn force<K: core::fmt::Display, V: core::fmt::Display>(
+        mut self,
+        name: K,
+        value: V,
+    ) -> Self {
+        self.forced
+            .insert(format!("{}", name), format!("{}", value));
+
+        self
+    }
+
+    /// cap numeric option and return self
+    pub fn cap<K: core::fmt::Display>(mut self, name: K, max: i64) -> Self {
+        self.caps.insert(format!("{}", name), max);
+
+        self
+    }
+
+    /// set maximum search time and return self
+    pub fn max_time(mut self, max_time: u64) -> Self {
+        self.max_time = Some(max_time);
+
+        self
+    }
+
+    /// rewrite command according to configuration
+    pub fn rewrite(&self, command: GuiToEngine) -> GuiToEngine {
+        match command {
+            GuiToEngine::SetOption { name, value } => {
+                let value = match (self.forced.get(&name), self.caps.get(&name), value) {
+                    (Some(forced), _, _) => Some(forced.to_string()),
+                    (_, Some(cap), Some(value)) => match value.trim().parse::<i64>() {
+                        Ok(number) if number > *cap => Some(cap.to_string()),
+                        _ => Some(value),
+                    },
+                    (_, _, value) => value,
+                };
+
+                GuiToEngine::SetOption { name, value }
+            }
+            GuiToEngine::Go(mut params) => {
+                if let (Some(max_time), Some(movetime)) = (self.max_time, params.movetime) {
+                    params.movetime = Some(movetime.min(max_time));
+                }
+
+                GuiToEngine::Go(params)
+            }
+            command => command,
+        }
+    }
+
+    /// setoption commands of forced options
+    fn forced_commands(&self) -> Vec<GuiToEngine> {
+        let mut names: Vec<&String> = self.forced.keys().collect();
+
+        names.sort();
+
+        names
+            .into_iter()
+            .map(|name| GuiToEngine::SetOption {
+                name: name.to_string(),
+                value: Some(self.forced[name].to_string()),
+            })
+            .collect()
+    }
+}
+
+/// uci proxy sitting between a gui and an engine, forwards gui commands rewritten
+/// according to the configuration and engine lines unchanged
+///
+/// commands are sent as custom go jobs in the order received, go jobs of its own
+/// wait for the searches started by the gui to finish
+pub struct UciProxy {
+    /// engine
+    engine: Arc<UciEngine>,
+    /// configuration
+    config: ProxyConfig,
+    /// id of the running search, 0 if none
+    search: Arc<AtomicU64>,
+}
+
+/// uci proxy implementation
+impl UciProxy {
+    /// create new proxy for engine
+    pub fn new(engine: Arc<UciEngine>, config: ProxyConfig) -> Self {
+        Self {
+            engine,
+            config,
+            search: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// send command to engine
+    fn send<T: core::fmt::Display>(&self, command: T) {
+        self.engine.go(GoJob::new().custom(command));
+    }
+
+    /// stop search after max time unless it finished before
+    fn watch_search(&self, search_id: u64) {
+        let max_time = match self.config.max_time {
+            Some(max_time) => max_time,
+            _ => return,
+        };
+
+        let engine = self.engine.clone();
+        let search = self.search.clone();
+
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(max_time)).await;
+
+            if search.load(Ordering::SeqCst) == search_id {
+                if log_enabled!(Level::Info) {
+                    info!("search {} exceeded {} ms, stopping it", search_id, max_time);
+                }
+
+                engine.go(GoJob::new().custom(GuiToEngine::Stop));
+            }
+        });
+    }
+
+    /// handle gui line, returns false on quit
+    fn handle(&self, line: &str, search_id: &mut u64) -> bool {
+        let command = match line.parse::<GuiToEngine>() {
+            Ok(command) => command,
+            Err(ProtocolError::Empty) => return true,
+            Err(err) => {
+                // unknown commands are forwarded unchanged
+                warn!("forwarding unparsed command {} : {}", line, err);
+
+                self.send(line);
+
+                return true;
+            }
+        };
+
+        let command = self.config.rewrite(command);
+
+        // the search is registered before go is sent, so that its bestmove can clear it
+        if let GuiToEngine::Go(_) = command {
+            *search_id += 1;
+
+            self.search.store(*search_id, Ordering::SeqCst);
+        }
+
+        self.send(&command);
+
+        match command {
+            GuiToEngine::Uci => {
+                for forced in self.config.forced_commands() {
+                    self.send(forced);
+                }
+            }
+            GuiToEngine::Go(params) if !params.infinite && !params.ponder => {
+                self.watch_search(*search_id)
+            }
+            GuiToEngine::PonderHit => self.watch_search(self.search.load(Ordering::SeqCst)),
+            GuiToEngine::Quit => return false,
+            _ => {}
+        }
+
+        true
+    }
+
+    /// run proxy, reading gui commands from input and writing engine lines to output,
+    /// returns when the gui quits or closes input, or the engine output closes
+    pub async fn run<R, W>(&self, input: R, mut output: W) -> std::io::Result<()>
+    where
+        R: AsyncRead + Unpin,
+        W: AsyncWrite + Unpin + Send + 'static,
+    {
+        // the gui must see every line, a lagging gui queues lines instead of losing them
+        let mut lrx = self.engine.line_sink();
+
+        let search = self.search.clone();
+
+        let engine = self.engine.clone();
+
+        let mut forward_handle = tokio::spawn(async move {
+            loop {
+                // lines already read are forwarded before closing
+                let recv_result = tokio::select! {
+                    biased;
+                    recv_result = lrx.recv() => recv_result,
+                    _ = engine.wait_output_closed() => break,
+                };
+
+                let line = match recv_result {
+                    Some(line) => line,
+                    _ => break,
+                };
+
+                if line.starts_with("bestmove") {
+                    search.store(0, Ordering::SeqCst);
+                }
+
+                let write_result = output.write_all(format!("{}\n", line).as_bytes()).await;
+
+                if write_result.and(output.flush().await).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut lines = BufReader::new(input).lines();
+
+        let mut search_id: u64 = 0;
+
+        loop {
+            let line = tokio::select! {
+                line = lines.next_line() => line?,
+                _ = self.engine.wait_output_closed() => {
+                    if log_enabled!(Level::Info) {
+                        info!("engine terminated, closing proxy");
+                    }
+
+                    break;
+                }
+            };
+
+            let line = match line {
+                Some(line) => line,
+                _ => {
+                    self.send(GuiToEngine::Quit);
+
+                    break;
+                }
+            };
+
+            if log_enabled!(Level::Debug) {
+                debug!("proxy in : {}", line);
+            }
+
+            if !self.handle(&line, &mut search_id) {
+                break;
+            }
+        }
+
+        // give the engine time to send its last lines
+        if tokio::time::timeout(Duration::from_millis(1000), &mut forward_handle)
+            .await
+            .is_err()
+        {
+            forward_handle.abort();
+        }
+
+        Ok(())
+    }
+}
+
+#[test]
+fn rewrite_commands() {
+    let config = ProxyConfig::new()
+        .force("Ponder", "false")
+        .cap("Threads", 4)
+        .max_time(1000);
+
+    let rewrite = |command: &str| {
+        config
+            .rewrite(command.parse::<GuiToEngine>().unwrap())
+            .to_string()
+    };
+
+    assert_eq!(
+        rewrite("setoption name Threads value 64"),
+        "setoption name Threads value 4"
+    );
+    assert_eq!(
+        rewrite("setoption name Threads value 2"),
+        "setoption name Threads value 2"
+    );
+    assert_eq!(
+        rewrite("setoption name Ponder value true"),
+        "setoption name Ponder value false"
+    );
+    assert_eq!(rewrite("go movetime 5000"), "go movetime 1000");
+    assert_eq!(rewrite("go depth 20"), "go depth 20");
+    assert_eq!(
+        config.forced_commands(),
+        vec![GuiToEngine::SetOption {
+            name: "Ponder".to_string(),
+            value: Some("false".to_string())
+        }]
+    );
+}
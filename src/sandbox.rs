@@ -0,0 +1,417 @@
+use log::{debug, log_enabled, warn, Level};
+
+use serde::{Deserialize, Serialize};
+
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+
+use tokio::process::Command;
+
+use crate::analysis::ParserConfig;
+use crate::pool::EnginePool;
+use crate::uciengine::{EngineError, UciEngine};
+
+/// resource limits and isolation of an engine process
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpawnOptions {
+    /// cpu time limit in seconds ( RLIMIT_CPU ), the engine gets SIGXCPU when it is reached
+    pub cpu_time: Option<u64>,
+    /// address space limit in bytes ( RLIMIT_AS )
+    pub address_space: Option<u64>,
+    /// open files limit ( RLIMIT_NOFILE )
+    pub open_files: Option<u64>,
+    /// run engine in its own process group, kill then kills the whole group
+    pub process_group: bool,
+    /// resident set size cap in bytes, checked by the memory watchdog
+    pub max_rss: Option<u64>,
+    /// memory watchdog sampling interval
+    pub rss_interval: Duration,
+    /// cpus the engine may run on ( sched_setaffinity ), all cpus if None
+    pub cpus: Option<Vec<usize>>,
+}
+
+/// default spawn options
+impl Default for SpawnOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// resource limit of value, hard limit hard_extra above the soft limit
+fn rlimit(value: u64, hard_extra: u64) -> libc::rlimit {
+    libc::rlimit {
+        rlim_cur: value as libc::rlim_t,
+        rlim_max: value.saturating_add(hard_extra) as libc::rlim_t,
+    }
+}
+
+/// error of the last libc call if it returned nonzero
+fn check(result: libc::c_int) -> std::io::Result<()> {
+    match result {
+        0 => Ok(()),
+        _ => Err(std::io::Error::last_os_error()),
+    }
+}
+
+/// cpu set of cpus, cpus beyond the cpu set size are ignored
+fn cpu_set(cpus: &[usize]) -> libc::cpu_set_t {
+    unsafe {
+        let mut cpu_set: libc::cpu_set_t = std::mem::zeroed();
+
+        for &cpu in cpus {
+            if cpu < libc::CPU_SETSIZE as usize {
+                libc::CPU_SET(cpu, &mut cpu_set);
+            }
+        }
+
+        cpu_set
+    }
+}
+
+/// cpus process pid may run on, 0 for the calling process
+pub fn read_affinity(pid: u32) -> std::io::Result<Vec<usize>> {
+    unsafe {
+        let mut cpu_set: libc::cpu_set_t = std::mem::zeroed();
+
+        check(libc::sched_getaffinity(
+            pid as libc::pid_t,
+            std::mem::size_of::<libc::cpu_set_t>(),
+            &mut cpu_set,
+        ))?;
+
+        Ok((0..libc::CPU_SETSIZE as usize)
+            .filter(|&cpu| libc::CPU_ISSET(cpu, &cpu_set))
+            .collect())
+    }
+}
+
+/// parse cpu list of the form 0-3,8,10-11
+pub fn parse_cpu_list(list: &str) -> Option<Vec<usize>> {
+    let mut cpus = vec![];
+
+    for range in list.trim().split(',').filter(|range| !range.is_empty()) {
+        match range.split_once('-') {
+            Some((first, last)) => cpus.extend(first.parse::<usize>().ok()?..=last.parse().ok()?),
+            _ => cpus.push(range.parse().ok()?),
+        }
+    }
+
+    Some(cpus)
+}
+
+/// cpus of numa nodes, as listed in /sys/devices/system/node, empty if not available
+pub fn numa_nodes() -> Vec<Vec<usize>> {
+    let mut nodes: Vec<(usize, Vec<usize>)> = std::fs::read_dir("/sys/devices/system/node")
+        .map(|entries| {
+            entries
+                .filter_map(|entry| {
+                    let entry = entry.ok()?;
+
+                    let node = entry
+                        .file_name()
+                        .to_str()?
+                        .strip_prefix("node")?
+                        .parse()
+                        .ok()?;
+
+                    let cpulist = std::fs::read_to_string(entry.path().join("cpulist")).ok()?;
+
+                    Some((node, parse_cpu_list(&cpulist)?))
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    nodes.sort();
+
+    nodes.into_iter().map(|(_, cpus)| cpus).collect()
+}
+
+/// split cpus into one set per engine of the given thread counts,
+/// an engine gets the cpus of a single numa node if some node has enough free cpus,
+/// if there are fewer cpus than threads the sets wrap around and overlap
+pub fn partition_cpus(cpus: &[usize], nodes: &[Vec<usize>], threads: &[usize]) -> Vec<Vec<usize>> {
+    // free cpus grouped by node, cpus of no known node form a group of their own
+    let mut groups: Vec<Vec<usize>> = nodes
+        .iter()
+        .map(|node| {
+            node.iter()
+                .copied()
+                .filter(|cpu| cpus.contains(cpu))
+                .collect()
+        })
+        .collect();
+
+    groups.push(
+        cpus.iter()
+            .copied()
+            .filter(|cpu| !nodes.iter().any(|node| node.contains(cpu)))
+            .collect(),
+    );
+
+    let order: Vec<usize> = groups.iter().flatten().copied().collect();
+
+    let mut next = 0;
+
+    threads
+        .iter()
+        .map(|&threads| {
+            let threads = threads.max(1);
+
+            if let Some(group) = groups.iter_mut().find(|group| group.len() >= threads) {
+                return group.drain(..threads).collect();
+            }
+
+            // no node has room, take the free cpus in order, then wrap around
+            let mut set: Vec<usize> = vec![];
+
+            for group in groups.iter_mut() {
+                let take = (threads - set.len()).min(group.len());
+
+                set.extend(group.drain(..take));
+            }
+
+            while set.len() < threads && !order.is_empty() {
+                set.push(order[next % order.len()]);
+
+                next += 1;
+            }
+
+            set.sort_unstable();
+            set.dedup();
+
+            set
+        })
+        .collect()
+}
+
+/// resident set size of process in bytes, None if it cannot be read
+pub fn read_rss(pid: u32) -> Option<u64> {
+    let status = std::fs::read_to_string(format!("/proc/{}/status", pid)).ok()?;
+
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+
+    let kb: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+
+    Some(kb * 1024)
+}
+
+/// spawn options implementation
+impl SpawnOptions {
+    /// create new spawn options without limits, sampling rss every 100 milliseconds
+    pub fn new() -> Self {
+        Self {
+            cpu_time: None,
+            address_space: None,
+            open_files: None,
+            process_group: false,
+            max_rss: None,
+            rss_interval: Duration::from_millis(100),
+            cpus: None,
+        }
+    }
+
+    /// set cpu time limit in seconds and return self
+    pub fn cpu_time(mut self, seconds: u64) -> Self {
+        self.cpu_time = Some(seconds);
+
+        self
+    }
+
+    /// set address space limit in bytes and return self
+    pub fn address_space(mut self, bytes: u64) -> Self {
+        self.address_space = Some(bytes);
+
+        self
+    }
+
+    /// set open files limit and return self
+    pub fn open_files(mut self, files: u64) -> Self {
+        self.open_files = Some(files);
+
+        self
+    }
+
+    /// set process group and return self
+    pub fn process_group(mut self, value: bool) -> Self {
+        self.process_group = value;
+
+        self
+    }
+
+    /// set resident set size cap in bytes and return self
+    pub fn max_rss(mut self, bytes: u64) -> Self {
+        self.max_rss = Some(bytes);
+
+        self
+    }
+
+    /// set memory watchdog sampling interval and return self
+    pub fn rss_interval(mut self, interval: Duration) -> Self {
+        self.rss_interval = interval;
+
+        self
+    }
+
+    /// set cpus and return self
+    pub fn cpus(mut self, cpus: Vec<usize>) -> Self {
+        self.cpus = Some(cpus);
+
+        self
+    }
+
+    /// set up command to apply limits, process group and cpu affinity in the child before exec
+    pub fn apply(&self, command: &mut Command) {
+        let options = self.clone();
+
+        // the cpu set is built here, so that the child does not allocate
+        let cpu_set = self.cpus.as_ref().map(|cpus| cpu_set(cpus));
+
+        // setrlimit and setpgid are async-signal-safe
+        let pre_exec = move || unsafe {
+            if let Some(seconds) = options.cpu_time {
+                // the hard limit one second later kills engines ignoring SIGXCPU
+                check(libc::setrlimit(libc::RLIMIT_CPU, &rlimit(seconds, 1)))?;
+            }
+
+            if let Some(bytes) = options.address_space {
+                check(libc::setrlimit(libc::RLIMIT_AS, &rlimit(bytes, 0)))?;
+            }
+
+            if let Some(files) = options.open_files {
+                check(libc::setrlimit(libc::RLIMIT_NOFILE, &rlimit(files, 0)))?;
+            }
+
+            if options.process_group {
+                check(libc::setpgid(0, 0))?;
+            }
+
+            if let Some(cpu_set) = &cpu_set {
+                check(libc::sched_setaffinity(
+                    0,
+                    std::mem::size_of::<libc::cpu_set_t>(),
+                    cpu_set,
+                ))?;
+            }
+
+            Ok(())
+        };
+
+        // the closure only calls async-signal-safe functions and does not allocate
+        unsafe {
+            command.pre_exec(pre_exec);
+        }
+    }
+}
+
+/// sample rss of engine process until it terminates or is dropped,
+/// kill it with a memory limit error if it exceeds max_rss
+async fn memory_watchdog(engine: Weak<UciEngine>, pid: u32, max_rss: u64, interval: Duration) {
+    loop {
+        tokio::time::sleep(interval).await;
+
+        let engine = match engine.upgrade() {
+            Some(engine) if !engine.is_terminated() => engine,
+            _ => break,
+        };
+
+        let rss = match read_rss(pid) {
+            Some(rss) => rss,
+            _ => break,
+        };
+
+        if rss > max_rss {
+            warn!("engine {} rss {} exceeds {}, killing it", pid, rss, max_rss);
+
+            engine.kill_with(EngineError::MemoryLimit {
+                rss,
+                limit: max_rss,
+            });
+
+            break;
+        }
+    }
+
+    if log_enabled!(Level::Debug) {
+        debug!("memory watchdog of engine {} stopped", pid);
+    }
+}
+
+/// uci engine sandboxing implementation
+impl UciEngine {
+    /// create new uci engine from command with resource limits, process group
+    /// and memory watchdog of spawn options
+    pub fn spawn_sandboxed(
+        command: Command,
+        options: &SpawnOptions,
+    ) -> std::io::Result<Arc<UciEngine>> {
+        Self::spawn_sandboxed_with_config(command, options, ParserConfig::implicit())
+    }
+
+    /// create new sandboxed uci engine, parsing engine output with parser configuration
+    pub fn spawn_sandboxed_with_config(
+        command: Command,
+        options: &SpawnOptions,
+        parser_config: ParserConfig,
+    ) -> std::io::Result<Arc<UciEngine>> {
+        let mut command = command;
+
+        options.apply(&mut command);
+
+        let engine = Self::spawn_with_config(command, parser_config)?;
+
+        engine.set_process_group(options.process_group);
+
+        if let (Some(max_rss), Some(pid)) = (options.max_rss, engine.pid()) {
+            tokio::spawn(memory_watchdog(
+                Arc::downgrade(&engine),
+                pid,
+                max_rss,
+                options.rss_interval,
+            ));
+        }
+
+        Ok(engine)
+    }
+
+    /// cpus the engine process may run on, None if there is no process
+    /// or its affinity cannot be read
+    pub fn affinity(&self) -> Option<Vec<usize>> {
+        read_affinity(self.pid()?).ok()
+    }
+}
+
+/// engine pool affinity implementation
+impl EnginePool {
+    /// cpus each engine of the pool may run on
+    pub fn affinities(&self) -> Vec<Option<Vec<usize>>> {
+        self.engines()
+            .iter()
+            .map(|engine| engine.affinity())
+            .collect()
+    }
+}
+
+#[test]
+fn partition_numa_nodes() {
+    assert_eq!(
+        parse_cpu_list("0-3,8,10-11\n"),
+        Some(vec![0, 1, 2, 3, 8, 10, 11])
+    );
+
+    let nodes = vec![vec![0, 1, 2, 3], vec![4, 5, 6, 7]];
+
+    let cpus: Vec<usize> = (0..8).collect();
+
+    // the second engine does not fit into the rest of node 0 and goes to node 1
+    assert_eq!(
+        partition_cpus(&cpus, &nodes, &[3, 2, 1, 2]),
+        vec![vec![0, 1, 2], vec![4, 5], vec![3], vec![6, 7]]
+    );
+
+    // more threads than cpus wrap around
+    assert_eq!(
+        partition_cpus(&[0, 1], &[], &[1, 1, 1]),
+        vec![vec![0], vec![1], vec![0]]
+    );
+}
@@ -0,0 +1,362 @@
+use log::{debug, info, log_enabled, warn, Level};
+
+use serde::{Deserialize, Serialize};
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+
+use tokio::sync::{broadcast, oneshot};
+
+use crate::analysis::AnalysisInfo;
+use crate::metrics::encode_prometheus;
+use crate::pool::{EnginePool, PooledEngine};
+use crate::uciengine::{GoJob, GoResult};
+
+/// analysis request, without limit the default movetime of the server is used
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AnalysisRequest {
+    /// position fen, starting position if None
+    #[serde(default)]
+    pub fen: Option<String>,
+    /// space separated uci moves played from the position
+    #[serde(default)]
+    pub moves: Option<String>,
+    /// search to depth
+    #[serde(default)]
+    pub depth: Option<usize>,
+    /// search this many nodes
+    #[serde(default)]
+    pub nodes: Option<u64>,
+    /// search this many milliseconds, capped to the maximum movetime of the server
+    /// ( searches limited by depth or nodes are capped to it as well )
+    #[serde(default)]
+    pub movetime: Option<usize>,
+    /// search until stopped, only over websocket
+    #[serde(default)]
+    pub infinite: bool,
+}
+
+/// server configuration
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ServerConfig {
+    /// movetime of requests without limit in milliseconds
+    pub default_movetime: usize,
+    /// maximum movetime in milliseconds
+    pub max_movetime: usize,
+}
+
+/// default server configuration
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// server configuration implementation
+impl ServerConfig {
+    /// create new server configuration, one second default and one minute maximum movetime
+    pub fn new() -> Self {
+        Self {
+            default_movetime: 1000,
+            max_movetime: 60000,
+        }
+    }
+
+    /// set default movetime and return self
+    pub fn default_movetime(mut self, movetime: usize) -> Self {
+        self.default_movetime = movetime;
+
+        self
+    }
+
+    /// set maximum movetime and return self
+    pub fn max_movetime(mut self, movetime: usize) -> Self {
+        self.max_movetime = movetime;
+
+        self
+    }
+}
+
+/// error sent to the client
+#[derive(Debug, Serialize)]
+struct ErrorResponse {
+    /// disposition
+    disposition: &'static str,
+    /// error message
+    error: String,
+}
+
+/// error response json
+fn error_json<T: core::fmt::Display>(error: T) -> String {
+    serde_json::to_string(&ErrorResponse {
+        disposition: "Error",
+        error: format!("{}", error),
+    })
+    .unwrap()
+}
+
+/// analysis request implementation
+impl AnalysisRequest {
+    /// go job of request, error if the position is invalid
+    pub fn to_go_job(&self, config: &ServerConfig) -> Result<GoJob, String> {
+        let mut go_job = match &self.fen {
+            Some(fen) => GoJob::new().pos_fen(fen),
+            _ => GoJob::new().pos_startpos(),
+        };
+
+        if let Some(moves) = &self.moves {
+            go_job = go_job.pos_moves(moves);
+        }
+
+        if go_job.position().is_none() {
+            return Err("invalid position".to_string());
+        }
+
+        if let Some(depth) = self.depth {
+            go_job = go_job.go_opt("depth", depth);
+        }
+
+        if let Some(nodes) = self.nodes {
+            go_job = go_job.go_opt("nodes", nodes);
+        }
+
+        let has_limit = self.depth.is_some() || self.nodes.is_some();
+
+        if self.infinite {
+            return Ok(go_job.go_opt("infinite", ""));
+        }
+
+        // every finite search ends within max movetime, so that it can not hold an engine
+        let movetime = match self.movetime {
+            Some(movetime) => movetime,
+            _ if has_limit => config.max_movetime,
+            _ => config.default_movetime,
+        };
+
+        go_job = go_job.go_opt("movetime", movetime.min(config.max_movetime));
+
+        Ok(go_job)
+    }
+}
+
+/// server state
+#[derive(Debug)]
+struct ServerState {
+    pool: Arc<EnginePool>,
+    config: ServerConfig,
+}
+
+/// router serving POST /analysis ( request json in, go result json out ),
+/// GET /ws ( websocket streaming analysis info ) and GET /metrics ( prometheus text )
+pub fn router(pool: Arc<EnginePool>, config: ServerConfig) -> Router {
+    Router::new()
+        .route("/analysis", post(analysis))
+        .route("/ws", get(websocket))
+        .route("/metrics", get(metrics))
+        .with_state(Arc::new(ServerState { pool, config }))
+}
+
+/// serve router on listener until the server fails
+pub async fn serve(
+    listener: tokio::net::TcpListener,
+    pool: Arc<EnginePool>,
+    config: ServerConfig,
+) -> std::io::Result<()> {
+    if log_enabled!(Level::Info) {
+        info!("analysis server listening on {:?}", listener.local_addr());
+    }
+
+    axum::serve(listener, router(pool, config)).await
+}
+
+/// json response with status
+fn json_response(status: StatusCode, json: String) -> Response {
+    (
+        status,
+        [(axum::http::header::CONTENT_TYPE, "application/json")],
+        json,
+    )
+        .into_response()
+}
+
+/// POST /analysis
+async fn analysis(
+    State(state): State<Arc<ServerState>>,
+    Json(request): Json<AnalysisRequest>,
+) -> Response {
+    if request.infinite {
+        return json_response(
+            StatusCode::BAD_REQUEST,
+            error_json("infinite analysis is only available over websocket"),
+        );
+    }
+
+    let go_job = match request.to_go_job(&state.config) {
+        Ok(go_job) => go_job,
+        Err(err) => return json_response(StatusCode::BAD_REQUEST, error_json(err)),
+    };
+
+    let engine = match state.pool.acquire().await {
+        Ok(engine) => engine,
+        Err(err) => return json_response(StatusCode::SERVICE_UNAVAILABLE, error_json(err)),
+    };
+
+    // stops the search if the client goes away before the result
+    let mut guard = SearchGuard {
+        engine: &engine,
+        done: false,
+    };
+
+    let go_result = engine.go(go_job).await;
+
+    guard.done = true;
+
+    match go_result {
+        Ok(go_result) => json_response(StatusCode::OK, go_result.to_json().unwrap()),
+        _ => json_response(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            error_json("engine terminated"),
+        ),
+    }
+}
+
+/// stops the search of a pooled engine when dropped before the search is done,
+/// so that the engine does not go back to the pool still searching
+struct SearchGuard<'a> {
+    engine: &'a PooledEngine,
+    done: bool,
+}
+
+/// stop unfinished search
+impl Drop for SearchGuard<'_> {
+    fn drop(&mut self) {
+        if !self.done {
+            if log_enabled!(Level::Debug) {
+                debug!("request dropped, stopping search");
+            }
+
+            self.engine.stop();
+        }
+    }
+}
+
+/// GET /metrics
+async fn metrics(State(state): State<Arc<ServerState>>) -> Response {
+    (
+        StatusCode::OK,
+        [(
+            axum::http::header::CONTENT_TYPE,
+            "text/plain; version=0.0.4",
+        )],
+        encode_prometheus(&[("pool", state.pool.metrics())]),
+    )
+        .into_response()
+}
+
+/// GET /ws
+async fn websocket(State(state): State<Arc<ServerState>>, upgrade: WebSocketUpgrade) -> Response {
+    upgrade.on_upgrade(move |socket| run_websocket(socket, state))
+}
+
+/// wait for value of optional future, pending forever if None
+async fn recv_opt<T, F: std::future::Future<Output = T>>(future: Option<F>) -> T {
+    match future {
+        Some(future) => future.await,
+        _ => std::future::pending().await,
+    }
+}
+
+/// websocket session, the client sends analysis requests as json and stop as text,
+/// the server streams analysis info json and finishes each search with go result json
+async fn run_websocket(mut socket: WebSocket, state: Arc<ServerState>) {
+    let mut engine: Option<PooledEngine> = None;
+    let mut arx: Option<broadcast::Receiver<AnalysisInfo>> = None;
+    let mut rrx: Option<oneshot::Receiver<GoResult>> = None;
+
+    loop {
+        let reply = tokio::select! {
+            message = socket.recv() => {
+                let text = match message {
+                    Some(Ok(Message::Text(text))) => text,
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                    _ => continue,
+                };
+
+                if text.trim() == "stop" {
+                    if let Some(engine) = &engine {
+                        engine.stop();
+                    }
+
+                    continue;
+                }
+
+                if engine.is_some() {
+                    error_json("search already running")
+                } else {
+                    match serde_json::from_str::<AnalysisRequest>(&text)
+                        .map_err(|err| err.to_string())
+                        .and_then(|request| request.to_go_job(&state.config))
+                    {
+                        Ok(go_job) => match state.pool.acquire().await {
+                            Ok(pooled) => {
+                                arx = Some(pooled.atx.subscribe());
+                                rrx = Some(pooled.go(go_job));
+                                engine = Some(pooled);
+
+                                continue;
+                            }
+                            Err(err) => error_json(err),
+                        },
+                        Err(err) => error_json(err),
+                    }
+                }
+            }
+            ai = recv_opt(arx.as_mut().map(|arx| arx.recv())) => match ai {
+                Ok(ai) => ai.to_json().unwrap(),
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => {
+                    arx = None;
+
+                    continue;
+                }
+            },
+            go_result = recv_opt(rrx.as_mut()) => {
+                arx = None;
+                rrx = None;
+                engine = None;
+
+                match go_result {
+                    Ok(go_result) => go_result.to_json().unwrap(),
+                    _ => error_json("engine terminated"),
+                }
+            }
+        };
+
+        if socket.send(Message::Text(reply)).await.is_err() {
+            break;
+        }
+    }
+
+    // a search still running is stopped before its engine goes back to the pool
+    if let (Some(engine), Some(rrx)) = (&engine, rrx) {
+        if log_enabled!(Level::Debug) {
+            debug!("websocket closed, stopping search");
+        }
+
+        engine.stop();
+
+        if tokio::time::timeout(Duration::from_millis(5000), rrx)
+            .await
+            .is_err()
+        {
+            warn!("search did not stop after websocket closed");
+        }
+    }
+}
@@ -0,0 +1,364 @@
+use serde::{Deserialize, Serialize};
+
+/// z value of a two sided 95 % confidence interval
+const Z_95: f64 = 1.959_963_984_540_054;
+
+/// logistic elo difference for expected score
+pub fn elo_from_score(score: f64) -> f64 {
+    -400.0 * (1.0 / score - 1.0).log10()
+}
+
+/// expected score for logistic elo difference
+pub fn score_from_elo(elo: f64) -> f64 {
+    1.0 / (1.0 + 10_f64.powf(-elo / 400.0))
+}
+
+/// error function ( Abramowitz and Stegun 7.1.26, absolute error below 1.5e-7 )
+fn erf(x: f64) -> f64 {
+    let t = 1.0 / (1.0 + 0.327_591_1 * x.abs());
+
+    let poly = t
+        * (0.254_829_592
+            + t * (-0.284_496_736
+                + t * (1.421_413_741 + t * (-1.453_152_027 + t * 1.061_405_429))));
+
+    let y = 1.0 - poly * (-x * x).exp();
+
+    if x < 0.0 {
+        -y
+    } else {
+        y
+    }
+}
+
+/// mean and variance of a distribution given as ( score, count ) pairs
+fn mean_variance(dist: &[(f64, usize)]) -> Option<(f64, f64, usize)> {
+    let n: usize = dist.iter().map(|(_, count)| count).sum();
+
+    if n == 0 {
+        return None;
+    }
+
+    let mean = dist
+        .iter()
+        .map(|(score, count)| score * *count as f64)
+        .sum::<f64>()
+        / n as f64;
+
+    let variance = dist
+        .iter()
+        .map(|(score, count)| (score - mean).powi(2) * *count as f64)
+        .sum::<f64>()
+        / n as f64;
+
+    Some((mean, variance, n))
+}
+
+/// elo estimate
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct EloEstimate {
+    /// elo difference
+    pub elo: f64,
+    /// half width of the 95 % confidence interval
+    pub error: f64,
+    /// likelihood of superiority
+    pub los: f64,
+}
+
+/// game results of a match, from the point of view of the first engine
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Trinomial {
+    /// losses
+    pub losses: usize,
+    /// draws
+    pub draws: usize,
+    /// wins
+    pub wins: usize,
+}
+
+/// trinomial implementation
+impl Trinomial {
+    /// create new trinomial from wins, losses and draws
+    pub fn new(wins: usize, losses: usize, draws: usize) -> Self {
+        Self {
+            losses,
+            draws,
+            wins,
+        }
+    }
+
+    /// number of games
+    pub fn games(&self) -> usize {
+        self.wins + self.losses + self.draws
+    }
+
+    /// per game score distribution
+    fn distribution(&self) -> [(f64, usize); 3] {
+        [(0.0, self.losses), (0.5, self.draws), (1.0, self.wins)]
+    }
+
+    /// likelihood of superiority, computed from wins and losses only
+    pub fn los(&self) -> f64 {
+        let decisive = (self.wins + self.losses) as f64;
+
+        if decisive == 0.0 {
+            return 0.5;
+        }
+
+        0.5 * (1.0 + erf((self.wins as f64 - self.losses as f64) / (2.0 * decisive).sqrt()))
+    }
+
+    /// elo estimate, None if there are no games or the score is 0 or 1
+    pub fn elo(&self) -> Option<EloEstimate> {
+        let (mean, variance, n) = mean_variance(&self.distribution())?;
+
+        estimate(mean, variance / n as f64, self.los())
+    }
+}
+
+/// game pair results of a match, each opening is played with colors swapped,
+/// counts are indexed by the pair score of the first engine in half points ( 0 to 4 )
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Pentanomial {
+    /// pair counts for pair scores 0, 0.5, 1, 1.5 and 2
+    pub counts: [usize; 5],
+}
+
+/// pentanomial implementation
+impl Pentanomial {
+    /// create pentanomial from first engine game scores in playing order,
+    /// a trailing unpaired game is ignored
+    pub fn from_scores(scores: &[f64]) -> Self {
+        let mut counts = [0; 5];
+
+        for pair in scores.chunks_exact(2) {
+            counts[((pair[0] + pair[1]) * 2.0).round() as usize] += 1;
+        }
+
+        Self { counts }
+    }
+
+    /// number of game pairs
+    pub fn pairs(&self) -> usize {
+        self.counts.iter().sum()
+    }
+
+    /// per game score distribution of pairs
+    fn distribution(&self) -> [(f64, usize); 5] {
+        let mut dist = [(0.0, 0); 5];
+
+        for (index, count) in self.counts.iter().enumerate() {
+            dist[index] = (index as f64 / 4.0, *count);
+        }
+
+        dist
+    }
+
+    /// elo estimate, None if there are no pairs or the score is 0 or 1,
+    /// likelihood of superiority uses the normal approximation of the pair scores
+    pub fn elo(&self) -> Option<EloEstimate> {
+        let (mean, variance, n) = mean_variance(&self.distribution())?;
+
+        let variance_of_mean = variance / n as f64;
+
+        let los = if variance_of_mean > 0.0 {
+            0.5 * (1.0 + erf((mean - 0.5) / (2.0 * variance_of_mean).sqrt()))
+        } else {
+            0.5
+        };
+
+        estimate(mean, variance_of_mean, los)
+    }
+}
+
+/// elo estimate from mean score and variance of the mean
+fn estimate(mean: f64, variance_of_mean: f64, los: f64) -> Option<EloEstimate> {
+    if mean <= 0.0 || mean >= 1.0 {
+        return None;
+    }
+
+    let stdev = variance_of_mean.sqrt();
+
+    let low = elo_from_score((mean - Z_95 * stdev).max(f64::EPSILON));
+    let high = elo_from_score((mean + Z_95 * stdev).min(1.0 - f64::EPSILON));
+
+    Some(EloEstimate {
+        elo: elo_from_score(mean),
+        error: (high - low) / 2.0,
+        los,
+    })
+}
+
+/// sprt decision
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum SprtStatus {
+    /// bounds not crossed yet
+    Continue,
+    /// lower bound crossed, elo0 hypothesis accepted
+    AcceptH0,
+    /// upper bound crossed, elo1 hypothesis accepted
+    AcceptH1,
+}
+
+/// sprt state after some games
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SprtResult {
+    /// log likelihood ratio
+    pub llr: f64,
+    /// lower bound
+    pub lower: f64,
+    /// upper bound
+    pub upper: f64,
+    /// decision
+    pub status: SprtStatus,
+}
+
+/// sequential probability ratio test of elo0 against elo1 ( logistic elo )
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Sprt {
+    /// elo of the null hypothesis
+    pub elo0: f64,
+    /// elo of the alternative hypothesis
+    pub elo1: f64,
+    /// probability of accepting H1 when H0 is true
+    pub alpha: f64,
+    /// probability of accepting H0 when H1 is true
+    pub beta: f64,
+}
+
+/// default sprt
+impl Default for Sprt {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// sprt implementation
+impl Sprt {
+    /// create new sprt with elo0 = 0, elo1 = 5, alpha = beta = 0.05
+    pub fn new() -> Self {
+        Self {
+            elo0: 0.0,
+            elo1: 5.0,
+            alpha: 0.05,
+            beta: 0.05,
+        }
+    }
+
+    /// set hypotheses and return self
+    pub fn elo(mut self, elo0: f64, elo1: f64) -> Self {
+        self.elo0 = elo0;
+        self.elo1 = elo1;
+
+        self
+    }
+
+    /// set error probabilities and return self
+    pub fn error(mut self, alpha: f64, beta: f64) -> Self {
+        self.alpha = alpha;
+        self.beta = beta;
+
+        self
+    }
+
+    /// lower and upper llr bounds
+    pub fn bounds(&self) -> (f64, f64) {
+        (
+            (self.beta / (1.0 - self.alpha)).ln(),
+            ((1.0 - self.beta) / self.alpha).ln(),
+        )
+    }
+
+    /// approximate log likelihood ratio of a score distribution
+    fn llr(&self, dist: &[(f64, usize)]) -> f64 {
+        let (mean, variance, n) = match mean_variance(dist) {
+            Some(mean_variance) => mean_variance,
+            _ => return 0.0,
+        };
+
+        if variance <= 0.0 {
+            return 0.0;
+        }
+
+        let s0 = score_from_elo(self.elo0);
+        let s1 = score_from_elo(self.elo1);
+
+        n as f64 * (s1 - s0) * (2.0 * mean - s0 - s1) / (2.0 * variance)
+    }
+
+    /// test result for llr
+    fn result(&self, llr: f64) -> SprtResult {
+        let (lower, upper) = self.bounds();
+
+        let status = if llr >= upper {
+            SprtStatus::AcceptH1
+        } else if llr <= lower {
+            SprtStatus::AcceptH0
+        } else {
+            SprtStatus::Continue
+        };
+
+        SprtResult {
+            llr,
+            lower,
+            upper,
+            status,
+        }
+    }
+
+    /// test game results
+    pub fn test_trinomial(&self, trinomial: &Trinomial) -> SprtResult {
+        self.result(self.llr(&trinomial.distribution()))
+    }
+
+    /// test game pair results
+    pub fn test_pentanomial(&self, pentanomial: &Pentanomial) -> SprtResult {
+        self.result(self.llr(&pentanomial.distribution()))
+    }
+}
+
+#[test]
+fn elo_estimate() {
+    assert!((elo_from_score(score_from_elo(35.0)) - 35.0).abs() < 1e-9);
+
+    let trinomial = Trinomial::new(60, 40, 100);
+    let elo = trinomial.elo().unwrap();
+
+    assert!((elo.elo - 34.86).abs() < 0.01);
+    assert!(elo.error > 30.0 && elo.error < 45.0);
+    assert!((trinomial.los() - 0.977).abs() < 0.001);
+
+    assert!(Trinomial::new(3, 0, 0).elo().is_none());
+
+    let pentanomial = Pentanomial::from_scores(&[1.0, 0.5, 0.0, 0.0, 0.5, 0.5, 1.0]);
+
+    assert_eq!(pentanomial.counts, [1, 0, 1, 1, 0]);
+    assert_eq!(pentanomial.pairs(), 3);
+    assert!(pentanomial.elo().unwrap().elo < 0.0);
+}
+
+#[test]
+fn sprt_bounds() {
+    let sprt = Sprt::new().elo(0.0, 10.0).error(0.05, 0.05);
+
+    let (lower, upper) = sprt.bounds();
+
+    assert!((lower + 2.944).abs() < 0.001);
+    assert!((upper - 2.944).abs() < 0.001);
+
+    assert_eq!(
+        sprt.test_trinomial(&Trinomial::new(600, 400, 1000)).status,
+        SprtStatus::AcceptH1
+    );
+    assert_eq!(
+        sprt.test_trinomial(&Trinomial::new(400, 600, 1000)).status,
+        SprtStatus::AcceptH0
+    );
+    assert_eq!(
+        sprt.test_pentanomial(&Pentanomial {
+            counts: [1, 2, 3, 2, 1]
+        })
+        .status,
+        SprtStatus::Continue
+    );
+}
@@ -0,0 +1,290 @@
+use log::{debug, log_enabled, warn, Level};
+
+use shakmaty::{CastlingMode, Chess, Move, Position};
+use shakmaty_syzygy::{AmbiguousWdl, Dtz, MaybeRounded, SyzygyError, Tablebase};
+
+use std::path::Path;
+
+/// tablebase probe result
+#[derive(Debug, Clone)]
+pub struct TablebaseProbe {
+    /// tablebase optimal move in uci notation
+    pub bestmove: String,
+    /// win / draw / loss from the point of view of the side to move
+    /// ( -2 loss, -1 blessed loss, 0 draw, 1 cursed win, 2 win )
+    pub wdl: i32,
+    /// distance to zeroing move in plies, rounded for some tables
+    pub dtz: i32,
+}
+
+/// source of tablebase answers, implemented by syzygy tables and by test doubles
+pub trait TableSource: Send + Sync {
+    /// maximum number of pieces of positions answered
+    fn max_pieces(&self) -> usize;
+
+    /// tablebase optimal move and the dtz after it, None if the game is over
+    fn best_move(&self, pos: &Chess) -> Result<Option<(Move, MaybeRounded<Dtz>)>, SyzygyError>;
+
+    /// win / draw / loss of position
+    fn probe_wdl(&self, pos: &Chess) -> Result<AmbiguousWdl, SyzygyError>;
+
+    /// distance to zeroing move of position
+    fn probe_dtz(&self, pos: &Chess) -> Result<MaybeRounded<Dtz>, SyzygyError>;
+}
+
+/// syzygy tables as table source
+impl TableSource for Tablebase<Chess> {
+    fn max_pieces(&self) -> usize {
+        Tablebase::max_pieces(self)
+    }
+
+    fn best_move(&self, pos: &Chess) -> Result<Option<(Move, MaybeRounded<Dtz>)>, SyzygyError> {
+        Tablebase::best_move(self, pos)
+    }
+
+    fn probe_wdl(&self, pos: &Chess) -> Result<AmbiguousWdl, SyzygyError> {
+        Tablebase::probe_wdl(self, pos)
+    }
+
+    fn probe_dtz(&self, pos: &Chess) -> Result<MaybeRounded<Dtz>, SyzygyError> {
+        Tablebase::probe_dtz(self, pos)
+    }
+}
+
+/// syzygy tablebase, needs both wdl and dtz tables for the probed material
+pub struct SyzygyTablebase {
+    tb: Tablebase<Chess>,
+    /// source answering instead of the tables, if any
+    source: Option<Box<dyn TableSource>>,
+}
+
+/// debug syzygy tablebase
+impl std::fmt::Debug for SyzygyTablebase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "SyzygyTablebase [max pieces {}]", self.max_pieces())
+    }
+}
+
+/// default syzygy tablebase
+impl Default for SyzygyTablebase {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// syzygy tablebase implementation
+impl SyzygyTablebase {
+    /// create new tablebase without tables
+    pub fn new() -> Self {
+        Self {
+            tb: Tablebase::new(),
+            source: None,
+        }
+    }
+
+    /// create tablebase answering from source instead of table files
+    pub fn from_source<S: TableSource + 'static>(source: S) -> Self {
+        Self {
+            tb: Tablebase::new(),
+            source: Some(Box::new(source)),
+        }
+    }
+
+    /// source answering probes
+    fn source(&self) -> &dyn TableSource {
+        match &self.source {
+            Some(source) => source.as_ref(),
+            _ => &self.tb,
+        }
+    }
+
+    /// create tablebase from tables in directory
+    pub fn open<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        let mut tablebase = Self::new();
+
+        tablebase.add_directory(path)?;
+
+        Ok(tablebase)
+    }
+
+    /// add tables in directory, returns the number of tables added
+    /// ( not used by tablebases created from a source )
+    pub fn add_directory<P: AsRef<Path>>(&mut self, path: P) -> std::io::Result<usize> {
+        self.tb.add_directory(path)
+    }
+
+    /// maximum number of pieces of added tables
+    pub fn max_pieces(&self) -> usize {
+        self.source().max_pieces()
+    }
+
+    /// true if position has few enough pieces and no castling rights to be probed
+    pub fn covers(&self, pos: &Chess) -> bool {
+        pos.board().occupied().count() <= self.max_pieces()
+            && pos.castles().castling_rights().is_empty()
+    }
+
+    /// probe position, None if not covered by tables, game over or probe failed
+    pub fn probe(&self, pos: &Chess) -> Option<TablebaseProbe> {
+        if !self.covers(pos) {
+            return None;
+        }
+
+        let source = self.source();
+
+        let probe_result = source.best_move(pos).and_then(|best_move| {
+            let wdl = source.probe_wdl(pos)?;
+            let dtz = source.probe_dtz(pos)?;
+
+            Ok(best_move.map(|(m, _)| TablebaseProbe {
+                bestmove: m.to_uci(CastlingMode::Standard).to_string(),
+                wdl: wdl.unambiguous().unwrap_or_else(|| wdl.after_zeroing()) as i32,
+                dtz: dtz.ignore_rounding().0,
+            }))
+        });
+
+        match probe_result {
+            Ok(probe) => {
+                if log_enabled!(Level::Debug) {
+                    debug!("tablebase probe {:?}", probe);
+                }
+
+                probe
+            }
+            Err(err) => {
+                warn!("tablebase probe failed {:?}", err);
+
+                None
+            }
+        }
+    }
+}
+
+#[test]
+fn probe_without_tables() {
+    let tablebase = SyzygyTablebase::new();
+
+    let pos: Chess = "8/8/8/8/8/2k5/8/K6Q w - - 0 1"
+        .parse::<shakmaty::fen::Fen>()
+        .unwrap()
+        .into_position(CastlingMode::Standard)
+        .unwrap();
+
+    assert_eq!(tablebase.max_pieces(), 0);
+    assert!(!tablebase.covers(&pos));
+    assert!(tablebase.probe(&pos).is_none());
+}
+
+#[cfg(test)]
+/// table source answering every position with fixed values
+struct FixedTables {
+    bestmove: Option<&'static str>,
+    wdl: AmbiguousWdl,
+    dtz: MaybeRounded<Dtz>,
+}
+
+#[cfg(test)]
+impl TableSource for FixedTables {
+    fn max_pieces(&self) -> usize {
+        3
+    }
+
+    fn best_move(&self, pos: &Chess) -> Result<Option<(Move, MaybeRounded<Dtz>)>, SyzygyError> {
+        Ok(self.bestmove.map(|uci| {
+            let m = shakmaty::uci::UciMove::from_ascii(uci.as_bytes())
+                .unwrap()
+                .to_move(pos)
+                .unwrap();
+
+            (m, self.dtz)
+        }))
+    }
+
+    fn probe_wdl(&self, _pos: &Chess) -> Result<AmbiguousWdl, SyzygyError> {
+        Ok(self.wdl)
+    }
+
+    fn probe_dtz(&self, _pos: &Chess) -> Result<MaybeRounded<Dtz>, SyzygyError> {
+        Ok(self.dtz)
+    }
+}
+
+#[test]
+fn probe_source() {
+    let pos: Chess = "8/8/8/8/8/2k5/8/K6Q w - - 0 1"
+        .parse::<shakmaty::fen::Fen>()
+        .unwrap()
+        .into_position(CastlingMode::Standard)
+        .unwrap();
+
+    let tablebase = SyzygyTablebase::from_source(FixedTables {
+        bestmove: Some("h1h8"),
+        wdl: AmbiguousWdl::Win,
+        dtz: MaybeRounded::Precise(Dtz(19)),
+    });
+
+    let probe = tablebase.probe(&pos).unwrap();
+
+    assert_eq!(probe.bestmove, "h1h8");
+    assert_eq!((probe.wdl, probe.dtz), (2, 19));
+
+    // ambiguous values are taken as if the position was just reached by a zeroing move
+    let tablebase = SyzygyTablebase::from_source(FixedTables {
+        bestmove: Some("a1b1"),
+        wdl: AmbiguousWdl::MaybeLoss,
+        dtz: MaybeRounded::Rounded(Dtz(-100)),
+    });
+
+    let probe = tablebase.probe(&pos).unwrap();
+
+    assert_eq!((probe.wdl, probe.dtz), (-2, -100));
+
+    let tablebase = SyzygyTablebase::from_source(FixedTables {
+        bestmove: Some("h1h2"),
+        wdl: AmbiguousWdl::BlessedLoss,
+        dtz: MaybeRounded::Precise(Dtz(-101)),
+    });
+
+    assert_eq!(tablebase.probe(&pos).unwrap().wdl, -1);
+
+    // game over, and too many pieces
+    let tablebase = SyzygyTablebase::from_source(FixedTables {
+        bestmove: None,
+        wdl: AmbiguousWdl::Draw,
+        dtz: MaybeRounded::Precise(Dtz(0)),
+    });
+
+    assert!(tablebase.probe(&pos).is_none());
+    assert!(!tablebase.covers(&Chess::default()));
+}
+
+#[test]
+fn probe_kqvk() {
+    // tables are not bundled, point SYZYGY_PATH to a directory with KQvK tables to run
+    let path = match std::env::var("SYZYGY_PATH") {
+        Ok(path) => path,
+        _ => return,
+    };
+
+    let tablebase = SyzygyTablebase::open(path).unwrap();
+
+    let pos: Chess = "8/8/8/8/8/2k5/8/K6Q w - - 0 1"
+        .parse::<shakmaty::fen::Fen>()
+        .unwrap()
+        .into_position(CastlingMode::Standard)
+        .unwrap();
+
+    let probe = tablebase.probe(&pos).unwrap();
+
+    assert_eq!(probe.wdl, 2);
+    assert!(probe.dtz > 0);
+
+    // the side to move loses with black to move
+    let pos: Chess = "8/8/8/8/8/2k5/8/K6Q b - - 0 1"
+        .parse::<shakmaty::fen::Fen>()
+        .unwrap()
+        .into_position(CastlingMode::Standard)
+        .unwrap();
+
+    assert_eq!(tablebase.probe(&pos).unwrap().wdl, -2);
+}
@@ -0,0 +1,254 @@
+use log::{debug, log_enabled, Level};
+
+use std::time::Duration;
+
+use crate::uciengine::{GoJob, GoResult, Timecontrol};
+
+/// minimum time in milliseconds ever given to the engine
+const MIN_THINK_TIME: usize = 10;
+/// default network latency overhead in milliseconds
+const DEFAULT_OVERHEAD: usize = 100;
+/// default time in milliseconds kept on the clock as a reserve
+const DEFAULT_SAFETY_MARGIN: usize = 300;
+/// weight of a new lag sample in the average lag ( in percent )
+const LAG_SAMPLE_WEIGHT: usize = 20;
+
+/// clock state of a game, as reported by the game server ( all times are in milliseconds )
+#[derive(Debug, Clone, Copy)]
+pub struct ClockState {
+    /// white time
+    pub wtime: usize,
+    /// white increment
+    pub winc: usize,
+    /// black time
+    pub btime: usize,
+    /// black increment
+    pub binc: usize,
+    /// moves to go until next time control, None for sudden death
+    pub movestogo: Option<usize>,
+    /// true if the engine plays white
+    pub white: bool,
+    /// full move number ( starting from 1 )
+    pub move_number: usize,
+}
+
+/// clock state implementation
+impl ClockState {
+    /// own remaining time and increment
+    fn own(&self) -> (usize, usize) {
+        if self.white {
+            (self.wtime, self.winc)
+        } else {
+            (self.btime, self.binc)
+        }
+    }
+}
+
+/// time given to the engine for a move
+#[derive(Debug)]
+pub enum TimeAllocation {
+    /// let the engine manage its time from the clock
+    Clock(Timecontrol),
+    /// let the engine think exactly this long ( milliseconds )
+    Movetime(usize),
+}
+
+/// time manager, turns game clock state into go parameters
+#[derive(Debug, Clone)]
+pub struct TimeManager {
+    /// network latency overhead in milliseconds, used until lag is observed
+    overhead: usize,
+    /// time in milliseconds kept on the clock as a reserve
+    safety_margin: usize,
+    /// allocate explicit movetime instead of passing the clock
+    movetime: bool,
+    /// average observed lag in milliseconds, None until first observation
+    avg_lag: Option<usize>,
+}
+
+/// default time manager
+impl Default for TimeManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// time manager implementation
+impl TimeManager {
+    /// create new time manager with defaults
+    pub fn new() -> Self {
+        Self {
+            overhead: DEFAULT_OVERHEAD,
+            safety_margin: DEFAULT_SAFETY_MARGIN,
+            movetime: false,
+            avg_lag: None,
+        }
+    }
+
+    /// set network latency overhead in milliseconds and return self
+    pub fn overhead(mut self, overhead: usize) -> Self {
+        self.overhead = overhead;
+
+        self
+    }
+
+    /// set safety margin in milliseconds and return self
+    pub fn safety_margin(mut self, safety_margin: usize) -> Self {
+        self.safety_margin = safety_margin;
+
+        self
+    }
+
+    /// set whether explicit movetime should be allocated and return self
+    pub fn movetime(mut self, movetime: bool) -> Self {
+        self.movetime = movetime;
+
+        self
+    }
+
+    /// expected lag per move in milliseconds,
+    /// average observed lag if any, overhead otherwise
+    pub fn lag(&self) -> usize {
+        self.avg_lag.unwrap_or(self.overhead)
+    }
+
+    /// observe a finished search, elapsed is the time charged by the game server
+    /// for the move, the part of it not spent searching is counted as lag
+    pub fn observe(&mut self, elapsed: Duration, go_result: &GoResult) {
+        let elapsed = elapsed.as_millis() as usize;
+
+        let lag = elapsed.saturating_sub(go_result.ai.time);
+
+        self.avg_lag = Some(match self.avg_lag {
+            Some(avg_lag) => (avg_lag * (100 - LAG_SAMPLE_WEIGHT) + lag * LAG_SAMPLE_WEIGHT) / 100,
+            _ => lag,
+        });
+
+        if log_enabled!(Level::Debug) {
+            debug!("observed lag {} , average lag {:?}", lag, self.avg_lag);
+        }
+    }
+
+    /// estimated number of moves left in the game
+    fn moves_left(clock: &ClockState) -> usize {
+        match clock.movestogo {
+            Some(movestogo) if movestogo > 0 => movestogo,
+            _ => std::cmp::max(10, 50_usize.saturating_sub(clock.move_number)),
+        }
+    }
+
+    /// time left on a clock once lag and safety margin are reserved
+    fn usable(&self, time: usize) -> usize {
+        std::cmp::max(
+            MIN_THINK_TIME,
+            time.saturating_sub(self.lag() + self.safety_margin),
+        )
+    }
+
+    /// allocate time for the next move
+    pub fn allocate(&self, clock: &ClockState) -> TimeAllocation {
+        if self.movetime {
+            let (time, inc) = clock.own();
+
+            let usable = self.usable(time);
+
+            let movetime = std::cmp::min(usable, usable / Self::moves_left(clock) + inc * 3 / 4);
+
+            return TimeAllocation::Movetime(std::cmp::max(MIN_THINK_TIME, movetime));
+        }
+
+        // only the own clock is reserved, the opponent's time is passed as is
+        let (wtime, btime) = if clock.white {
+            (self.usable(clock.wtime), clock.btime)
+        } else {
+            (clock.wtime, self.usable(clock.btime))
+        };
+
+        TimeAllocation::Clock(Timecontrol {
+            wtime,
+            winc: clock.winc,
+            btime,
+            binc: clock.binc,
+        })
+    }
+
+    /// allocate time for the next move and set it on go job, returns go job
+    pub fn go_job(&self, clock: &ClockState, go_job: GoJob) -> GoJob {
+        match self.allocate(clock) {
+            TimeAllocation::Clock(tc) => match clock.movestogo {
+                Some(movestogo) => go_job.tc(tc).go_opt("movestogo", movestogo),
+                _ => go_job.tc(tc),
+            },
+            TimeAllocation::Movetime(movetime) => go_job.go_opt("movetime", movetime),
+        }
+    }
+}
+
+#[test]
+fn allocate_clock() {
+    let tm = TimeManager::new().overhead(100).safety_margin(400);
+
+    let clock = ClockState {
+        wtime: 60000,
+        winc: 1000,
+        btime: 300,
+        binc: 1000,
+        movestogo: None,
+        white: true,
+        move_number: 1,
+    };
+
+    match tm.allocate(&clock) {
+        TimeAllocation::Clock(tc) => {
+            assert_eq!(tc.wtime, 59500);
+            assert_eq!(tc.winc, 1000);
+            assert_eq!(tc.btime, 300);
+        }
+        allocation => panic!("unexpected allocation {:?}", allocation),
+    }
+
+    match tm.clone().movetime(true).allocate(&clock) {
+        TimeAllocation::Movetime(movetime) => assert_eq!(movetime, 59500 / 49 + 750),
+        allocation => panic!("unexpected allocation {:?}", allocation),
+    }
+
+    let clock = ClockState {
+        white: false,
+        move_number: 30,
+        ..clock
+    };
+
+    match tm.allocate(&clock) {
+        TimeAllocation::Clock(tc) => {
+            assert_eq!(tc.wtime, 60000);
+            assert_eq!(tc.btime, MIN_THINK_TIME);
+        }
+        allocation => panic!("unexpected allocation {:?}", allocation),
+    }
+
+    assert_eq!(TimeManager::moves_left(&clock), 20);
+}
+
+#[test]
+fn observe_lag() {
+    let mut tm = TimeManager::new().overhead(500);
+
+    assert_eq!(tm.lag(), 500);
+
+    let mut go_result = GoResult {
+        bestmove: None,
+        ponder: None,
+        ai: crate::analysis::AnalysisInfo::new(),
+        source: crate::uciengine::MoveSource::Engine,
+    };
+
+    go_result.ai.time = 1000;
+
+    tm.observe(Duration::from_millis(1100), &go_result);
+
+    assert_eq!(tm.lag(), 100);
+
+    tm.observe(Duration::from_millis(1600), &go_result);
+
+    assert_eq!(tm.lag(), 200);
+}
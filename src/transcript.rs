@@ -0,0 +1,399 @@
+use log::{debug, log_enabled, warn, Level};
+
+use serde::{Deserialize, Serialize};
+
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+
+use crate::uciengine::UciEngine;
+
+/// direction of a transcript line
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    /// command sent to engine
+    ToEngine,
+    /// line received from engine
+    FromEngine,
+}
+
+/// transcript line
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TranscriptEntry {
+    /// unix time in milliseconds
+    pub ts: u64,
+    /// milliseconds since recording started
+    pub elapsed: u64,
+    /// id of the go job running when the line was sent or received, 0 if none
+    pub job: u64,
+    /// direction
+    pub dir: Direction,
+    /// line without line ending
+    pub line: String,
+}
+
+/// message to the transcript writer thread
+enum WriterMessage {
+    /// write entry
+    Entry(TranscriptEntry),
+    /// flush and acknowledge
+    Flush(mpsc::SyncSender<()>),
+}
+
+/// transcript recorder, writes one json entry per line
+///
+/// entries are written through a buffer by a thread of their own, so that recording
+/// does not block the engine tasks, the buffer is flushed whenever no entry is waiting
+/// and when the transcript is dropped
+pub struct Transcript {
+    wtx: std::sync::Mutex<mpsc::Sender<WriterMessage>>,
+    start: Instant,
+}
+
+/// debug transcript
+impl std::fmt::Debug for Transcript {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Transcript [started {:?} ago]", self.start.elapsed())
+    }
+}
+
+/// transcript implementation
+impl Transcript {
+    /// create transcript writing to writer
+    pub fn from_writer<W: Write + Send + 'static>(writer: W) -> Self {
+        let (wtx, wrx) = mpsc::channel::<WriterMessage>();
+
+        std::thread::spawn(move || write_entries(BufWriter::new(writer), wrx));
+
+        Self {
+            wtx: std::sync::Mutex::new(wtx),
+            start: Instant::now(),
+        }
+    }
+
+    /// create transcript writing to file, an existing file is truncated
+    pub fn create<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        Ok(Self::from_writer(std::fs::File::create(path)?))
+    }
+
+    /// record line
+    pub fn record(&self, dir: Direction, job: u64, line: &str) {
+        let entry = TranscriptEntry {
+            ts: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|since| since.as_millis() as u64)
+                .unwrap_or(0),
+            elapsed: self.start.elapsed().as_millis() as u64,
+            job,
+            dir,
+            line: line.to_string(),
+        };
+
+        let _ = self.wtx.lock().unwrap().send(WriterMessage::Entry(entry));
+    }
+
+    /// wait until the lines recorded so far are written, blocks the calling thread
+    pub fn flush(&self) {
+        let (atx, arx) = mpsc::sync_channel::<()>(1);
+
+        let send_result = self.wtx.lock().unwrap().send(WriterMessage::Flush(atx));
+
+        if send_result.is_ok() {
+            let _ = arx.recv();
+        }
+    }
+}
+
+/// write transcript entries received on wrx until all senders are gone
+fn write_entries<W: Write>(mut writer: BufWriter<W>, wrx: mpsc::Receiver<WriterMessage>) {
+    while let Ok(message) = wrx.recv() {
+        let mut next = Some(message);
+
+        // write all waiting entries before flushing
+        while let Some(message) = next {
+            match message {
+                WriterMessage::Entry(entry) => {
+                    let write_result = serde_json::to_writer(&mut writer, &entry)
+                        .map_err(std::io::Error::from)
+                        .and_then(|_| writer.write_all(b"\n"));
+
+                    if let Err(err) = write_result {
+                        warn!("writing transcript failed {:?}", err);
+                    }
+                }
+                WriterMessage::Flush(atx) => {
+                    let _ = writer.flush();
+
+                    let _ = atx.send(());
+                }
+            }
+
+            next = wrx.try_recv().ok();
+        }
+
+        if let Err(err) = writer.flush() {
+            warn!("flushing transcript failed {:?}", err);
+        }
+    }
+}
+
+/// transcript slot shared by the engine tasks, together with the id of the running go job
+#[derive(Debug, Default)]
+pub(crate) struct TranscriptSlot {
+    transcript: std::sync::Mutex<Option<std::sync::Arc<Transcript>>>,
+    job: AtomicU64,
+}
+
+/// transcript slot implementation
+impl TranscriptSlot {
+    /// set transcript, None stops recording
+    pub(crate) fn set(&self, transcript: Option<Transcript>) {
+        *self.transcript.lock().unwrap() = transcript.map(std::sync::Arc::new);
+    }
+
+    /// set id of running go job
+    pub(crate) fn set_job(&self, job: u64) {
+        self.job.store(job, Ordering::Relaxed);
+    }
+
+    /// id of running go job
+    pub(crate) fn job(&self) -> u64 {
+        self.job.load(Ordering::Relaxed)
+    }
+
+    /// record line of running go job if recording
+    pub(crate) fn record(&self, dir: Direction, line: &str) {
+        self.record_job(dir, self.job(), line);
+    }
+
+    /// record line of go job if recording
+    pub(crate) fn record_job(&self, dir: Direction, job: u64, line: &str) {
+        let transcript = self.transcript.lock().unwrap().clone();
+
+        if let Some(transcript) = transcript {
+            transcript.record(dir, job, line);
+        }
+    }
+
+    /// wait until the lines recorded so far are written, if recording
+    pub(crate) async fn flush(&self) {
+        let transcript = self.transcript.lock().unwrap().clone();
+
+        if let Some(transcript) = transcript {
+            let _ = tokio::task::spawn_blocking(move || transcript.flush()).await;
+        }
+    }
+}
+
+/// read transcript entries from jsonl text, empty lines are skipped
+pub fn read_transcript(text: &str) -> serde_json::Result<Vec<TranscriptEntry>> {
+    text.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(serde_json::from_str)
+        .collect()
+}
+
+/// replay of a recorded transcript acting as engine
+///
+/// engine lines recorded before the first command are sent at start, after that every
+/// command received answers with the engine lines recorded up to the next recorded command,
+/// the output is closed when the transcript is used up
+#[derive(Debug, Clone)]
+pub struct Replay {
+    /// recorded entries
+    entries: Vec<TranscriptEntry>,
+    /// true to keep recorded delays between engine lines
+    realtime: bool,
+}
+
+/// replay implementation
+impl Replay {
+    /// create new replay of entries, without delays
+    pub fn new(entries: Vec<TranscriptEntry>) -> Self {
+        Self {
+            entries,
+            realtime: false,
+        }
+    }
+
+    /// create new replay of transcript file
+    pub fn open<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        let text = std::fs::read_to_string(path)?;
+
+        Ok(Self::new(read_transcript(&text)?))
+    }
+
+    /// set realtime and return self, realtime replay keeps the recorded delays
+    pub fn realtime(mut self, realtime: bool) -> Self {
+        self.realtime = realtime;
+
+        self
+    }
+
+    /// engine lines following the command at index ( or the start for None ),
+    /// with the index of the next command
+    fn answer(&self, command: Option<usize>) -> (Vec<&TranscriptEntry>, Option<usize>) {
+        let from = command.map_or(0, |index| index + 1);
+
+        let next = self.entries[from..]
+            .iter()
+            .position(|entry| entry.dir == Direction::ToEngine)
+            .map(|pos| from + pos);
+
+        let to = next.unwrap_or(self.entries.len());
+
+        (self.entries[from..to].iter().collect(), next)
+    }
+
+    /// run replay, reading commands from input and writing engine lines to output
+    pub async fn run_async<R, W>(&self, input: R, mut output: W) -> std::io::Result<()>
+    where
+        R: AsyncRead + Unpin,
+        W: AsyncWrite + Unpin,
+    {
+        let mut lines = BufReader::new(input).lines();
+
+        let (mut answer, mut next) = self.answer(None);
+
+        let mut last_elapsed = self.entries.first().map_or(0, |entry| entry.elapsed);
+
+        loop {
+            for entry in answer {
+                if self.realtime {
+                    tokio::time::sleep(Duration::from_millis(
+                        entry.elapsed.saturating_sub(last_elapsed),
+                    ))
+                    .await;
+                }
+
+                last_elapsed = entry.elapsed;
+
+                output
+                    .write_all(format!("{}\n", entry.line).as_bytes())
+                    .await?;
+                output.flush().await?;
+            }
+
+            let index = match next {
+                Some(index) => index,
+                _ => return Ok(()),
+            };
+
+            let command = match lines.next_line().await? {
+                Some(command) => command,
+                _ => return Ok(()),
+            };
+
+            let recorded = &self.entries[index];
+
+            if command.trim() != recorded.line.trim() {
+                warn!(
+                    "replay command mismatch, received {} , recorded {}",
+                    command, recorded.line
+                );
+            } else if log_enabled!(Level::Debug) {
+                debug!("replay command {}", command);
+            }
+
+            last_elapsed = recorded.elapsed;
+
+            let (command_answer, command_next) = self.answer(Some(index));
+
+            answer = command_answer;
+            next = command_next;
+        }
+    }
+
+    /// run replay in a task and return an engine talking to it over an in-memory duplex
+    pub fn start_engine(self) -> std::sync::Arc<UciEngine> {
+        let (engine_io, replay_io) = tokio::io::duplex(64 * 1024);
+
+        tokio::spawn(async move {
+            let (input, output) = tokio::io::split(replay_io);
+
+            self.run_async(input, output).await
+        });
+
+        let (reader, writer) = tokio::io::split(engine_io);
+
+        UciEngine::from_io(reader, writer)
+    }
+}
+
+#[test]
+fn replay_answers() {
+    let entry = |elapsed, dir, line: &str| TranscriptEntry {
+        ts: 0,
+        elapsed,
+        job: 1,
+        dir,
+        line: line.to_string(),
+    };
+
+    let entries = [
+        entry(0, Direction::FromEngine, "Engine 1.0"),
+        entry(1, Direction::ToEngine, "go depth 1"),
+        entry(5, Direction::FromEngine, "info depth 1"),
+        entry(9, Direction::FromEngine, "bestmove e2e4"),
+        entry(10, Direction::ToEngine, "quit"),
+    ];
+
+    let jsonl: Vec<String> = entries
+        .iter()
+        .map(|entry| serde_json::to_string(entry).unwrap())
+        .collect();
+
+    let replay = Replay::new(read_transcript(&jsonl.join("\n")).unwrap());
+
+    let (answer, next) = replay.answer(None);
+
+    assert_eq!(answer.len(), 1);
+    assert_eq!(next, Some(1));
+
+    let (answer, next) = replay.answer(next);
+
+    assert_eq!(answer[1].line, "bestmove e2e4");
+    assert_eq!(next, Some(4));
+
+    assert_eq!(replay.answer(next), (vec![], None));
+}
+
+#[cfg(test)]
+#[derive(Clone, Default)]
+struct SharedBuffer(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+#[cfg(test)]
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        Write::write(&mut *self.0.lock().unwrap(), buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn record_flush() {
+    let buffer = SharedBuffer::default();
+
+    let transcript = Transcript::from_writer(buffer.clone());
+
+    for i in 0..3000 {
+        transcript.record(Direction::FromEngine, 1, &format!("info depth {}", i));
+    }
+
+    transcript.flush();
+
+    let text = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+
+    let entries = read_transcript(&text).unwrap();
+
+    assert_eq!(entries.len(), 3000);
+    assert_eq!(entries[2999].line, "info depth 2999");
+}
@@ -1,14 +1,28 @@
-use log::{debug, error, info, log_enabled, Level};
+use log::{debug, error, info, log_enabled, warn, Level};
 
-use envor::envor::env_true;
+use serde::{Deserialize, Serialize};
 
 use std::collections::HashMap;
-use std::process::Stdio;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
-use tokio::process::Command;
+use std::process::{ExitStatus, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::process::{Child, Command};
 use tokio::sync::*;
 
+use thiserror::Error;
+
+use shakmaty::fen::Fen;
+use shakmaty::uci::UciMove;
+use shakmaty::{CastlingMode, Chess, Position};
+
 use crate::analysis::*;
+use crate::book::PolyglotBook;
+use crate::cache::{AnalysisCache, CacheQuery};
+use crate::metrics::EngineMetrics;
+use crate::protocol::{self, EngineToGui, GoParams, GuiToEngine, Id, UciOption};
+use crate::tablebase::SyzygyTablebase;
+use crate::transcript::{Direction, Transcript, TranscriptSlot};
 
 /// enum of possible position specifiers
 #[derive(Debug)]
@@ -23,29 +37,48 @@ pub enum PosSpec {
 
 use PosSpec::*;
 
+/// search limit
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum SearchLimit {
+    /// search to depth
+    Depth(usize),
+    /// search this many nodes
+    Nodes(u64),
+    /// search this many milliseconds
+    Movetime(usize),
+}
+
 /// go command job
 #[derive(Debug)]
 pub struct GoJob {
     /// uci options as key value pairs
-    uci_options: HashMap<String, String>,
+    pub(crate) uci_options: HashMap<String, String>,
     /// position specifier
-    pos_spec: PosSpec,
+    pub(crate) pos_spec: PosSpec,
     /// position fen
-    pos_fen: Option<String>,
+    pub(crate) pos_fen: Option<String>,
     /// position moves
-    pos_moves: Option<String>,
+    pub(crate) pos_moves: Option<String>,
     /// go command options as key value pairs
-    go_options: HashMap<String, String>,
+    pub(crate) go_options: HashMap<String, String>,
     /// custom command
-    custom_command: Option<String>,
+    pub(crate) custom_command: Option<String>,
     /// ponder ( go option )
-    ponder: bool,
+    pub(crate) ponder: bool,
     /// ponderhit ( ponderhit uci commend )
     ponderhit: bool,
     /// pondermiss ( alias to awaited stop )
     pondermiss: bool,
     /// result sender
-    rtx: Option<oneshot::Sender<GoResult>>,
+    pub(crate) rtx: Option<oneshot::Sender<GoResult>>,
+    /// ponder control receiver, set for jobs started with go_ponder
+    pcrx: Option<oneshot::Receiver<PonderControl>>,
+    /// time the job entered the go job queue
+    queued: Option<std::time::Instant>,
+    /// tablebase covering the position, probed when the job is taken from the queue
+    tablebase: Option<std::sync::Arc<SyzygyTablebase>>,
+    /// sender of the analysis info of every info line of the search, set by go_analysis
+    itx: Option<mpsc::UnboundedSender<AnalysisInfo>>,
 }
 
 /// time control ( all values are in milliseconds )
@@ -61,11 +94,10 @@ pub struct Timecontrol {
     pub binc: usize,
 }
 
-/// implementation of time control
-impl Timecontrol {
-    /// create default time control
-    /// ( one minute thinking time for both sides, no increment )
-    pub fn default() -> Self {
+/// default time control
+/// ( one minute thinking time for both sides, no increment )
+impl Default for Timecontrol {
+    fn default() -> Self {
         Self {
             wtime: 60000,
             winc: 0,
@@ -75,6 +107,13 @@ impl Timecontrol {
     }
 }
 
+/// default go job
+impl Default for GoJob {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// go command job implementation
 impl GoJob {
     /// create new GoJob with defaults
@@ -86,10 +125,14 @@ impl GoJob {
             uci_options: HashMap::new(),
             go_options: HashMap::new(),
             rtx: None,
+            pcrx: None,
             custom_command: None,
             ponder: false,
             ponderhit: false,
             pondermiss: false,
+            queued: None,
+            tablebase: None,
+            itx: None,
         }
     }
 
@@ -111,65 +154,113 @@ impl GoJob {
         let mut commands: Vec<String> = vec![];
 
         if self.ponderhit {
-            commands.push(format!("{}", "ponderhit"));
+            commands.push(GuiToEngine::PonderHit.to_string());
 
             return commands;
         }
 
         if self.pondermiss {
-            commands.push(format!("{}", "stop"));
+            commands.push(GuiToEngine::Stop.to_string());
 
             return commands;
         }
 
         if let Some(command) = &self.custom_command {
-            commands.push(format!("{}", command));
+            commands.push(command.to_string());
 
             return commands;
         }
 
-        for (key, value) in &self.uci_options {
-            commands.push(format!("setoption name {} value {}", key, value));
-        }
-
-        let mut pos_command_moves = "".to_string();
+        commands.extend(self.option_commands());
 
-        if let Some(pos_moves) = &self.pos_moves {
-            pos_command_moves = format!(" moves {}", pos_moves)
-        }
+        let moves: Vec<String> = match &self.pos_moves {
+            Some(pos_moves) => pos_moves
+                .split_whitespace()
+                .map(|m| m.to_string())
+                .collect(),
+            _ => vec![],
+        };
 
-        let pos_command: Option<String> = match self.pos_spec {
-            Startpos => Some(format!("position startpos{}", pos_command_moves)),
-            Fen => {
-                let fen = match &self.pos_fen {
-                    Some(fen) => fen,
-                    _ => "",
-                };
-                Some(format!("position fen {}{}", fen, pos_command_moves))
-            }
+        let position = match self.pos_spec {
+            Startpos => Some(protocol::Position::Startpos),
+            Fen => Some(protocol::Position::Fen(
+                self.pos_fen.clone().unwrap_or_default(),
+            )),
             _ => None,
         };
 
-        if let Some(pos_command) = pos_command {
-            commands.push(pos_command);
+        if let Some(position) = position {
+            commands.push(GuiToEngine::Position { position, moves }.to_string());
         }
 
-        let mut go_command = "go".to_string();
+        let mut go_params = GoParams::default();
 
         for (key, value) in &self.go_options {
-            go_command = go_command + &format!(" {} {}", key, value);
+            if let Err(err) = go_params.set(key, value) {
+                warn!("ignoring go option {} {} : {}", key, value, err);
+            }
         }
 
-        if self.ponder {
-            go_command = go_command + &format!(" {}", "ponder");
-        }
+        go_params.ponder = self.ponder;
 
-        commands.push(go_command);
+        commands.push(GuiToEngine::Go(go_params).to_string());
 
         commands
     }
 
+    /// setoption commands of uci options
+    pub(crate) fn option_commands(&self) -> Vec<String> {
+        self.uci_options
+            .iter()
+            .map(|(key, value)| {
+                GuiToEngine::SetOption {
+                    name: key.to_string(),
+                    value: Some(value.to_string()),
+                }
+                .to_string()
+            })
+            .collect()
+    }
+
+    /// chess position of go job, None if position is not specified,
+    /// invalid or of a variant other than standard chess
+    pub fn position(&self) -> Option<Chess> {
+        if let Some(variant) = self.uci_options.get("UCI_Variant") {
+            if variant != "chess" {
+                return None;
+            }
+        }
+
+        let mut pos: Chess = match self.pos_spec {
+            Startpos => Chess::default(),
+            Fen => Fen::from_ascii(self.pos_fen.as_ref()?.as_bytes())
+                .ok()?
+                .into_position(CastlingMode::Standard)
+                .ok()?,
+            No => return None,
+        };
+
+        if let Some(pos_moves) = &self.pos_moves {
+            for uci in pos_moves.split_whitespace() {
+                let m = UciMove::from_ascii(uci.as_bytes())
+                    .ok()?
+                    .to_move(&pos)
+                    .ok()?;
+
+                pos.play_unchecked(m);
+            }
+        }
+
+        Some(pos)
+    }
+
+    /// true if go job is a search that awaits bestmove
+    pub(crate) fn is_search(&self) -> bool {
+        self.custom_command.is_none() && !self.ponder && !self.ponderhit && !self.pondermiss
+    }
+
     /// set ponder and return self
+    #[deprecated(note = "use UciEngine::go_ponder, a ponder job sent with go has no result")]
     pub fn set_ponder(mut self, value: bool) -> Self {
         self.ponder = value;
 
@@ -177,6 +268,7 @@ impl GoJob {
     }
 
     /// set ponder to true and return self
+    #[deprecated(note = "use UciEngine::go_ponder, a ponder job sent with go has no result")]
     pub fn ponder(mut self) -> Self {
         self.ponder = true;
 
@@ -184,6 +276,7 @@ impl GoJob {
     }
 
     /// set ponderhit and return self
+    #[deprecated(note = "use UciEngine::go_ponder and PonderHandle::hit")]
     pub fn ponderhit(mut self) -> Self {
         self.ponderhit = true;
 
@@ -191,6 +284,7 @@ impl GoJob {
     }
 
     /// set pondermiss and return self
+    #[deprecated(note = "use UciEngine::go_ponder and PonderHandle::miss")]
     pub fn pondermiss(mut self) -> Self {
         self.pondermiss = true;
 
@@ -260,6 +354,15 @@ impl GoJob {
         self
     }
 
+    /// set search limit as go option and return self
+    pub fn limit(self, limit: SearchLimit) -> Self {
+        match limit {
+            SearchLimit::Depth(depth) => self.go_opt("depth", depth),
+            SearchLimit::Nodes(nodes) => self.go_opt("nodes", nodes),
+            SearchLimit::Movetime(movetime) => self.go_opt("movetime", movetime),
+        }
+    }
+
     /// set time control and return self
     pub fn tc(mut self, tc: Timecontrol) -> Self {
         self.go_options
@@ -275,8 +378,27 @@ impl GoJob {
     }
 }
 
+/// where the move of a go result came from
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum MoveSource {
+    /// engine search
+    Engine,
+    /// opening book
+    Book,
+    /// tablebase, with win / draw / loss and distance to zeroing move
+    /// as described in TablebaseProbe
+    Tablebase {
+        /// win / draw / loss
+        wdl: i32,
+        /// distance to zeroing move
+        dtz: i32,
+    },
+    /// analysis cache, the result of an earlier engine search
+    Cache,
+}
+
 /// go command result
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct GoResult {
     /// best move if any
     pub bestmove: Option<String>,
@@ -284,13 +406,173 @@ pub struct GoResult {
     pub ponder: Option<String>,
     /// analysis info
     pub ai: AnalysisInfo,
+    /// move source
+    pub source: MoveSource,
+}
+
+/// go result serde
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GoResultSerde {
+    /// disposition
+    pub disposition: String,
+    /// best move if any
+    pub bestmove: Option<String>,
+    /// ponder if any
+    pub ponder: Option<String>,
+    /// analysis info
+    pub ai: AnalysisInfoSerde,
+    /// move source
+    pub source: MoveSource,
+}
+
+/// go result implementation
+impl GoResult {
+    /// to serde
+    pub fn to_serde(&self) -> GoResultSerde {
+        GoResultSerde {
+            disposition: "GoResult".to_string(),
+            bestmove: self.bestmove.clone(),
+            ponder: self.ponder.clone(),
+            ai: self.ai.to_serde(),
+            source: self.source,
+        }
+    }
+
+    /// from serde
+    pub fn from_serde(grs: GoResultSerde) -> Self {
+        Self {
+            bestmove: grs.bestmove,
+            ponder: grs.ponder,
+            ai: AnalysisInfo::from_serde(grs.ai),
+            source: grs.source,
+        }
+    }
+
+    /// to json
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string(&self.to_serde())
+    }
+}
+
+/// ponder control message, sent by ponder handle to the go job loop
+#[derive(Debug)]
+enum PonderControl {
+    /// ponderhit, result goes to sender
+    Hit(oneshot::Sender<GoResult>),
+    /// pondermiss, go job is searched after the ponder search is stopped
+    Miss(Box<GoJob>),
+}
+
+/// handle to a ponder search started with UciEngine::go_ponder,
+/// dropping it without hit or miss stops the ponder search
+#[derive(Debug)]
+pub struct PonderHandle {
+    pctx: oneshot::Sender<PonderControl>,
+}
+
+/// ponder handle implementation
+impl PonderHandle {
+    /// ponderhit ( opponent played the expected move ),
+    /// the ponder search goes on as a normal search
+    /// and the receiver resolves with its bestmove
+    pub fn hit(self) -> oneshot::Receiver<GoResult> {
+        let (rtx, rrx) = oneshot::channel();
+
+        let send_result = self.pctx.send(PonderControl::Hit(rtx));
+
+        if log_enabled!(Level::Debug) {
+            debug!("send ponderhit result {:?}", send_result.is_ok());
+        }
+
+        rrx
+    }
+
+    /// pondermiss ( opponent played a different move ),
+    /// the ponder search is stopped and its bestmove discarded,
+    /// then go job is searched and the receiver resolves with its bestmove
+    pub fn miss(self, go_job: GoJob) -> oneshot::Receiver<GoResult> {
+        let mut go_job = go_job;
+
+        let (rtx, rrx) = oneshot::channel();
+
+        go_job.rtx = Some(rtx);
+
+        let send_result = self.pctx.send(PonderControl::Miss(Box::new(go_job)));
+
+        if log_enabled!(Level::Debug) {
+            debug!("send pondermiss result {:?}", send_result.is_ok());
+        }
+
+        rrx
+    }
+}
+
+/// engine identity and options announced in reply to uci
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct EngineId {
+    /// engine name
+    pub name: Option<String>,
+    /// engine author
+    pub author: Option<String>,
+    /// options
+    pub options: Vec<UciOption>,
+}
+
+/// EngineError captures possible reasons of a search failing because the engine went away
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum EngineError {
+    #[error("engine terminated ( {0} )")]
+    Terminated(String),
+    #[error("engine killed for exceeding memory limit, rss {rss} > {limit} bytes")]
+    MemoryLimit { rss: u64, limit: u64 },
+    #[error("engine killed for exceeding cpu time limit")]
+    CpuTimeLimit,
+}
+
+/// outcome of engine shutdown
+#[derive(Debug)]
+pub struct ShutdownStatus {
+    /// exit status if the process exited and could be waited on
+    pub exit_status: Option<ExitStatus>,
+    /// true if the engine did not quit in time and had to be killed
+    pub killed: bool,
 }
 
 /// uci engine
+///
+/// the engine process is killed when the last handle is dropped
 pub struct UciEngine {
     gtx: mpsc::UnboundedSender<GoJob>,
+    ctx: CommandSender,
+    ktx: std::sync::Mutex<Option<oneshot::Sender<()>>>,
+    erx: watch::Receiver<Option<ExitStatus>>,
+    orx: watch::Receiver<bool>,
+    is_process: bool,
     pub ai: std::sync::Arc<std::sync::Mutex<AnalysisInfo>>,
     pub atx: std::sync::Arc<broadcast::Sender<AnalysisInfo>>,
+    ltx: std::sync::Arc<broadcast::Sender<String>>,
+    stx: mpsc::UnboundedSender<mpsc::UnboundedSender<String>>,
+    book: std::sync::Mutex<Option<std::sync::Arc<PolyglotBook>>>,
+    tablebase: std::sync::Mutex<Option<std::sync::Arc<SyzygyTablebase>>>,
+    cache: std::sync::Mutex<Option<(std::sync::Arc<AnalysisCache>, String)>>,
+    transcript: std::sync::Arc<TranscriptSlot>,
+    stats: std::sync::Arc<std::sync::Mutex<ParseStats>>,
+    pub metrics: std::sync::Arc<EngineMetrics>,
+    pid: Option<u32>,
+    process_group: std::sync::atomic::AtomicBool,
+    kill_reason: std::sync::Mutex<Option<EngineError>>,
+}
+
+/// debug uci engine
+impl std::fmt::Debug for UciEngine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "UciEngine [process {} , terminated {}]",
+            self.is_process,
+            self.is_terminated()
+        )
+    }
 }
 
 /// uci engine implementation
@@ -299,16 +581,46 @@ impl UciEngine {
     pub fn new<T>(path: T) -> std::sync::Arc<UciEngine>
     where
         T: core::fmt::Display,
+    {
+        Self::new_with_args::<T, &str>(path, &[])
+    }
+
+    /// create new uci engine, passing command line arguments to the engine process
+    pub fn new_with_args<T, A>(path: T, args: &[A]) -> std::sync::Arc<UciEngine>
+    where
+        T: core::fmt::Display,
+        A: AsRef<std::ffi::OsStr>,
     {
         // you can use anything that can be converted to string as path
         let path = path.to_string();
 
+        let mut command = Command::new(path.as_str());
+
+        command.args(args);
+
+        Self::spawn(command).expect("failed to spawn engine")
+    }
+
+    /// create new uci engine from command, its stdin and stdout are piped to the engine
+    pub fn spawn(command: Command) -> std::io::Result<std::sync::Arc<UciEngine>> {
+        Self::spawn_with_config(command, ParserConfig::implicit())
+    }
+
+    /// create new uci engine from command, parsing engine output with parser configuration
+    pub fn spawn_with_config(
+        command: Command,
+        parser_config: ParserConfig,
+    ) -> std::io::Result<std::sync::Arc<UciEngine>> {
+        let mut command = command;
+
+        let name = format!("{:?}", command.as_std().get_program());
+
         // spawn engine process
-        let mut child = Command::new(path.as_str())
+        let mut child = command
             .stdout(Stdio::piped())
             .stdin(Stdio::piped())
-            .spawn()
-            .expect("failed to spawn engine");
+            .kill_on_drop(true)
+            .spawn()?;
 
         // obtain process stdout
         let stdout = child
@@ -322,43 +634,170 @@ impl UciEngine {
             .take()
             .expect("child did not have a handle to stdin");
 
-        // stdout reader
-        let reader = BufReader::new(stdout).lines();
+        Ok(Self::start(stdout, stdin, Some(child), name, parser_config))
+    }
+
+    /// create new uci engine connected to tcp address
+    pub async fn connect_tcp<A>(addr: A) -> std::io::Result<std::sync::Arc<UciEngine>>
+    where
+        A: tokio::net::ToSocketAddrs,
+    {
+        let stream = tokio::net::TcpStream::connect(addr).await?;
+
+        let name = format!("tcp {:?}", stream.peer_addr()?);
+
+        let (reader, writer) = stream.into_split();
+
+        Ok(Self::start(
+            reader,
+            writer,
+            None,
+            name,
+            ParserConfig::implicit(),
+        ))
+    }
+
+    /// create new uci engine connected to unix domain socket
+    #[cfg(unix)]
+    pub async fn connect_unix<P>(path: P) -> std::io::Result<std::sync::Arc<UciEngine>>
+    where
+        P: AsRef<std::path::Path>,
+    {
+        let name = format!("unix {:?}", path.as_ref());
+
+        let stream = tokio::net::UnixStream::connect(path).await?;
+
+        let (reader, writer) = stream.into_split();
+
+        Ok(Self::start(
+            reader,
+            writer,
+            None,
+            name,
+            ParserConfig::implicit(),
+        ))
+    }
+
+    /// create new uci engine reading engine output from reader and writing commands to writer
+    ///
+    /// there is no process to wait for, the engine terminates when reader reaches end of input,
+    /// kill closes the connection
+    pub fn from_io<R, W>(reader: R, writer: W) -> std::sync::Arc<UciEngine>
+    where
+        R: AsyncRead + Unpin + Send + 'static,
+        W: AsyncWrite + Unpin + Send + 'static,
+    {
+        Self::from_io_with_config(reader, writer, ParserConfig::implicit())
+    }
+
+    /// create new uci engine on reader and writer, parsing engine output with parser configuration
+    pub fn from_io_with_config<R, W>(
+        reader: R,
+        writer: W,
+        parser_config: ParserConfig,
+    ) -> std::sync::Arc<UciEngine>
+    where
+        R: AsyncRead + Unpin + Send + 'static,
+        W: AsyncWrite + Unpin + Send + 'static,
+    {
+        Self::start(reader, writer, None, "io".to_string(), parser_config)
+    }
+
+    /// start engine tasks on transport, child is the engine process if any
+    fn start<R, W>(
+        reader: R,
+        writer: W,
+        child: Option<Child>,
+        name: String,
+        parser_config: ParserConfig,
+    ) -> std::sync::Arc<UciEngine>
+    where
+        R: AsyncRead + Unpin + Send + 'static,
+        W: AsyncWrite + Unpin + Send + 'static,
+    {
+        // engine output reader
+        let reader = BufReader::new(reader).lines();
 
         // channel for receiving bestmove result
         let (tx, rx) = mpsc::unbounded_channel::<String>();
 
-        tokio::spawn(async move {
-            // run engine process and wait for exit code
-            let status = child
-                .wait()
-                .await
-                .expect("engine process encountered an error");
-
-            if log_enabled!(Level::Info) {
-                info!("engine process exit status : {}", status);
-            }
-        });
+        // channel for requesting kill, also fires when the engine handle is dropped
+        let (ktx, krx) = oneshot::channel::<()>();
+
+        // channel for publishing exit status
+        let (etx, erx) = watch::channel::<Option<ExitStatus>>(None);
+
+        // channel for publishing that engine output is closed
+        let (otx, orx) = watch::channel::<bool>(false);
+
+        let is_process = child.is_some();
+
+        let pid = child.as_ref().and_then(|child| child.id());
 
         let ai = std::sync::Arc::new(std::sync::Mutex::new(AnalysisInfo::new()));
 
         let ai_clone = ai.clone();
 
+        // number of searches started, the reader starts over with a new analysis info
+        // when it changes
+        let searches = std::sync::Arc::new(AtomicU64::new(0));
+
+        let searches_clone = searches.clone();
+
         let (atx, _) = broadcast::channel::<AnalysisInfo>(20);
 
         let atx = std::sync::Arc::new(atx);
 
         let atx_clone = atx.clone();
 
-        tokio::spawn(async move {
+        let (ltx, _) = broadcast::channel::<String>(1024);
+
+        let ltx = std::sync::Arc::new(ltx);
+
+        let ltx_clone = ltx.clone();
+
+        // channel for registering lossless line sinks with the reader
+        let (stx, srx) = mpsc::unbounded_channel::<mpsc::UnboundedSender<String>>();
+
+        // channel for registering the analysis info sender of a search with the reader
+        let (jtx, jrx) = mpsc::unbounded_channel::<SearchSink>();
+
+        let otx = std::sync::Arc::new(otx);
+
+        let otx_clone = otx.clone();
+
+        let transcript = std::sync::Arc::new(TranscriptSlot::default());
+
+        let transcript_clone = transcript.clone();
+
+        let stats = std::sync::Arc::new(std::sync::Mutex::new(ParseStats::default()));
+
+        let stats_clone = stats.clone();
+
+        let metrics = std::sync::Arc::new(EngineMetrics::new());
+
+        let metrics_clone = metrics.clone();
+
+        let reader_handle = tokio::spawn(async move {
             let mut reader = reader;
             let ai = ai_clone;
             let atx = atx_clone;
-
-            let test_parse_info = env_true("TEST_PARSE_INFO");
+            let ltx = ltx_clone;
+            let mut srx = srx;
+            let mut sinks: Vec<mpsc::UnboundedSender<String>> = vec![];
+            let mut jrx = jrx;
+            let mut search_sink: Option<SearchSink> = None;
+            let transcript = transcript_clone;
+            let searches = searches_clone;
+
+            // lines are parsed into a reader owned analysis info, the shared one is
+            // only updated on pv and bestmove lines
+            let mut local_ai = AnalysisInfo::new();
+            let mut search: u64 = 0;
+
+            let stats = stats_clone;
+            let metrics = metrics_clone;
             let mut num_lines: usize = 0;
-            let mut ok_lines: usize = 0;
-            let mut failed_lines: usize = 0;
 
             loop {
                 match reader.next_line().await {
@@ -366,48 +805,85 @@ impl UciEngine {
                         if let Some(line) = line_opt {
                             num_lines += 1;
 
+                            transcript.record(Direction::FromEngine, &line);
+
+                            while let Ok(sink) = srx.try_recv() {
+                                sinks.push(sink);
+                            }
+
+                            // sinks whose receiver is gone are dropped
+                            if !sinks.is_empty() {
+                                sinks.retain(|sink| sink.send(line.clone()).is_ok());
+                            }
+
+                            // no subscriber is not an error, but then there is nothing to send
+                            if ltx.receiver_count() > 0 {
+                                let _ = ltx.send(line.clone());
+                            }
+
                             if log_enabled!(Level::Debug) {
                                 debug!("uci engine out ( {} ) : {}", num_lines, line);
                             }
 
-                            let mut is_bestmove = line.len() >= 8;
+                            let is_bestmove = line.starts_with("bestmove");
+
+                            let current_search = searches.load(Ordering::Relaxed);
+
+                            if current_search != search {
+                                search = current_search;
+
+                                local_ai = AnalysisInfo::new();
+                            }
+
+                            let parse_result = local_ai.parse_with(&parser_config, &line);
 
                             if is_bestmove {
-                                is_bestmove = &line[0..8] == "bestmove";
+                                local_ai.done = true;
                             }
 
-                            {
-                                let mut ai = ai.lock().unwrap();
+                            while let Ok(sink) = jrx.try_recv() {
+                                search_sink = Some(sink);
+                            }
 
-                                let parse_result = ai.parse(line.to_owned());
+                            // the analysis of a search only goes to the sink of that search
+                            if let Some((sink_search, sink)) = &search_sink {
+                                if *sink_search == search {
+                                    if parse_result.is_ok() {
+                                        let _ = sink.send(local_ai);
+                                    }
 
-                                if is_bestmove {
-                                    ai.done = true;
+                                    if is_bestmove {
+                                        search_sink = None;
+                                    }
                                 }
+                            }
 
-                                debug!("parse result {:?} , ai {:?}", parse_result, ai);
+                            debug!("parse result {:?} , ai {:?}", parse_result, local_ai);
 
-                                if parse_result.is_ok() {
-                                    ok_lines += 1;
+                            if is_bestmove || (parse_result.is_ok() && line.contains(" pv ")) {
+                                *ai.lock().unwrap() = local_ai;
+                            }
 
-                                    let send_result = atx.send(*ai);
+                            if parse_result.is_ok() {
+                                // no subscriber is not an error, but then there is nothing to send
+                                if atx.receiver_count() > 0 {
+                                    let send_result = atx.send(local_ai);
 
                                     debug!("send ai result {:?}", send_result);
-                                } else {
-                                    failed_lines += 1;
+                                }
+                            } else {
+                                metrics.record_parse_failure();
 
-                                    println!(
+                                if log_enabled!(Level::Warn) {
+                                    warn!(
                                         "parsing failed on {} with error {:?}",
                                         line, parse_result
                                     );
                                 }
+                            }
 
-                                if test_parse_info {
-                                    println!(
-                                        "read {} , parsed ok {} , failed {}",
-                                        num_lines, ok_lines, failed_lines
-                                    );
-                                }
+                            if parser_config.collect_stats {
+                                stats.lock().unwrap().record(&parse_result);
                             }
 
                             if is_bestmove {
@@ -438,6 +914,101 @@ impl UciEngine {
             if log_enabled!(Level::Debug) {
                 debug!("engine read terminated");
             }
+
+            let _ = otx_clone.send(true);
+        });
+
+        // channel for writing commands to engine input, tagged with their go job id
+        let (ctx, crx) = mpsc::unbounded_channel::<(u64, String)>();
+
+        let transcript_clone = transcript.clone();
+
+        let writer_handle = tokio::spawn(async move {
+            let mut writer = writer;
+            let mut crx = crx;
+            let transcript = transcript_clone;
+
+            while let Some((job, command)) = crx.recv().await {
+                transcript.record_job(Direction::ToEngine, job, &command);
+
+                let command = format!("{}\n", command);
+
+                if log_enabled!(Level::Debug) {
+                    debug!("issuing engine command : {}", command);
+                }
+
+                let write = async {
+                    writer.write_all(command.as_bytes()).await?;
+
+                    writer.flush().await
+                };
+
+                #[cfg(feature = "tracing")]
+                let write = tracing::Instrument::instrument(
+                    write,
+                    tracing::debug_span!("command_write", command = command.trim_end()),
+                );
+
+                let write_result = write.await;
+
+                if log_enabled!(Level::Debug) {
+                    debug!("write result {:?}", write_result);
+                }
+            }
+        });
+
+        tokio::spawn(async move {
+            let mut child = match child {
+                Some(child) => child,
+                _ => {
+                    // no process to kill, kill closes the connection instead
+                    let _ = krx.await;
+
+                    if log_enabled!(Level::Info) {
+                        info!("closing engine connection");
+                    }
+
+                    reader_handle.abort();
+                    writer_handle.abort();
+
+                    let _ = otx.send(true);
+
+                    return;
+                }
+            };
+
+            // run engine process and wait for exit code, unless kill is requested first
+            let wait_result = tokio::select! {
+                wait_result = child.wait() => wait_result,
+                _ = krx => {
+                    if log_enabled!(Level::Info) {
+                        info!("killing engine process");
+                    }
+
+                    let kill_result = child.kill().await;
+
+                    if log_enabled!(Level::Debug) {
+                        debug!("kill result {:?}", kill_result);
+                    }
+
+                    child.wait().await
+                }
+            };
+
+            match wait_result {
+                Ok(status) => {
+                    if log_enabled!(Level::Info) {
+                        info!("engine process exit status : {}", status);
+                    }
+
+                    let _ = etx.send(Some(status));
+                }
+                Err(err) => {
+                    if log_enabled!(Level::Error) {
+                        error!("engine process encountered an error {:?}", err);
+                    }
+                }
+            }
         });
 
         // channel for sending go jobs
@@ -445,95 +1016,524 @@ impl UciEngine {
 
         let ai_clone = ai.clone();
 
+        let searches_clone = searches.clone();
+
+        let ctx_clone = ctx.clone();
+
+        let transcript_clone = transcript.clone();
+
+        let metrics_clone = metrics.clone();
+
         tokio::spawn(async move {
-            let mut stdin = stdin;
+            let jtx = jtx;
+            let ctx = ctx_clone;
             let mut grx = grx;
             let mut rx = rx;
             let ai = ai_clone;
-
-            while let Some(go_job) = grx.recv().await {
+            let transcript = transcript_clone;
+            let metrics = metrics_clone;
+            let searches = searches_clone;
+            let mut job_id: u64 = 0;
+            // searches started by custom go commands whose bestmove was not received yet,
+            // these bestmoves belong to no go job
+            let mut unclaimed: usize = 0;
+
+            while let Some(mut go_job) = grx.recv().await {
                 if log_enabled!(Level::Debug) {
                     debug!("received go job {:?}", go_job);
                 }
 
-                for command in go_job.to_commands() {
-                    let command = format!("{}\n", command);
+                job_id += 1;
+
+                transcript.set_job(job_id);
 
+                if let Some(go_result) = probe_tablebase(&mut go_job).await {
                     if log_enabled!(Level::Debug) {
-                        debug!("issuing engine command : {}", command);
+                        debug!("go job answered from tablebase {:?}", go_result);
                     }
 
-                    let write_result = stdin.write_all(command.as_bytes()).await;
+                    let input = JobInput {
+                        ctx: &ctx,
+                        job: job_id,
+                        searches: &searches,
+                        jtx: &jtx,
+                    };
 
-                    if log_enabled!(Level::Debug) {
-                        debug!("write result {:?}", write_result);
+                    for command in go_job.option_commands() {
+                        input.send(command);
                     }
-                }
-
-                if go_job.custom_command.is_none() && (!go_job.ponder) {
-                    {
-                        let mut ai = ai.lock().unwrap();
 
-                        *ai = AnalysisInfo::new();
+                    if let Some(rtx) = go_job.rtx.take() {
+                        let _ = rtx.send(go_result);
                     }
 
-                    let recv_result = rx.recv().await.unwrap();
+                    continue;
+                }
 
-                    if log_enabled!(Level::Debug) {
-                        debug!("recv result {:?}", recv_result);
+                match &go_job.custom_command {
+                    Some(command) => {
+                        if command.split_whitespace().next() == Some("go") {
+                            unclaimed += 1;
+                        }
+                    }
+                    _ => {
+                        // wait for the searches started by custom go commands to finish
+                        // before the bestmove of this job can be received
+                        while unclaimed > 0 {
+                            match rx.recv().await {
+                                Some(line) => {
+                                    if log_enabled!(Level::Debug) {
+                                        debug!("discarding unclaimed {}", line);
+                                    }
+
+                                    unclaimed -= 1;
+                                }
+                                _ => break,
+                            }
+                        }
                     }
+                }
 
-                    let parts: Vec<&str> = recv_result.split(" ").collect();
+                let queue_wait = go_job
+                    .queued
+                    .map(|queued| queued.elapsed())
+                    .unwrap_or_default();
 
-                    let send_ai: AnalysisInfo;
+                let is_search = go_job.is_search() || go_job.pcrx.is_some();
 
-                    {
-                        let ai = ai.lock().unwrap();
+                let start = std::time::Instant::now();
 
-                        send_ai = *ai;
-                    }
-
-                    let mut go_result = GoResult {
-                        bestmove: None,
-                        ponder: None,
-                        ai: send_ai,
+                let run = async {
+                    let input = JobInput {
+                        ctx: &ctx,
+                        job: job_id,
+                        searches: &searches,
+                        jtx: &jtx,
                     };
 
-                    if parts.len() > 1 {
-                        go_result.bestmove = Some(parts[1].to_string());
+                    if go_job.pcrx.is_some() {
+                        run_ponder_job(go_job, &input, &mut rx, &ai).await
+                    } else {
+                        run_go_job(go_job, &input, &mut rx, &ai).await
                     }
+                };
 
-                    if parts.len() > 3 {
-                        go_result.ponder = Some(parts[3].to_string());
-                    }
+                #[cfg(feature = "tracing")]
+                let run = tracing::Instrument::instrument(
+                    run,
+                    tracing::info_span!(
+                        "go_job",
+                        job = job_id,
+                        queue_wait_ms = queue_wait.as_millis() as u64
+                    ),
+                );
+
+                let engine_alive = run.await;
 
-                    let send_result = go_job.rtx.unwrap().send(go_result);
+                if is_search && engine_alive {
+                    let nodes = ai.lock().unwrap().nodes;
 
+                    metrics.record_job(queue_wait, start.elapsed(), nodes);
+                }
+
+                if !engine_alive {
                     if log_enabled!(Level::Debug) {
-                        debug!("result of send go result {:?}", send_result);
+                        debug!("engine output closed, dropping go job");
                     }
+
+                    break;
                 }
             }
         });
 
         if log_enabled!(Level::Info) {
-            info!("spawned uci engine : {}", path);
+            info!("started uci engine : {}", name);
         }
 
         std::sync::Arc::new(UciEngine {
-            gtx: gtx,
-            ai: ai,
-            atx: atx,
+            gtx,
+            ctx,
+            ktx: std::sync::Mutex::new(Some(ktx)),
+            erx,
+            orx,
+            is_process,
+            ai,
+            atx,
+            ltx,
+            stx,
+            book: std::sync::Mutex::new(None),
+            tablebase: std::sync::Mutex::new(None),
+            cache: std::sync::Mutex::new(None),
+            transcript,
+            stats,
+            metrics,
+            pid,
+            process_group: std::sync::atomic::AtomicBool::new(false),
+            kill_reason: std::sync::Mutex::new(None),
         })
     }
 
-    /// get analysis info
+    /// shut down engine gracefully, first by sending stop and quit,
+    /// then by killing the process if it did not exit within timeout
+    /// ( engines without process are expected to close their output on quit )
+    pub async fn shutdown(&self, timeout: Duration) -> ShutdownStatus {
+        // stop and quit bypass the go job queue, so that they reach a busy engine
+        let job = self.transcript.job();
+
+        let _ = self.ctx.send((job, GuiToEngine::Stop.to_string()));
+        let _ = self.ctx.send((job, GuiToEngine::Quit.to_string()));
+
+        if self.wait_terminated(timeout).await {
+            self.flush_transcript(timeout).await;
+
+            return ShutdownStatus {
+                exit_status: self.exit_status(),
+                killed: false,
+            };
+        }
+
+        if log_enabled!(Level::Info) {
+            info!("engine did not quit within {:?}", timeout);
+        }
+
+        self.kill();
+
+        self.wait_terminated(timeout).await;
+
+        self.flush_transcript(timeout).await;
+
+        ShutdownStatus {
+            exit_status: self.exit_status(),
+            killed: true,
+        }
+    }
+
+    /// wait for the last engine lines to be read and the transcript to be written
+    async fn flush_transcript(&self, timeout: Duration) {
+        let _ = tokio::time::timeout(timeout, self.wait_output_closed()).await;
+
+        self.transcript.flush().await;
+    }
+
+    /// kill engine process without waiting for it to exit,
+    /// closes the connection of engines without process
+    pub fn kill(&self) {
+        #[cfg(unix)]
+        if let Some(pid) = self.pid {
+            if self
+                .process_group
+                .load(std::sync::atomic::Ordering::Relaxed)
+                && !self.is_terminated()
+            {
+                // the engine is the group leader, this also kills processes it started
+                unsafe {
+                    libc::kill(-(pid as i32), libc::SIGKILL);
+                }
+            }
+        }
+
+        if let Some(ktx) = self.ktx.lock().unwrap().take() {
+            let _ = ktx.send(());
+        }
+    }
+
+    /// kill engine, reporting reason as error of the search in flight
+    pub fn kill_with(&self, reason: EngineError) {
+        *self.kill_reason.lock().unwrap() = Some(reason);
+
+        self.kill();
+    }
+
+    /// process id of engine, None if there is no process
+    pub fn pid(&self) -> Option<u32> {
+        self.pid
+    }
+
+    /// set whether the engine runs in its own process group
+    pub(crate) fn set_process_group(&self, value: bool) {
+        self.process_group
+            .store(value, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    /// reason of engine termination, after the engine terminated
+    async fn termination_error(&self) -> EngineError {
+        if let Some(reason) = self.kill_reason.lock().unwrap().clone() {
+            return reason;
+        }
+
+        // output closes before the exit status is known
+        self.wait_terminated(Duration::from_millis(1000)).await;
+
+        match self.exit_status() {
+            #[cfg(unix)]
+            Some(status)
+                if std::os::unix::process::ExitStatusExt::signal(&status)
+                    == Some(libc::SIGXCPU) =>
+            {
+                EngineError::CpuTimeLimit
+            }
+            Some(status) => EngineError::Terminated(status.to_string()),
+            _ => EngineError::Terminated("output closed".to_string()),
+        }
+    }
+
+    /// issue go command and wait for its result, error with the reason
+    /// if the engine terminates before returning a result
+    pub async fn go_checked(&self, go_job: GoJob) -> Result<GoResult, EngineError> {
+        match self.go(go_job).await {
+            Ok(go_result) => Ok(go_result),
+            _ => Err(self.termination_error().await),
+        }
+    }
+
+    /// exit status of engine process, None if still running or there is no process
+    pub fn exit_status(&self) -> Option<ExitStatus> {
+        *self.erx.borrow()
+    }
+
+    /// true if engine process exited, or for engines without process, output was closed
+    pub fn is_terminated(&self) -> bool {
+        if self.is_process {
+            self.erx.borrow().is_some()
+        } else {
+            *self.orx.borrow()
+        }
+    }
+
+    /// wait for engine to terminate at most timeout long, true if it terminated
+    async fn wait_terminated(&self, timeout: Duration) -> bool {
+        let mut erx = self.erx.clone();
+        let mut orx = self.orx.clone();
+
+        // sender gone means the process could not be waited on
+        let wait = async move {
+            if self.is_process {
+                erx.wait_for(|status| status.is_some()).await.is_ok()
+            } else {
+                orx.wait_for(|closed| *closed).await.is_ok()
+            }
+        };
+
+        tokio::time::timeout(timeout, wait).await.unwrap_or(false)
+    }
+
+    /// parse statistics of engine output, collected if enabled in the parser configuration
+    pub fn parse_stats(&self) -> ParseStats {
+        self.stats.lock().unwrap().clone()
+    }
+
+    /// wait until engine output is closed ( engine exited or connection closed )
+    pub async fn wait_output_closed(&self) {
+        let mut orx = self.orx.clone();
+
+        let _ = orx.wait_for(|closed| *closed).await;
+    }
+
+    /// send uci and collect id and options until uciok, None if the engine
+    /// does not answer within timeout
+    pub async fn identify(&self, timeout: Duration) -> Option<EngineId> {
+        let mut lrx = self.subscribe_lines();
+
+        self.go(GoJob::new().custom(GuiToEngine::Uci));
+
+        let mut engine_id = EngineId::default();
+
+        let collect = async {
+            loop {
+                let line = match lrx.recv().await {
+                    Ok(line) => line,
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                };
+
+                match line.parse::<EngineToGui>() {
+                    Ok(EngineToGui::Id(Id::Name(name))) => engine_id.name = Some(name),
+                    Ok(EngineToGui::Id(Id::Author(author))) => engine_id.author = Some(author),
+                    Ok(EngineToGui::Option(option)) => engine_id.options.push(option),
+                    Ok(EngineToGui::UciOk) => return Some(engine_id),
+                    _ => {}
+                }
+            }
+        };
+
+        tokio::select! {
+            engine_id = tokio::time::timeout(timeout, collect) => engine_id.ok().flatten(),
+            _ = self.wait_output_closed() => None,
+        }
+    }
+
+    /// send isready after the queued go jobs and wait for readyok, false if the engine
+    /// does not answer within timeout
+    pub async fn is_ready(&self, timeout: Duration) -> bool {
+        let mut lrx = self.line_sink();
+
+        self.go(GoJob::new().custom(GuiToEngine::IsReady));
+
+        let wait = async {
+            while let Some(line) = lrx.recv().await {
+                if let Ok(EngineToGui::ReadyOk) = line.parse::<EngineToGui>() {
+                    return true;
+                }
+            }
+
+            false
+        };
+
+        tokio::select! {
+            ready = tokio::time::timeout(timeout, wait) => ready.unwrap_or(false),
+            _ = self.wait_output_closed() => false,
+        }
+    }
+
+    /// send stop bypassing the go job queue, a running search sends bestmove
+    /// and resolves its go job
+    pub fn stop(&self) {
+        let _ = self
+            .ctx
+            .send((self.transcript.job(), GuiToEngine::Stop.to_string()));
+    }
+
+    /// subscribe to raw engine output lines, only lines read after subscribing are received,
+    /// lines are skipped if the receiver lags behind by more than 1024 lines
+    pub fn subscribe_lines(&self) -> broadcast::Receiver<String> {
+        self.ltx.subscribe()
+    }
+
+    /// subscribe to raw engine output lines without ever skipping lines, only lines read
+    /// after subscribing are received, lines not received yet are queued without limit
+    pub fn line_sink(&self) -> mpsc::UnboundedReceiver<String> {
+        let (tx, rx) = mpsc::unbounded_channel::<String>();
+
+        let _ = self.stx.send(tx);
+
+        rx
+    }
+
+    /// get analysis info, as of the last pv or bestmove line
     pub fn get_ai(&self) -> AnalysisInfo {
         let ai = self.ai.lock().unwrap();
 
         *ai
     }
 
+    /// start ponder search and return a handle for resolving it
+    /// on ponderhit or pondermiss, the go job is sent with ponder set
+    ///
+    /// ### Example
+    /// ```no_run
+    /// use uciengine::uciengine::*;
+    ///
+    /// # async fn example() {
+    /// let engine = UciEngine::new("./stockfish12");
+    ///
+    /// let ponder_handle = engine.go_ponder(GoJob::new().pos_startpos().pos_moves("e2e4 e7e5"));
+    ///
+    /// // opponent played e7e5, as expected
+    /// let go_result = ponder_handle.hit().await;
+    /// # }
+    /// ```
+    pub fn go_ponder(&self, go_job: GoJob) -> PonderHandle {
+        let mut go_job = go_job;
+
+        go_job.ponder = true;
+
+        let (pctx, pcrx) = oneshot::channel::<PonderControl>();
+
+        go_job.pcrx = Some(pcrx);
+        go_job.queued = Some(std::time::Instant::now());
+
+        let send_result = self.gtx.send(go_job);
+
+        if log_enabled!(Level::Debug) {
+            debug!("send ponder job result {:?}", send_result);
+        }
+
+        PonderHandle { pctx }
+    }
+
+    /// set opening book, searches of positions found in the book
+    /// are answered from the book without querying the engine
+    /// ( uci options of such go jobs are still sent to the engine ),
+    /// None removes the book
+    pub fn set_book(&self, book: Option<PolyglotBook>) {
+        *self.book.lock().unwrap() = book.map(std::sync::Arc::new);
+    }
+
+    /// set syzygy tablebase, searches of positions covered by the tablebase
+    /// are answered with the tablebase optimal move without querying the engine
+    /// ( uci options of such go jobs are still sent to the engine ),
+    /// None removes the tablebase
+    pub fn set_tablebase(&self, tablebase: Option<SyzygyTablebase>) {
+        *self.tablebase.lock().unwrap() = tablebase.map(std::sync::Arc::new);
+    }
+
+    /// set analysis cache, searches with a result deep enough in the cache
+    /// are answered from the cache, results of other searches are stored in it,
+    /// identity ( e.g. engine name and version ) is part of the cache key,
+    /// None removes the cache
+    pub fn set_cache<T: core::fmt::Display>(
+        &self,
+        cache: Option<std::sync::Arc<AnalysisCache>>,
+        identity: T,
+    ) {
+        *self.cache.lock().unwrap() = cache.map(|cache| (cache, identity.to_string()));
+    }
+
+    /// record every line sent to and received from the engine as jsonl to path,
+    /// together with timestamps and go job ids, an existing file is truncated,
+    /// shutdown returns once the recorded lines are written
+    pub fn record_transcript<P: AsRef<std::path::Path>>(&self, path: P) -> std::io::Result<()> {
+        self.transcript.set(Some(Transcript::create(path)?));
+
+        Ok(())
+    }
+
+    /// set transcript recorder, None stops recording
+    pub fn set_transcript(&self, transcript: Option<Transcript>) {
+        self.transcript.set(transcript);
+    }
+
+    /// send uci options of go job answered without querying the engine,
+    /// so that later searches run with them
+    fn send_options(&self, go_job: &GoJob) {
+        for command in go_job.option_commands() {
+            self.go(GoJob::new().custom(command));
+        }
+    }
+
+    /// answer go job from book, None if the engine has to be queried
+    fn prequery_go_result(&self, go_job: &GoJob) -> Option<GoResult> {
+        let book = self.book.lock().unwrap().clone()?;
+
+        if !go_job.is_search() {
+            return None;
+        }
+
+        let bestmove = book.probe(&go_job.position()?)?;
+
+        let mut ai = AnalysisInfo::new();
+
+        ai.done = true;
+
+        Some(GoResult {
+            bestmove: Some(bestmove),
+            ponder: None,
+            ai,
+            source: MoveSource::Book,
+        })
+    }
+
+    /// tablebase covering the position of go job, None if there is none
+    fn covering_tablebase(&self, go_job: &GoJob) -> Option<std::sync::Arc<SyzygyTablebase>> {
+        let tablebase = self.tablebase.lock().unwrap().clone()?;
+
+        if !go_job.is_search() || !tablebase.covers(&go_job.position()?) {
+            return None;
+        }
+
+        Some(tablebase)
+    }
+
     /// issue go command
     pub fn go(&self, go_job: GoJob) -> oneshot::Receiver<GoResult> {
         let mut go_job = go_job;
@@ -541,7 +1541,70 @@ impl UciEngine {
         let (rtx, rrx): (oneshot::Sender<GoResult>, oneshot::Receiver<GoResult>) =
             oneshot::channel();
 
+        // probing reads table files, so it is done off the runtime when the job
+        // is taken from the queue, tablebase results are not cached
+        go_job.tablebase = self.covering_tablebase(&go_job);
+
+        if go_job.tablebase.is_none() {
+            if let Some(go_result) = self.prequery_go_result(&go_job) {
+                if log_enabled!(Level::Debug) {
+                    debug!("go job answered without engine {:?}", go_result);
+                }
+
+                self.send_options(&go_job);
+
+                let _ = rtx.send(go_result);
+
+                return rrx;
+            }
+        }
+
+        let mut rtx = rtx;
+
+        let cache = match go_job.tablebase {
+            Some(_) => None,
+            _ => self.cache.lock().unwrap().clone(),
+        };
+
+        if let Some((cache, query)) = cache.and_then(|(cache, identity)| {
+            CacheQuery::new(identity, &go_job).map(|query| (cache, query))
+        }) {
+            if let Some(go_result) = cache.probe(&query) {
+                if log_enabled!(Level::Debug) {
+                    debug!("go job answered from cache {:?}", go_result);
+                }
+
+                self.send_options(&go_job);
+
+                let _ = rtx.send(go_result);
+
+                return rrx;
+            }
+
+            // the result is stored on its way to the caller
+            let (stx, srx) = oneshot::channel::<GoResult>();
+
+            let rtx = std::mem::replace(&mut rtx, stx);
+
+            tokio::spawn(async move {
+                if let Ok(go_result) = srx.await {
+                    // storing writes the cache log, which must not block the runtime
+                    let stored = tokio::task::spawn_blocking(move || {
+                        cache.store(&query, &go_result);
+
+                        go_result
+                    })
+                    .await;
+
+                    if let Ok(go_result) = stored {
+                        let _ = rtx.send(go_result);
+                    }
+                }
+            });
+        }
+
         go_job.rtx = Some(rtx);
+        go_job.queued = Some(std::time::Instant::now());
 
         let send_result = self.gtx.send(go_job);
 
@@ -552,8 +1615,247 @@ impl UciEngine {
         rrx
     }
 
+    /// issue go command, also returning a receiver of the analysis info of every info line
+    /// of this search and no other, nothing is received if the go job is answered without
+    /// the engine
+    pub fn go_analysis(
+        &self,
+        go_job: GoJob,
+    ) -> (
+        oneshot::Receiver<GoResult>,
+        mpsc::UnboundedReceiver<AnalysisInfo>,
+    ) {
+        let mut go_job = go_job;
+
+        let (itx, irx) = mpsc::unbounded_channel::<AnalysisInfo>();
+
+        go_job.itx = Some(itx);
+
+        (self.go(go_job), irx)
+    }
+
     /// quit engine
     pub fn quit(&self) {
         self.go(GoJob::new().custom("quit"));
     }
 }
+
+/// kill engine process when the handle goes away, so that it does not outlive it
+impl Drop for UciEngine {
+    fn drop(&mut self) {
+        self.kill();
+    }
+}
+
+/// sender of commands to engine input, tagged with the id of the go job they belong to
+type CommandSender = mpsc::UnboundedSender<(u64, String)>;
+
+/// analysis info sender of a search, together with the number of the search
+type SearchSink = (u64, mpsc::UnboundedSender<AnalysisInfo>);
+
+/// engine input of a running go job
+struct JobInput<'a> {
+    ctx: &'a CommandSender,
+    job: u64,
+    /// number of searches started, shared with the reader
+    searches: &'a AtomicU64,
+    /// registers analysis info senders of searches with the reader
+    jtx: &'a mpsc::UnboundedSender<SearchSink>,
+}
+
+/// job input implementation
+impl JobInput<'_> {
+    /// send command tagged with go job id
+    fn send<T: core::fmt::Display>(&self, command: T) {
+        let _ = self.ctx.send((self.job, command.to_string()));
+    }
+}
+
+/// probe tablebase of go job on a blocking thread, None if the engine has to be queried
+async fn probe_tablebase(go_job: &mut GoJob) -> Option<GoResult> {
+    let tablebase = go_job.tablebase.take()?;
+
+    let pos = go_job.position()?;
+
+    let probe = tokio::task::spawn_blocking(move || tablebase.probe(&pos))
+        .await
+        .ok()??;
+
+    let mut ai = AnalysisInfo::new();
+
+    ai.done = true;
+
+    Some(GoResult {
+        bestmove: Some(probe.bestmove),
+        ponder: None,
+        ai,
+        source: MoveSource::Tablebase {
+            wdl: probe.wdl,
+            dtz: probe.dtz,
+        },
+    })
+}
+
+/// reset analysis info and write go job commands to engine
+fn issue_go_job(go_job: &GoJob, input: &JobInput<'_>, ai: &std::sync::Mutex<AnalysisInfo>) {
+    if go_job.custom_command.is_none() {
+        let mut ai = ai.lock().unwrap();
+
+        *ai = AnalysisInfo::new();
+
+        let search = input.searches.fetch_add(1, Ordering::Relaxed) + 1;
+
+        // registered before the commands are sent, so that the reader has it
+        // when the first line of the search arrives
+        if let Some(itx) = &go_job.itx {
+            let _ = input.jtx.send((search, itx.clone()));
+        }
+    }
+
+    for command in go_job.to_commands() {
+        input.send(command);
+    }
+}
+
+/// wait for bestmove and turn it into go result, None if engine output closed
+async fn recv_go_result(
+    rx: &mut mpsc::UnboundedReceiver<String>,
+    ai: &std::sync::Mutex<AnalysisInfo>,
+) -> Option<GoResult> {
+    let recv_result = rx.recv().await?;
+
+    if log_enabled!(Level::Debug) {
+        debug!("recv result {:?}", recv_result);
+    }
+
+    let send_ai: AnalysisInfo;
+
+    {
+        let ai = ai.lock().unwrap();
+
+        send_ai = *ai;
+    }
+
+    let mut go_result = GoResult {
+        bestmove: None,
+        ponder: None,
+        ai: send_ai,
+        source: MoveSource::Engine,
+    };
+
+    match recv_result.parse::<EngineToGui>() {
+        Ok(EngineToGui::BestMove { bestmove, ponder }) => {
+            go_result.bestmove = Some(bestmove);
+            go_result.ponder = ponder;
+        }
+        parse_result => warn!("invalid bestmove {} : {:?}", recv_result, parse_result),
+    }
+
+    Some(go_result)
+}
+
+/// send go result to the job that awaits it
+fn send_go_result(rtx: Option<oneshot::Sender<GoResult>>, go_result: GoResult) {
+    if let Some(rtx) = rtx {
+        let send_result = rtx.send(go_result);
+
+        if log_enabled!(Level::Debug) {
+            debug!("result of send go result {:?}", send_result);
+        }
+    }
+}
+
+/// run go job, returns false if engine output closed
+async fn run_go_job(
+    go_job: GoJob,
+    input: &JobInput<'_>,
+    rx: &mut mpsc::UnboundedReceiver<String>,
+    ai: &std::sync::Mutex<AnalysisInfo>,
+) -> bool {
+    issue_go_job(&go_job, input, ai);
+
+    if go_job.custom_command.is_some() || go_job.ponder {
+        return true;
+    }
+
+    let search = recv_go_result(rx, ai);
+
+    #[cfg(feature = "tracing")]
+    let search = tracing::Instrument::instrument(search, tracing::debug_span!("search"));
+
+    match search.await {
+        Some(go_result) => {
+            send_go_result(go_job.rtx, go_result);
+
+            true
+        }
+        _ => false,
+    }
+}
+
+/// run ponder job until the ponder handle resolves it, returns false if engine output closed
+async fn run_ponder_job(
+    go_job: GoJob,
+    input: &JobInput<'_>,
+    rx: &mut mpsc::UnboundedReceiver<String>,
+    ai: &std::sync::Mutex<AnalysisInfo>,
+) -> bool {
+    let mut go_job = go_job;
+
+    let mut pcrx = go_job.pcrx.take().unwrap();
+
+    issue_go_job(&go_job, input, ai);
+
+    // engines should not send bestmove while pondering, but some do
+    let first = tokio::select! {
+        control = &mut pcrx => Ok(control.ok()),
+        go_result = recv_go_result(rx, ai) => Err(go_result),
+    };
+
+    let (control, early_result) = match first {
+        Ok(control) => (control, None),
+        Err(Some(go_result)) => {
+            if log_enabled!(Level::Debug) {
+                debug!("bestmove received while pondering {:?}", go_result);
+            }
+
+            ((&mut pcrx).await.ok(), Some(go_result))
+        }
+        Err(None) => return false,
+    };
+
+    match control {
+        Some(PonderControl::Hit(rtx)) => {
+            let go_result = match early_result {
+                Some(go_result) => go_result,
+                _ => {
+                    input.send(GuiToEngine::PonderHit);
+
+                    match recv_go_result(rx, ai).await {
+                        Some(go_result) => go_result,
+                        _ => return false,
+                    }
+                }
+            };
+
+            send_go_result(Some(rtx), go_result);
+
+            true
+        }
+        control => {
+            // pondermiss or dropped handle, the bestmove of the ponder search belongs to no one
+            if early_result.is_none() {
+                input.send(GuiToEngine::Stop);
+
+                if recv_go_result(rx, ai).await.is_none() {
+                    return false;
+                }
+            }
+
+            match control {
+                Some(PonderControl::Miss(go_job)) => run_go_job(*go_job, input, rx, ai).await,
+                _ => true,
+            }
+        }
+    }
+}
@@ -0,0 +1,53 @@
+use uciengine::annotate::*;
+use uciengine::mockengine::*;
+use uciengine::pgn::*;
+
+/// one search answer of the mock engine, score is from the point of view of the side to move
+fn search(score: &str, pv: &str) -> Vec<MockAction> {
+    vec![
+        send(format!("info depth 12 score {} pv {}", score, pv)),
+        send(format!("bestmove {}", pv.split(' ').next().unwrap())),
+    ]
+}
+
+#[tokio::test]
+async fn annotate_scholars_mate() {
+    // one answer per position, the final checkmate is not searched
+    let engine = MockScript::empty()
+        .on_times("go", search("cp 30", "e2e4 e7e5"), 1)
+        .on_times("go", search("cp -30", "e7e5 g1f3"), 1)
+        .on_times("go", search("cp 40", "g1f3 b8c6"), 1)
+        .on_times("go", search("cp 40", "b8c6 f1c4"), 1)
+        .on_times("go", search("cp -30", "f1c4 g8f6"), 1)
+        .on_times("go", search("cp -100", "g7g6 h5f3"), 1)
+        .on_times("go", search("mate 1", "h5f7"), 1)
+        .start_engine();
+
+    let game = read_games("1. e4 e5 2. Qh5 Nc6 3. Bc4 Nf6 4. Qxf7# 1-0")
+        .unwrap()
+        .remove(0);
+
+    let annotated = Annotator::new()
+        .annotate_game(&engine, &game)
+        .await
+        .unwrap();
+
+    let moves = &annotated.moves;
+
+    assert_eq!(moves[0].comment.as_deref(), Some("[%eval 0.30]"));
+    assert!(moves[0].nags.is_empty());
+
+    // 2. Qh5 drops 80 centipawns
+    assert_eq!(moves[2].comment.as_deref(), Some("[%eval -0.40]"));
+    assert_eq!(moves[2].nags, vec![NAG_INACCURACY]);
+    assert_eq!(moves[2].variation, vec!["Nf3", "Nc6"]);
+
+    // 3... Nf6 allows mate in one
+    assert_eq!(moves[5].comment.as_deref(), Some("[%eval #1]"));
+    assert_eq!(moves[5].nags, vec![NAG_BLUNDER]);
+    assert_eq!(moves[5].variation, vec!["g6", "Qf3"]);
+
+    // the mating move ends the game and gets no eval
+    assert_eq!(moves[6].comment, None);
+    assert!(moves[6].nags.is_empty());
+}
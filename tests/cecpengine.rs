@@ -0,0 +1,76 @@
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+use uciengine::cecpengine::*;
+use uciengine::uciengine::*;
+
+/// minimal cecp engine answering in san, records the commands it receives
+async fn fake_engine(
+    io: tokio::io::DuplexStream,
+    commands: std::sync::Arc<std::sync::Mutex<Vec<String>>>,
+) {
+    let (input, mut output) = tokio::io::split(io);
+
+    let mut lines = BufReader::new(input).lines();
+
+    while let Ok(Some(line)) = lines.next_line().await {
+        commands.lock().unwrap().push(line.clone());
+
+        let answer = match line.as_str() {
+            "protover 2" => "feature myname=\"Fake\" setboard=1 san=1 usermove=1 done=1\n",
+            "go" => "4 35 12 5000 Nc6 Nf3\nmove Nc6\n",
+            "quit" => return,
+            _ => continue,
+        };
+
+        output.write_all(answer.as_bytes()).await.unwrap();
+    }
+}
+
+#[tokio::test]
+async fn cecp_go_job() {
+    let (engine_io, fake_io) = tokio::io::duplex(64 * 1024);
+
+    let commands = std::sync::Arc::new(std::sync::Mutex::new(vec![]));
+
+    tokio::spawn(fake_engine(fake_io, commands.clone()));
+
+    let (reader, writer) = tokio::io::split(engine_io);
+
+    let engine = CecpEngine::from_io(reader, writer);
+
+    let go_result = engine
+        .go(GoJob::new()
+            .pos_startpos()
+            .pos_moves("e2e4")
+            .tc(Timecontrol {
+                wtime: 60000,
+                winc: 1000,
+                btime: 90000,
+                binc: 1000,
+            }))
+        .await
+        .unwrap();
+
+    assert_eq!(go_result.bestmove, Some("b8c6".to_string()));
+    assert_eq!(go_result.ai.depth, 4);
+    assert_eq!(go_result.ai.pv(), Some("b8c6 g1f3".to_string()));
+    assert_eq!(engine.features().name(), Some("Fake".to_string()));
+
+    let commands = commands.lock().unwrap().clone();
+
+    // engine plays black, time is in centiseconds
+    for expected in &[
+        "usermove e4",
+        "level 0 1:30 1",
+        "time 9000",
+        "otim 6000",
+        "post",
+        "go",
+    ] {
+        assert!(
+            commands.contains(&expected.to_string()),
+            "missing {}",
+            expected
+        );
+    }
+}
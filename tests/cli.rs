@@ -0,0 +1,130 @@
+use std::path::PathBuf;
+use std::process::Output;
+
+use shakmaty::{CastlingMode, Chess, Position};
+
+use uciengine::mockengine::*;
+
+/// write file to temp dir and return its path
+fn temp_file(name: &str, text: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("uciengine_cli_{}_{}", std::process::id(), name));
+
+    std::fs::write(&path, text).unwrap();
+
+    path
+}
+
+/// run cli with a config file starting the mock engine on script
+fn run_cli(name: &str, script: &MockScript, options: &str, args: &[&str]) -> Output {
+    let config = temp_file(
+        &format!("{}.toml", name),
+        &format!(
+            "engine = '{}'\nargs = ['{}']\n[options]\n{}",
+            env!("CARGO_BIN_EXE_mockengine"),
+            script.to_json(),
+            options
+        ),
+    );
+
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_uciengine"))
+        .args(args)
+        .arg("--config")
+        .arg(&config)
+        .output()
+        .unwrap();
+
+    let _ = std::fs::remove_file(&config);
+
+    output
+}
+
+/// mock script answering go perft 2 of the starting position with divide lines,
+/// the count of e2e4 is off by wrong
+fn perft_script(wrong: u64) -> MockScript {
+    let pos = Chess::default();
+
+    let mut lines = vec![];
+
+    let mut total = 0;
+
+    for m in pos.legal_moves() {
+        let uci = m.to_uci(CastlingMode::Standard).to_string();
+
+        let mut after = pos.clone();
+
+        after.play_unchecked(m);
+
+        let mut nodes = shakmaty::perft(&after, 1);
+
+        if uci == "e2e4" {
+            nodes += wrong;
+        }
+
+        total += nodes;
+
+        lines.push(send(format!("{}: {}", uci, nodes)));
+    }
+
+    lines.push(send(""));
+    lines.push(send(format!("Nodes searched: {}", total)));
+
+    MockScript::new().first("go perft 2", lines)
+}
+
+#[test]
+fn perft_check_mock() {
+    let output = run_cli(
+        "perft_ok",
+        &perft_script(0),
+        "",
+        &["perft-check", "--depth", "2"],
+    );
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert!(output.status.success(), "{}", stdout);
+    assert!(stdout.contains("perft 2 : reference 400 , engine 400"));
+
+    let output = run_cli(
+        "perft_mismatch",
+        &perft_script(1),
+        "",
+        &["perft-check", "--depth", "2"],
+    );
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stdout.contains("e2e4 : reference Some(20) , engine Some(21)"));
+    assert!(stdout.contains("mismatch"));
+}
+
+#[test]
+fn bench_sends_options_once() {
+    let positions = temp_file(
+        "positions.epd",
+        "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1\n\
+         rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2\n\
+         8/8/8/8/8/8/8/K1k5 w - - 0 1\n",
+    );
+
+    // a repeated setoption crashes the engine
+    let script = MockScript::new()
+        .on_times("setoption name Hash value 16", vec![], 1)
+        .on("setoption", vec![exit(1)]);
+
+    let output = run_cli(
+        "bench",
+        &script,
+        "Hash = 16",
+        &["bench", positions.to_str().unwrap(), "--depth", "1"],
+    );
+
+    let _ = std::fs::remove_file(&positions);
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert!(output.status.success(), "{}", stdout);
+    assert!(stdout.contains("position 3 / 3 : bestmove e2e4"));
+    assert!(stdout.contains("positions 3"));
+}
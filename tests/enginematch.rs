@@ -0,0 +1,93 @@
+use uciengine::enginematch::*;
+use uciengine::mockengine::*;
+
+/// mock engine playing moves in order, reporting score from its own point of view
+fn scripted_engine(name: &str, moves: &[(&str, &str)]) -> MatchEngine {
+    let script = moves
+        .iter()
+        .fold(MockScript::empty(), |script, (bestmove, score)| {
+            script.on_times(
+                "go",
+                vec![
+                    send(format!("info depth 5 score {} pv {}", score, bestmove)),
+                    send(format!("bestmove {}", bestmove)),
+                ],
+                1,
+            )
+        });
+
+    MatchEngine {
+        name: name.to_string(),
+        engine: script.start_engine(),
+    }
+}
+
+#[tokio::test]
+async fn play_game_checkmate() {
+    let first = scripted_engine("first", &[("g2g4", "cp -50")]);
+    let second = scripted_engine("second", &[("d8h4", "mate 1")]);
+
+    let engine_match = EngineMatch::new(first, second);
+
+    let opening = Opening {
+        fen: None,
+        moves: vec!["f2f3".to_string(), "e7e5".to_string()],
+    };
+
+    let game = engine_match.play_game(&opening, true, 1).await.unwrap();
+
+    assert_eq!(game.outcome, GameOutcome::BlackWins);
+    assert_eq!(game.termination, "checkmate");
+    assert_eq!(game.first_score(), 0.0);
+    assert_eq!(game.pgn.header("White"), Some("first"));
+    assert_eq!(game.pgn.header("Result"), Some("0-1"));
+
+    let sans: Vec<&str> = game.pgn.moves.iter().map(|m| m.san.as_str()).collect();
+
+    assert_eq!(sans, vec!["f3", "e5", "g4", "Qh4#"]);
+    assert!(game.pgn.moves[3]
+        .comment
+        .as_deref()
+        .unwrap()
+        .starts_with("+M1/5 "));
+}
+
+#[tokio::test]
+async fn run_resign_adjudication() {
+    // the second engine sees itself lost in both games and resigns after two moves
+    let first = scripted_engine(
+        "first",
+        &[("g1f3", "cp 600"), ("f3g1", "cp 600"), ("g8f6", "cp 600")],
+    );
+    let second = scripted_engine(
+        "second",
+        &[
+            ("g8f6", "cp -600"),
+            ("f6g8", "cp -600"),
+            ("g1f3", "cp -600"),
+            ("f3g1", "cp -600"),
+        ],
+    );
+
+    let engine_match = EngineMatch::new(first, second).adjudication(Adjudication {
+        resign_score: Some(500),
+        resign_moves: 2,
+        ..Adjudication::default()
+    });
+
+    let report = engine_match.run().await.unwrap();
+
+    assert_eq!((report.wins, report.losses, report.draws), (2, 0, 0));
+
+    let outcomes: Vec<GameOutcome> = report.games.iter().map(|game| game.outcome).collect();
+
+    assert_eq!(
+        outcomes,
+        vec![GameOutcome::WhiteWins, GameOutcome::BlackWins]
+    );
+    assert!(report
+        .games
+        .iter()
+        .all(|game| game.termination == "adjudication"));
+    assert_eq!(report.games[1].pgn.moves.len(), 3);
+}
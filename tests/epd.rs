@@ -0,0 +1,74 @@
+use uciengine::epd::*;
+use uciengine::mockengine::*;
+use uciengine::uciengine::*;
+
+const WAC001: &str =
+    "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id \"WAC.001\";";
+
+#[tokio::test]
+async fn run_position_solved() {
+    let engine = MockScript::empty()
+        .on(
+            "go depth 1",
+            vec![
+                delay(100),
+                send("info depth 1 time 5 score cp 0 pv g3g6"),
+                send("bestmove g3g6"),
+            ],
+        )
+        .on(
+            "go depth 3",
+            vec![
+                send("info depth 2 time 20 score cp 300 pv g3g6"),
+                send("info depth 3 nodes 4000 time 30 score mate 3 pv g3g6"),
+                send("bestmove g3g6"),
+            ],
+        )
+        .start_engine();
+
+    // the search queued before is still running when the position is searched,
+    // its analysis does not count
+    let earlier = engine.go(GoJob::new().pos_startpos().go_opt("depth", 1));
+
+    let runner = EpdRunner::new().limit(SearchLimit::Depth(3));
+
+    let result = runner
+        .run_position(&engine, &EpdPosition::parse(WAC001).unwrap(), 0)
+        .await
+        .unwrap();
+
+    assert!(earlier.await.is_ok());
+
+    assert_eq!(result.id, "WAC.001");
+    assert_eq!(result.bestmove, Some("g3g6".to_string()));
+    assert!(result.solved);
+    assert_eq!(result.time_to_solution, Some(20));
+    assert_eq!((result.depth, result.nodes), (3, 4000));
+}
+
+#[tokio::test]
+async fn run_position_unsolved() {
+    let engine = MockScript::empty()
+        .on(
+            "go",
+            vec![
+                send("info depth 1 time 10 score cp 300 pv g3g6"),
+                send("info depth 2 time 20 score cp 50 pv c2h7"),
+                send("bestmove c2h7"),
+            ],
+        )
+        .start_engine();
+
+    let suite = read_epd(&format!("{}\n# comment\n\n{}\n", WAC001, WAC001)).unwrap();
+
+    let report = EpdRunner::new()
+        .limit(SearchLimit::Depth(2))
+        .run(&engine, &suite)
+        .await
+        .unwrap();
+
+    assert_eq!((report.solved, report.total), (0, 2));
+    assert_eq!(report.results[0].bestmove, Some("c2h7".to_string()));
+    assert_eq!(report.results[0].expected, vec!["g3g6".to_string()]);
+    assert_eq!(report.results[0].time_to_solution, None);
+}
@@ -0,0 +1,404 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use shakmaty::uci::UciMove;
+use shakmaty::{Chess, Move};
+use shakmaty_syzygy::{AmbiguousWdl, Dtz, MaybeRounded, SyzygyError};
+
+use uciengine::mockengine::*;
+use uciengine::tablebase::*;
+use uciengine::uciengine::*;
+
+/// spawn mock engine running script
+fn mock_engine(script: MockScript) -> Arc<UciEngine> {
+    UciEngine::new_with_args(env!("CARGO_BIN_EXE_mockengine"), &[script.to_json()])
+}
+
+#[tokio::test]
+async fn go_queue() {
+    let engine = mock_engine(
+        MockScript::empty()
+            .on("go depth 3", vec![send("bestmove g1f3")])
+            .on_times("go", vec![delay(100), send("bestmove d2d4")], 1)
+            .on(
+                "go",
+                vec![
+                    send("info depth 1 score cp 10"),
+                    send("bestmove e2e4 ponder e7e5"),
+                ],
+            )
+            .on("quit", vec![exit(0)]),
+    );
+
+    let first = engine.go(GoJob::new().pos_startpos().go_opt("movetime", 100));
+    let second = engine.go(GoJob::new().pos_startpos().go_opt("depth", 3));
+    let third = engine.go(GoJob::new().pos_startpos().go_opt("nodes", 100));
+
+    let first = first.await.unwrap();
+    let second = second.await.unwrap();
+    let third = third.await.unwrap();
+
+    assert_eq!(first.bestmove, Some("d2d4".to_string()));
+    assert_eq!(second.bestmove, Some("g1f3".to_string()));
+    assert_eq!(third.bestmove, Some("e2e4".to_string()));
+    assert_eq!(third.ponder, Some("e7e5".to_string()));
+    assert_eq!(third.ai.depth, 1);
+
+    let shutdown_status = engine.shutdown(Duration::from_millis(3000)).await;
+
+    assert!(!shutdown_status.killed);
+    assert!(shutdown_status.exit_status.unwrap().success());
+}
+
+#[tokio::test]
+async fn ponder_hit_and_miss() {
+    let engine = mock_engine(MockScript::new().first("stop", vec![send("bestmove a2a3")]));
+
+    let go_job = || GoJob::new().pos_startpos().pos_moves("e2e4 e7e5");
+
+    let go_result = engine.go_ponder(go_job()).hit().await.unwrap();
+
+    assert_eq!(go_result.bestmove, Some("e2e4".to_string()));
+
+    // the bestmove sent on stop belongs to the missed ponder search and is discarded
+    let go_result = engine
+        .go_ponder(go_job())
+        .miss(GoJob::new().pos_startpos().go_opt("depth", 1))
+        .await
+        .unwrap();
+
+    assert_eq!(go_result.bestmove, Some("e2e4".to_string()));
+}
+
+#[tokio::test]
+async fn crash_during_search() {
+    let engine = mock_engine(MockScript::new().first("go", vec![send("info depth 1"), exit(3)]));
+
+    let go_result = engine.go(GoJob::new().pos_startpos()).await;
+
+    assert!(go_result.is_err());
+
+    let shutdown_status = engine.shutdown(Duration::from_millis(3000)).await;
+
+    assert_eq!(shutdown_status.exit_status.unwrap().code(), Some(3));
+}
+
+#[tokio::test]
+async fn parse_stats() {
+    let script = MockScript::new().first(
+        "go",
+        vec![
+            send("info depth 2 wdl 500 400 100 score cp 20"),
+            send("info depth x"),
+            send("bestmove e2e4"),
+        ],
+    );
+
+    let mut command = tokio::process::Command::new(env!("CARGO_BIN_EXE_mockengine"));
+
+    command.arg(script.to_json());
+
+    let parser_config = uciengine::analysis::ParserConfig::new()
+        .unknown_key(uciengine::analysis::UnknownKeyPolicy::Skip)
+        .arity("wdl", 3)
+        .collect_stats(true);
+
+    let engine = UciEngine::spawn_with_config(command, parser_config).unwrap();
+
+    let go_result = engine.go(GoJob::new().pos_startpos()).await.unwrap();
+
+    assert_eq!(go_result.ai.depth, 2);
+
+    let stats = engine.parse_stats();
+
+    assert_eq!((stats.lines, stats.ok, stats.failed), (3, 2, 1));
+}
+
+#[tokio::test]
+async fn identify_engine() {
+    let engine = mock_engine(MockScript::empty().on(
+        "uci",
+        vec![
+            send("id name Mock 1.0"),
+            send("id author Tester"),
+            send("option name Hash type spin default 16 min 1 max 1024"),
+            send("uciok"),
+        ],
+    ));
+
+    let engine_id = engine.identify(Duration::from_millis(3000)).await.unwrap();
+
+    assert_eq!(engine_id.name, Some("Mock 1.0".to_string()));
+    assert_eq!(engine_id.author, Some("Tester".to_string()));
+    assert_eq!(engine_id.options.len(), 1);
+    assert_eq!(engine_id.options[0].name, "Hash");
+}
+
+#[tokio::test]
+async fn cached_search() {
+    let path = std::env::temp_dir().join(format!("uciengine_cached_{}.jsonl", std::process::id()));
+
+    let _ = std::fs::remove_file(&path);
+
+    let cache = Arc::new(uciengine::cache::AnalysisCache::open(&path).unwrap());
+
+    let engine = mock_engine(
+        MockScript::empty()
+            .on_times(
+                "go",
+                vec![
+                    send("info depth 12 score cp 30 pv d2d4"),
+                    send("bestmove d2d4"),
+                ],
+                1,
+            )
+            .on("go", vec![send("bestmove a2a3")])
+            .on("quit", vec![exit(0)]),
+    );
+
+    engine.set_cache(Some(cache.clone()), "mock");
+
+    let transcript_path = path.with_extension("transcript.jsonl");
+
+    engine.record_transcript(&transcript_path).unwrap();
+
+    let job = || GoJob::new().pos_startpos().go_opt("depth", 10);
+
+    let first = engine.go(job()).await.unwrap();
+
+    assert_eq!(first.source, MoveSource::Engine);
+
+    // answered from the cache, the engine would play a2a3 now
+    let second = engine.go(job().uci_opt("Hash", 64)).await.unwrap();
+
+    assert_eq!(second.bestmove, Some("d2d4".to_string()));
+    assert_eq!(second.source, MoveSource::Cache);
+
+    let deeper = engine.go(job().go_opt("depth", 20)).await.unwrap();
+
+    assert_eq!(deeper.bestmove, Some("a2a3".to_string()));
+    assert_eq!(cache.len(), 1);
+
+    engine.shutdown(Duration::from_millis(3000)).await;
+
+    // the option of the cached job reaches the engine before the deeper search
+    let transcript = std::fs::read_to_string(&transcript_path).unwrap();
+
+    let option = transcript.find("setoption name Hash value 64").unwrap();
+
+    assert!(option < transcript.find("go depth 20").unwrap());
+
+    let _ = std::fs::remove_file(&path);
+    let _ = std::fs::remove_file(&transcript_path);
+}
+
+#[tokio::test]
+async fn start_profile() {
+    let script = MockScript::empty()
+        .on(
+            "uci",
+            vec![
+                send("id name Mock"),
+                send("option name Hash type spin default 16 min 1 max 1024"),
+                send("uciok"),
+            ],
+        )
+        // readyok only follows the option, so starting succeeds only if the option is set
+        .on("setoption name Hash value 64", vec![send("readyok")])
+        .on("go", vec![send("bestmove e2e4")])
+        .on("quit", vec![exit(0)]);
+
+    let toml = format!(
+        "[engines.mock]\npath = {:?}\nargs = [{:?}]\n[engines.mock.options]\nHash = 64\n",
+        env!("CARGO_BIN_EXE_mockengine"),
+        script.to_json()
+    );
+
+    let mut config = uciengine::config::EngineConfig::from_toml(&toml).unwrap();
+
+    let engine = config
+        .start("mock", Duration::from_millis(3000))
+        .await
+        .unwrap();
+
+    engine.kill();
+
+    // crashed pool engines are restarted from the profile
+    let pool = config
+        .start_pool("mock", 1, Duration::from_millis(3000))
+        .await
+        .unwrap();
+
+    let pooled = pool.acquire().await.unwrap();
+
+    pooled.shutdown(Duration::from_millis(3000)).await;
+
+    drop(pooled);
+
+    let pooled = pool.acquire().await.unwrap();
+
+    assert!(pooled.go(GoJob::new().pos_startpos()).await.is_ok());
+    assert_eq!(pool.metrics().restarts, 1);
+
+    drop(pooled);
+
+    config.engines.get_mut("mock").unwrap().options.insert(
+        "Threads".to_string(),
+        uciengine::config::ConfigValue::Int(4),
+    );
+
+    assert!(matches!(
+        config.start("mock", Duration::from_millis(3000)).await,
+        Err(uciengine::config::ConfigError::UnknownOption(_))
+    ));
+}
+
+#[tokio::test]
+async fn engine_metrics() {
+    let engine = mock_engine(
+        MockScript::empty()
+            .on(
+                "go",
+                vec![
+                    send("info depth 3 nodes 5000 score cp 20"),
+                    send("info depth x"),
+                    send("bestmove e2e4"),
+                ],
+            )
+            .on("quit", vec![exit(0)]),
+    );
+
+    engine.go(GoJob::new().pos_startpos()).await.unwrap();
+    engine.go(GoJob::new().pos_startpos()).await.unwrap();
+
+    let metrics = engine.metrics.snapshot();
+
+    assert_eq!(metrics.jobs, 2);
+    assert_eq!(metrics.nodes, 10000);
+    assert_eq!(metrics.parse_failures, 2);
+
+    let text = uciengine::metrics::encode_prometheus(&[("mock", metrics)]);
+
+    assert!(text.contains("uciengine_nodes_total{engine=\"mock\"} 10000\n"));
+}
+
+/// table source answering every position like white to move in KQvK
+struct KqvkTables;
+
+impl TableSource for KqvkTables {
+    fn max_pieces(&self) -> usize {
+        3
+    }
+
+    fn best_move(&self, pos: &Chess) -> Result<Option<(Move, MaybeRounded<Dtz>)>, SyzygyError> {
+        let m = UciMove::from_ascii(b"h1h8").unwrap().to_move(pos).unwrap();
+
+        Ok(Some((m, MaybeRounded::Precise(Dtz(-18)))))
+    }
+
+    fn probe_wdl(&self, _pos: &Chess) -> Result<AmbiguousWdl, SyzygyError> {
+        Ok(AmbiguousWdl::Win)
+    }
+
+    fn probe_dtz(&self, _pos: &Chess) -> Result<MaybeRounded<Dtz>, SyzygyError> {
+        Ok(MaybeRounded::Precise(Dtz(19)))
+    }
+}
+
+#[tokio::test]
+async fn tablebase_go() {
+    let engine = mock_engine(
+        MockScript::empty()
+            .on("go", vec![send("bestmove h1h7")])
+            .on("quit", vec![exit(0)]),
+    );
+
+    engine.set_tablebase(Some(SyzygyTablebase::from_source(KqvkTables)));
+
+    let transcript_path =
+        std::env::temp_dir().join(format!("uciengine-tablebase-{}.jsonl", std::process::id()));
+
+    engine.record_transcript(&transcript_path).unwrap();
+
+    let go_result = engine
+        .go(GoJob::new()
+            .pos_fen("8/8/8/8/8/2k5/8/K6Q w - - 0 1")
+            .uci_opt("Hash", 64))
+        .await
+        .unwrap();
+
+    assert_eq!(go_result.bestmove, Some("h1h8".to_string()));
+    assert_eq!(go_result.source, MoveSource::Tablebase { wdl: 2, dtz: 19 });
+
+    // positions with too many pieces always reach the engine
+    let go_result = engine.go(GoJob::new().pos_startpos()).await.unwrap();
+
+    assert_eq!(go_result.source, MoveSource::Engine);
+    assert_eq!(go_result.bestmove, Some("h1h7".to_string()));
+
+    engine.shutdown(Duration::from_millis(3000)).await;
+
+    // the option of the tablebase job is sent before the engine search
+    let transcript = std::fs::read_to_string(&transcript_path).unwrap();
+
+    let option = transcript.find("setoption name Hash value 64").unwrap();
+
+    assert!(option < transcript.find("position startpos").unwrap());
+
+    let _ = std::fs::remove_file(&transcript_path);
+}
+
+#[tokio::test]
+async fn book_go_sends_options() {
+    let path = std::env::temp_dir().join(format!(
+        "uciengine-book-options-{}.jsonl",
+        std::process::id()
+    ));
+
+    let engine = MockScript::new().start_engine();
+
+    // startpos e2e4 ( from e2 = 12, to e4 = 28 )
+    let mut book = vec![];
+
+    book.extend_from_slice(&0x463b96181691fc9c_u64.to_be_bytes());
+    book.extend_from_slice(&(28_u16 | (12 << 6)).to_be_bytes());
+    book.extend_from_slice(&1_u16.to_be_bytes());
+    book.extend_from_slice(&0_u32.to_be_bytes());
+
+    engine.set_book(Some(
+        uciengine::book::PolyglotBook::from_bytes(&book).unwrap(),
+    ));
+
+    engine.record_transcript(&path).unwrap();
+
+    let go_result = engine
+        .go(GoJob::new().pos_startpos().uci_opt("Hash", 64))
+        .await
+        .unwrap();
+
+    assert_eq!(go_result.source, MoveSource::Book);
+
+    // out of book, searched with the options set on the book job
+    let go_result = engine
+        .go(GoJob::new().pos_startpos().pos_moves("e2e4"))
+        .await
+        .unwrap();
+
+    assert_eq!(go_result.source, MoveSource::Engine);
+
+    engine.shutdown(Duration::from_millis(3000)).await;
+
+    let entries =
+        uciengine::transcript::read_transcript(&std::fs::read_to_string(&path).unwrap()).unwrap();
+
+    let commands: Vec<&str> = entries
+        .iter()
+        .filter(|entry| entry.dir == uciengine::transcript::Direction::ToEngine)
+        .map(|entry| entry.line.as_str())
+        .collect();
+
+    assert_eq!(commands[0], "setoption name Hash value 64");
+    assert_eq!(commands[1], "position startpos moves e2e4");
+
+    let _ = std::fs::remove_file(&path);
+}
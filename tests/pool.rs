@@ -0,0 +1,38 @@
+use std::time::Duration;
+
+use uciengine::mockengine::*;
+use uciengine::pool::*;
+use uciengine::uciengine::*;
+
+#[tokio::test]
+async fn acquire_after_shutdown() {
+    let pool = EnginePool::from_engines(vec![MockScript::new().start_engine()]);
+
+    let engine = pool.acquire().await.unwrap();
+
+    assert_eq!(pool.idle(), 0);
+
+    drop(engine);
+
+    pool.shutdown(Duration::from_millis(3000)).await;
+
+    assert!(matches!(pool.acquire().await, Err(PoolError::Closed)));
+}
+
+#[tokio::test]
+async fn respawn_terminated_engine() {
+    let crashing = MockScript::new().first("go", vec![exit(3)]).start_engine();
+
+    let pool = EnginePool::respawning(vec![crashing], |_| Ok(MockScript::new().start_engine()));
+
+    let engine = pool.acquire().await.unwrap();
+
+    assert!(engine.go(GoJob::new().pos_startpos()).await.is_err());
+
+    drop(engine);
+
+    let engine = pool.acquire().await.unwrap();
+
+    assert!(engine.go(GoJob::new().pos_startpos()).await.is_ok());
+    assert_eq!(pool.metrics().restarts, 1);
+}
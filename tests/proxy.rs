@@ -0,0 +1,159 @@
+use std::time::Duration;
+
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+use uciengine::mockengine::*;
+use uciengine::proxy::*;
+
+/// next line written by the proxy
+async fn next_line<R: tokio::io::AsyncBufRead + Unpin>(lines: &mut tokio::io::Lines<R>) -> String {
+    tokio::time::timeout(Duration::from_millis(3000), lines.next_line())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn proxy_rewrites_and_stops() {
+    // searches with movetime hang until stopped
+    let engine = MockScript::new()
+        .first("go movetime", vec![send("info depth 1 score cp 1 pv d2d4")])
+        .first(
+            "setoption name Threads value 4",
+            vec![send("info string threads 4")],
+        )
+        .start_engine();
+
+    let proxy = UciProxy::new(engine, ProxyConfig::new().cap("Threads", 4).max_time(200));
+
+    let (gui_io, proxy_io) = tokio::io::duplex(64 * 1024);
+
+    let (proxy_in, proxy_out) = tokio::io::split(proxy_io);
+
+    let proxy_task = tokio::spawn(async move { proxy.run(proxy_in, proxy_out).await });
+
+    let (gui_in, mut gui_out) = tokio::io::split(gui_io);
+
+    let mut lines = BufReader::new(gui_in).lines();
+
+    gui_out
+        .write_all(b"uci\nsetoption name Threads value 64\n")
+        .await
+        .unwrap();
+
+    assert_eq!(next_line(&mut lines).await, "id name MockEngine");
+    assert_eq!(next_line(&mut lines).await, "uciok");
+    assert_eq!(next_line(&mut lines).await, "info string threads 4");
+
+    // the proxy stops the search after max time
+    gui_out
+        .write_all(b"position startpos\ngo movetime 60000\n")
+        .await
+        .unwrap();
+
+    assert_eq!(
+        next_line(&mut lines).await,
+        "info depth 1 score cp 1 pv d2d4"
+    );
+    assert_eq!(next_line(&mut lines).await, "bestmove e2e4 ponder e7e5");
+
+    gui_out.write_all(b"quit\n").await.unwrap();
+
+    tokio::time::timeout(Duration::from_millis(3000), proxy_task)
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+}
+
+#[tokio::test]
+async fn late_bestmove_of_gui_search() {
+    // the gui search answers late, after the go job below is queued
+    let engine = MockScript::new()
+        .first(
+            "go depth 1",
+            vec![
+                send("info depth 1 score cp 1 pv a2a3"),
+                delay(200),
+                send("bestmove a2a3"),
+            ],
+        )
+        .first("go depth 2", vec![send("bestmove d2d4")])
+        .start_engine();
+
+    let proxy = UciProxy::new(engine.clone(), ProxyConfig::new());
+
+    let (gui_io, proxy_io) = tokio::io::duplex(64 * 1024);
+
+    let (proxy_in, proxy_out) = tokio::io::split(proxy_io);
+
+    tokio::spawn(async move { proxy.run(proxy_in, proxy_out).await });
+
+    let (gui_in, mut gui_out) = tokio::io::split(gui_io);
+
+    let mut lines = BufReader::new(gui_in).lines();
+
+    gui_out
+        .write_all(b"position startpos\ngo depth 1\n")
+        .await
+        .unwrap();
+
+    assert_eq!(
+        next_line(&mut lines).await,
+        "info depth 1 score cp 1 pv a2a3"
+    );
+
+    // the bestmove of the gui search is not taken as the result of this job
+    let go_result = engine
+        .go(uciengine::uciengine::GoJob::new()
+            .pos_startpos()
+            .go_opt("depth", 2))
+        .await
+        .unwrap();
+
+    assert_eq!(go_result.bestmove, Some("d2d4".to_string()));
+    assert_eq!(next_line(&mut lines).await, "bestmove a2a3");
+}
+
+#[tokio::test]
+async fn slow_gui_gets_all_lines() {
+    // more lines than the engine line broadcast holds
+    let mut actions: Vec<MockAction> = (0..2000)
+        .map(|i| send(format!("info string line {}", i)))
+        .collect();
+
+    actions.push(send("bestmove d2d4"));
+
+    let engine = MockScript::new().first("go", actions).start_engine();
+
+    let proxy = UciProxy::new(engine, ProxyConfig::new());
+
+    // small buffer, the proxy cannot write ahead of the gui
+    let (gui_io, proxy_io) = tokio::io::duplex(256);
+
+    let (proxy_in, proxy_out) = tokio::io::split(proxy_io);
+
+    tokio::spawn(async move { proxy.run(proxy_in, proxy_out).await });
+
+    let (gui_in, mut gui_out) = tokio::io::split(gui_io);
+
+    let mut lines = BufReader::new(gui_in).lines();
+
+    gui_out
+        .write_all(b"position startpos\ngo depth 1\n")
+        .await
+        .unwrap();
+
+    // the engine finishes the search while the gui is not reading
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    for i in 0..2000 {
+        assert_eq!(
+            next_line(&mut lines).await,
+            format!("info string line {}", i)
+        );
+    }
+
+    assert_eq!(next_line(&mut lines).await, "bestmove d2d4");
+}
@@ -0,0 +1,123 @@
+#![cfg(target_os = "linux")]
+
+use std::time::Duration;
+
+use uciengine::mockengine::*;
+use uciengine::sandbox::*;
+use uciengine::uciengine::*;
+
+/// mock engine command running script
+fn command(script: MockScript) -> tokio::process::Command {
+    let mut command = tokio::process::Command::new(env!("CARGO_BIN_EXE_mockengine"));
+
+    command.arg(script.to_json());
+
+    command
+}
+
+#[tokio::test]
+async fn limits_and_process_group() {
+    let options = SpawnOptions::new()
+        .open_files(64)
+        .cpu_time(100)
+        .process_group(true);
+
+    let engine = UciEngine::spawn_sandboxed(command(MockScript::new()), &options).unwrap();
+
+    let pid = engine.pid().unwrap();
+
+    let limits = std::fs::read_to_string(format!("/proc/{}/limits", pid)).unwrap();
+
+    let open_files = limits
+        .lines()
+        .find(|line| line.starts_with("Max open files"))
+        .unwrap();
+
+    assert_eq!(
+        open_files.split_whitespace().collect::<Vec<&str>>()[3..5],
+        ["64", "64"]
+    );
+
+    assert_eq!(unsafe { libc::getpgid(pid as i32) }, pid as i32);
+
+    let go_result = engine
+        .go_checked(GoJob::new().pos_startpos())
+        .await
+        .unwrap();
+
+    assert_eq!(go_result.bestmove, Some("e2e4".to_string()));
+
+    engine.kill();
+}
+
+#[tokio::test]
+async fn memory_watchdog_kills_engine() {
+    let options = SpawnOptions::new()
+        .max_rss(1024)
+        .rss_interval(Duration::from_millis(20));
+
+    let engine = UciEngine::spawn_sandboxed(
+        command(MockScript::new().first("go", vec![delay(5000), send("bestmove e2e4")])),
+        &options,
+    )
+    .unwrap();
+
+    let go_result = engine.go_checked(GoJob::new().pos_startpos()).await;
+
+    assert!(
+        matches!(go_result, Err(EngineError::MemoryLimit { limit: 1024, .. })),
+        "{:?}",
+        go_result
+    );
+}
+
+#[tokio::test]
+async fn pinned_pool() {
+    let script = MockScript::empty()
+        .on(
+            "uci",
+            vec![
+                send("id name Mock"),
+                send("option name Threads type spin default 1 min 1 max 64"),
+                send("uciok"),
+            ],
+        )
+        .on("isready", vec![send("readyok")])
+        .on("quit", vec![exit(0)]);
+
+    let json = serde_json::json!({
+        "engines": {
+            "mock": {
+                "path": env!("CARGO_BIN_EXE_mockengine"),
+                "args": [script.to_json()],
+                "options": { "Threads": 1 },
+                "pin_threads": true
+            }
+        }
+    });
+
+    let config = uciengine::config::EngineConfig::from_json(&json.to_string()).unwrap();
+
+    let pool = config
+        .start_pool("mock", 2, Duration::from_millis(3000))
+        .await
+        .unwrap();
+
+    let mut affinities: Vec<Vec<usize>> = pool
+        .affinities()
+        .into_iter()
+        .map(|affinity| affinity.unwrap())
+        .collect();
+
+    affinities.sort();
+
+    // without cpus in the profile, the cpus this process may run on are partitioned
+    let mut expected = partition_cpus(&read_affinity(0).unwrap(), &numa_nodes(), &[1, 1]);
+
+    expected.sort();
+
+    assert_eq!(affinities, expected);
+    assert!(affinities.iter().all(|cpus| cpus.len() == 1));
+
+    pool.shutdown(Duration::from_millis(3000)).await;
+}
@@ -0,0 +1,229 @@
+#![cfg(feature = "server")]
+
+use std::time::Duration;
+
+use futures_util::{SinkExt, StreamExt};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio_tungstenite::tungstenite::Message;
+
+use uciengine::mockengine::*;
+use uciengine::pool::EnginePool;
+use uciengine::server::*;
+
+/// start server on a free localhost port with a pool of two mock engines,
+/// infinite searches run until stopped
+async fn start_server() -> std::net::SocketAddr {
+    let script = || {
+        MockScript::new()
+            .first(
+                "go infinite",
+                vec![send("info depth 7 score cp 21 pv d2d4")],
+            )
+            .start_engine()
+    };
+
+    let pool = EnginePool::from_engines(vec![script(), script()]);
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(serve(listener, pool, ServerConfig::new()));
+
+    addr
+}
+
+/// post json to path and return status line and body
+async fn post(addr: std::net::SocketAddr, path: &str, json: &str) -> (String, String) {
+    let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+
+    let request = format!(
+        "POST {} HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\n\
+         Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+        path,
+        json.len(),
+        json
+    );
+
+    stream.write_all(request.as_bytes()).await.unwrap();
+
+    let mut response = String::new();
+
+    stream.read_to_string(&mut response).await.unwrap();
+
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+
+    (head.lines().next().unwrap().to_string(), body.to_string())
+}
+
+/// get path and return body
+async fn get(addr: std::net::SocketAddr, path: &str) -> String {
+    let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+
+    let request = format!(
+        "GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+        path
+    );
+
+    stream.write_all(request.as_bytes()).await.unwrap();
+
+    let mut response = String::new();
+
+    stream.read_to_string(&mut response).await.unwrap();
+
+    response.split_once("\r\n\r\n").unwrap().1.to_string()
+}
+
+/// next websocket message as json
+async fn next_json<S, E>(socket: &mut S) -> serde_json::Value
+where
+    S: futures_util::Stream<Item = Result<Message, E>> + Unpin,
+    E: std::fmt::Debug,
+{
+    let message = tokio::time::timeout(Duration::from_millis(3000), socket.next())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+
+    serde_json::from_str(message.to_text().unwrap()).unwrap()
+}
+
+/// next raw line of engine output
+async fn next_line(lrx: &mut tokio::sync::broadcast::Receiver<String>) -> String {
+    tokio::time::timeout(Duration::from_millis(3000), lrx.recv())
+        .await
+        .unwrap()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn rest_analysis() {
+    let addr = start_server().await;
+
+    let (status, body) = post(addr, "/analysis", r#"{"moves":"e2e4 e7e5","depth":5}"#).await;
+
+    assert!(status.contains("200"), "{}", status);
+
+    let go_result: serde_json::Value = serde_json::from_str(&body).unwrap();
+
+    assert_eq!(go_result["disposition"], "GoResult");
+    assert_eq!(go_result["bestmove"], "e2e4");
+    assert_eq!(go_result["ai"]["depth"], 1);
+
+    let (status, body) = post(addr, "/analysis", r#"{"fen":"not a fen"}"#).await;
+
+    assert!(status.contains("400"), "{}", status);
+    assert!(body.contains("invalid position"));
+
+    let metrics = get(addr, "/metrics").await;
+
+    assert!(metrics.contains("uciengine_jobs_total{engine=\"pool\"} 1\n"));
+}
+
+#[tokio::test]
+async fn websocket_stream_and_stop() {
+    let addr = start_server().await;
+
+    let (mut socket, _) = tokio_tungstenite::connect_async(format!("ws://{}/ws", addr))
+        .await
+        .unwrap();
+
+    socket
+        .send(Message::Text(r#"{"infinite":true}"#.to_string()))
+        .await
+        .unwrap();
+
+    let info = next_json(&mut socket).await;
+
+    assert_eq!(info["disposition"], "AnalysisInfo");
+    assert_eq!(info["depth"], 7);
+
+    socket
+        .send(Message::Text("stop".to_string()))
+        .await
+        .unwrap();
+
+    loop {
+        let value = next_json(&mut socket).await;
+
+        if value["disposition"] == "GoResult" {
+            assert_eq!(value["bestmove"], "e2e4");
+
+            break;
+        }
+    }
+}
+
+#[test]
+fn depth_search_capped() {
+    let config = ServerConfig::new().max_movetime(5000);
+
+    let request = AnalysisRequest {
+        depth: Some(99),
+        ..AnalysisRequest::default()
+    };
+
+    let commands = request.to_go_job(&config).unwrap().to_commands();
+
+    assert_eq!(
+        commands.last().map(|c| c.as_str()),
+        Some("go depth 99 movetime 5000")
+    );
+
+    let request = AnalysisRequest {
+        movetime: Some(60000),
+        ..AnalysisRequest::default()
+    };
+
+    let commands = request.to_go_job(&config).unwrap().to_commands();
+
+    assert_eq!(
+        commands.last().map(|c| c.as_str()),
+        Some("go movetime 5000")
+    );
+}
+
+#[tokio::test]
+async fn rest_disconnect_stops_search() {
+    // deep searches run until stopped
+    let engine = MockScript::new()
+        .first(
+            "go depth 99",
+            vec![send("info depth 7 score cp 21 pv d2d4")],
+        )
+        .start_engine();
+
+    let mut lrx = engine.subscribe_lines();
+
+    let pool = EnginePool::from_engines(vec![engine]);
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(serve(listener, pool, ServerConfig::new()));
+
+    let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+
+    let json = r#"{"depth":99}"#;
+
+    let request = format!(
+        "POST /analysis HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\n\
+         Content-Length: {}\r\n\r\n{}",
+        json.len(),
+        json
+    );
+
+    stream.write_all(request.as_bytes()).await.unwrap();
+
+    assert_eq!(
+        next_line(&mut lrx).await,
+        "info depth 7 score cp 21 pv d2d4"
+    );
+
+    // the client goes away, the search is stopped
+    drop(stream);
+
+    assert_eq!(next_line(&mut lrx).await, "bestmove e2e4 ponder e7e5");
+}
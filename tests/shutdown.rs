@@ -0,0 +1,49 @@
+#![cfg(unix)]
+
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use uciengine::uciengine::*;
+
+/// write executable shell script acting as engine
+fn script_engine(name: &str, script: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("uciengine_{}_{}.sh", name, std::process::id()));
+
+    std::fs::write(&path, format!("#!/bin/sh\n{}\n", script)).unwrap();
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    path
+}
+
+#[tokio::test]
+async fn quit_gracefully() {
+    let path = script_engine(
+        "quit",
+        "while read line; do [ \"$line\" = quit ] && exit 0; done",
+    );
+
+    let engine = UciEngine::new(path.to_str().unwrap());
+
+    let shutdown_status = engine.shutdown(Duration::from_millis(3000)).await;
+
+    assert!(!shutdown_status.killed);
+    assert!(shutdown_status.exit_status.unwrap().success());
+    assert!(engine.exit_status().is_some());
+
+    let _ = std::fs::remove_file(path);
+}
+
+#[tokio::test]
+async fn kill_hanging_engine() {
+    let path = script_engine("hang", "trap '' TERM\nwhile true; do sleep 1; done");
+
+    let engine = UciEngine::new(path.to_str().unwrap());
+
+    let shutdown_status = engine.shutdown(Duration::from_millis(200)).await;
+
+    assert!(shutdown_status.killed);
+    assert!(!shutdown_status.exit_status.unwrap().success());
+
+    let _ = std::fs::remove_file(path);
+}
@@ -0,0 +1,88 @@
+use std::time::Duration;
+
+use uciengine::mockengine::*;
+use uciengine::transcript::*;
+use uciengine::uciengine::*;
+
+#[tokio::test]
+async fn record_and_replay() {
+    let path =
+        std::env::temp_dir().join(format!("uciengine-transcript-{}.jsonl", std::process::id()));
+
+    let engine = MockScript::new()
+        .first(
+            "go depth 2",
+            vec![send("info depth 2 score cp 30"), send("bestmove d2d4")],
+        )
+        .start_engine();
+
+    engine.record_transcript(&path).unwrap();
+
+    let recorded = engine
+        .go(GoJob::new().pos_startpos().go_opt("depth", 2))
+        .await
+        .unwrap();
+
+    engine.shutdown(Duration::from_millis(3000)).await;
+
+    let entries = read_transcript(&std::fs::read_to_string(&path).unwrap()).unwrap();
+
+    assert_eq!(entries[0].dir, Direction::ToEngine);
+    assert!(entries
+        .iter()
+        .any(|entry| entry.line == "bestmove d2d4" && entry.job == 1));
+
+    let engine = Replay::open(&path).unwrap().start_engine();
+
+    let replayed = engine
+        .go(GoJob::new().pos_startpos().go_opt("depth", 2))
+        .await
+        .unwrap();
+
+    assert_eq!(replayed.bestmove, recorded.bestmove);
+    assert_eq!(replayed.ai.depth, 2);
+    assert_eq!(format!("{:?}", replayed.ai.score), "Cp(30)");
+
+    let shutdown_status = engine.shutdown(Duration::from_millis(3000)).await;
+
+    assert!(!shutdown_status.killed);
+
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn commands_tagged_with_job() {
+    let path = std::env::temp_dir().join(format!(
+        "uciengine-transcript-jobs-{}.jsonl",
+        std::process::id()
+    ));
+
+    let engine = MockScript::new().start_engine();
+
+    engine.record_transcript(&path).unwrap();
+
+    // custom jobs do not wait for the engine, so the job loop runs ahead of the writer
+    for value in 1..=3 {
+        drop(engine.go(GoJob::new().custom(format!("setoption name Opt value {}", value))));
+    }
+
+    engine.go(GoJob::new().pos_startpos()).await.unwrap();
+
+    engine.shutdown(Duration::from_millis(3000)).await;
+
+    let entries = read_transcript(&std::fs::read_to_string(&path).unwrap()).unwrap();
+
+    for value in 1..=3 {
+        let line = format!("setoption name Opt value {}", value);
+
+        assert!(entries
+            .iter()
+            .any(|entry| entry.line == line && entry.job == value));
+    }
+
+    assert!(entries
+        .iter()
+        .any(|entry| entry.line == "position startpos" && entry.job == 4));
+
+    let _ = std::fs::remove_file(&path);
+}
@@ -0,0 +1,78 @@
+use std::time::Duration;
+
+use uciengine::mockengine::*;
+use uciengine::uciengine::*;
+
+#[tokio::test]
+async fn in_memory_duplex() {
+    let engine = MockScript::new().start_engine();
+
+    let go_result = engine.go(GoJob::new().pos_startpos()).await.unwrap();
+
+    assert_eq!(go_result.bestmove, Some("e2e4".to_string()));
+
+    let shutdown_status = engine.shutdown(Duration::from_millis(3000)).await;
+
+    assert!(!shutdown_status.killed);
+    assert!(shutdown_status.exit_status.is_none());
+    assert!(engine.is_terminated());
+}
+
+#[tokio::test]
+async fn tcp_socket() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+
+        let (input, output) = stream.into_split();
+
+        MockScript::new()
+            .first("go", vec![send("bestmove d2d4")])
+            .run_async(input, output)
+            .await
+    });
+
+    let engine = UciEngine::connect_tcp(addr).await.unwrap();
+
+    let go_result = engine.go(GoJob::new().pos_startpos()).await.unwrap();
+
+    assert_eq!(go_result.bestmove, Some("d2d4".to_string()));
+
+    // a hanging connection is closed by kill
+    engine.kill();
+
+    assert!(engine.go(GoJob::new().pos_startpos()).await.is_err());
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn unix_socket() {
+    let path = std::env::temp_dir().join(format!("uciengine-test-{}.sock", std::process::id()));
+
+    let _ = std::fs::remove_file(&path);
+
+    let listener = tokio::net::UnixListener::bind(&path).unwrap();
+
+    tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+
+        let (input, output) = stream.into_split();
+
+        MockScript::new().run_async(input, output).await
+    });
+
+    let engine = UciEngine::connect_unix(&path).await.unwrap();
+
+    let go_result = engine.go(GoJob::new().pos_startpos()).await.unwrap();
+
+    assert_eq!(go_result.ponder, Some("e7e5".to_string()));
+
+    let shutdown_status = engine.shutdown(Duration::from_millis(3000)).await;
+
+    assert!(!shutdown_status.killed);
+
+    let _ = std::fs::remove_file(&path);
+}